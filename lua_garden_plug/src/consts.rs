@@ -19,4 +19,7 @@ pub const MOTD: &str = "Have fun. ♥";
 pub const WINDOW_SIZE_WIDTH: u32 = 640;
 pub const WINDOW_SIZE_HEIGHT: u32 = 512;
 
-pub const DARKMODE_DEFAULT: bool = true;
\ No newline at end of file
+pub const DARKMODE_DEFAULT: bool = true;
+
+pub const GAIN_RANGE_DB: f32 = 30.0;
+pub const PARAMETER_SMOOTHING_MS: f32 = 50.0;
\ No newline at end of file
@@ -0,0 +1,208 @@
+use std::collections::BTreeSet;
+use mlem_egui_themes::Theme;
+use nih_plug_egui::egui::{ text::LayoutJob, Color32, FontId, Stroke, TextFormat };
+use crate::runtime::{ api::{ ApiSymbol, SymbolKind }, lexer::{ self, TokenKind } };
+
+const ERROR_UNDERLINE_WIDTH: f32 = 1.5;
+const WARNING_UNDERLINE_WIDTH: f32 = 1.0;
+const MAX_COMPLETIONS: usize = 8;
+const MIN_COMPLETION_WORD: usize = 2;
+const MIN_IDENTIFIER_LENGTH: usize = 3;
+
+#[derive(Clone, Copy, PartialEq)]
+enum TokenType {
+    Text,
+    Keyword,
+    Global,
+    String,
+    Number,
+    Comment
+}
+
+// A suggestion for the word under the cursor, insert replaces the whole word.
+#[derive(Clone)]
+pub struct Completion {
+    pub label: String,
+    pub insert: String,
+    pub doc: String
+}
+
+struct Highlighter<'a> {
+    job: LayoutJob,
+    font_id: FontId,
+    theme: &'a Theme,
+    error_lines: &'a BTreeSet<usize>,
+    warning_lines: &'a BTreeSet<usize>,
+    line: usize
+}
+
+// Builds a layout job for Lua code, the marked lines are 1-based like Lua's own line numbers.
+pub fn highlight(text: &str, font_id: FontId, theme: &Theme, globals: &BTreeSet<String>, error_lines: &BTreeSet<usize>, warning_lines: &BTreeSet<usize>) -> LayoutJob {
+    let mut highlighter = Highlighter {
+        job: LayoutJob::default(),
+        font_id: font_id,
+        theme: theme,
+        error_lines: error_lines,
+        warning_lines: warning_lines,
+        line: 1
+    };
+
+    let tokens = lexer::tokenize(text);
+    for (index, token) in tokens.iter().enumerate() {
+        let token_type = match token.kind {
+            TokenKind::Keyword => TokenType::Keyword,
+            TokenKind::String => TokenType::String,
+            TokenKind::Number => TokenType::Number,
+            TokenKind::Comment => TokenType::Comment,
+            TokenKind::Name if globals.contains(token.text) && !is_field(&tokens[..index]) => TokenType::Global,
+            _ => TokenType::Text
+        };
+
+        highlighter.append(token.text, token_type);
+    }
+
+    return highlighter.job;
+}
+
+// The name being typed before char_index, like "osc.sa" or "lfo:ru", with the char index it starts at.
+pub fn word_before(text: &str, char_index: usize) -> (usize, String) {
+    let before: Vec<char> = text.chars().take(char_index).collect();
+
+    let mut start = before.len();
+    while start > 0 && is_word_char(before[start - 1]) {
+        start -= 1;
+    }
+
+    let word: String = before[start..].iter().collect();
+
+    // Nothing to complete in comments, numbers or right after a dot like "..".
+    let line: String = before.iter().rev().take_while(|c| **c != '\n').collect();
+    let in_comment = line.contains("--");
+    let starts_badly = word.starts_with(|c: char| c.is_ascii_digit() || c == '.' || c == ':');
+
+    if in_comment || starts_badly {
+        return (char_index, String::new());
+    }
+
+    return (start, word);
+}
+
+// Every name in the code that's long enough to be worth completing.
+pub fn identifiers(text: &str) -> BTreeSet<String> {
+    return lexer::tokenize(text).iter()
+        .filter(|t| t.kind == TokenKind::Name && t.text.len() >= MIN_IDENTIFIER_LENGTH)
+        .map(|t| String::from(t.text))
+        .collect();
+}
+
+pub fn completions(symbols: &[ApiSymbol], locals: &BTreeSet<String>, word: &str) -> Vec<Completion> {
+    let mut found = Vec::new();
+    let qualified = word.contains('.') || word.contains(':');
+
+    if word.chars().count() < MIN_COMPLETION_WORD && !qualified {
+        return found;
+    }
+
+    // lfo:ru -> any method called ru..., whatever lfo is.
+    match word.rsplit_once(':') {
+        Some((receiver, prefix)) => {
+            for symbol in symbols.iter().filter(|s| s.kind == SymbolKind::Method) {
+                let method = symbol.method_name();
+                if method.starts_with(prefix) && method != prefix {
+                    found.push(Completion {
+                        label: symbol.signature.clone(),
+                        insert: format!("{}:{}(", receiver, method),
+                        doc: symbol.doc.clone()
+                    });
+                }
+            }
+        },
+        None => {
+            for symbol in symbols.iter().filter(|s| s.kind != SymbolKind::Method) {
+                // Only one level at a time, "os" offers osc but not osc.sine.
+                let rest = match symbol.name.strip_prefix(word) {
+                    Some(rest) => rest,
+                    None => continue
+                };
+
+                if rest.is_empty() || rest.contains('.') {
+                    continue;
+                }
+
+                let insert = match symbol.kind {
+                    SymbolKind::Function => format!("{}(", symbol.name),
+                    _ => symbol.name.clone()
+                };
+
+                found.push(Completion { label: symbol.signature.clone(), insert: insert, doc: symbol.doc.clone() });
+            }
+
+            if !qualified {
+                for local in locals {
+                    let known = symbols.iter().any(|s| &s.name == local);
+                    if local.starts_with(word) && local != word && !known {
+                        found.push(Completion { label: local.clone(), insert: local.clone(), doc: String::new() });
+                    }
+                }
+            }
+        }
+    }
+
+    found.truncate(MAX_COMPLETIONS);
+    return found;
+}
+
+// Fields like foo.BUFFER or foo:run aren't globals.
+fn is_field(previous_tokens: &[lexer::Token]) -> bool {
+    return match previous_tokens.iter().rev().find(|t| t.kind != TokenKind::Space) {
+        Some(token) => token.is_symbol(".") || token.is_symbol(":"),
+        None => false
+    };
+}
+
+fn is_word_char(c: char) -> bool {
+    return c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == ':';
+}
+
+impl<'a> Highlighter<'a> {
+    fn color(&self, token_type: TokenType) -> Color32 {
+        return match token_type {
+            TokenType::Text => self.theme.f_high,
+            TokenType::Keyword => self.theme.f_med,
+            TokenType::Global => self.theme.b_inv,
+            TokenType::String => self.theme.b_inv,
+            TokenType::Number => self.theme.b_inv,
+            TokenType::Comment => self.theme.f_low
+        };
+    }
+
+    // Splits on new lines so marked lines can be underlined on their own.
+    fn append(&mut self, text: &str, token_type: TokenType) {
+        let color = self.color(token_type);
+
+        for (index, part) in text.split('\n').enumerate() {
+            if index > 0 {
+                self.push("\n", color);
+                self.line += 1;
+            }
+
+            if !part.is_empty() {
+                self.push(part, color);
+            }
+        }
+    }
+
+    fn push(&mut self, text: &str, color: Color32) {
+        let mut format = TextFormat::simple(self.font_id.clone(), color);
+
+        if text != "\n" {
+            if self.error_lines.contains(&self.line) {
+                format.underline = Stroke::new(ERROR_UNDERLINE_WIDTH, self.theme.b_inv);
+            } else if self.warning_lines.contains(&self.line) {
+                format.underline = Stroke::new(WARNING_UNDERLINE_WIDTH, self.theme.f_med);
+            }
+        }
+
+        self.job.append(text, 0.0, format);
+    }
+}
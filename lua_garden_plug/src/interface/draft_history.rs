@@ -0,0 +1,446 @@
+use std::{ collections::VecDeque, time::SystemTime };
+use nih_plug_egui::egui::{ self, Ui };
+use mlem_egui_themes::Theme;
+use crate::runtime::{ module_content::ModuleContent, runtime_data::RuntimeState };
+use super::{ interface_utils, DEFAULT_SPACE };
+
+const MAX_SNAPSHOTS: usize = 64;
+// Past this many line pairs a file is shown as replaced instead of diffed.
+const MAX_DIFF_CELLS: usize = 4_000_000;
+const HISTORY_MENU_WIDTH: f32 = 320.0;
+const HISTORY_DIFF_ID: &str = "Central/DraftEditor/History";
+const CONFIRM_WINDOW_TITLE: &str = "Replace draft?";
+
+pub struct Snapshot {
+    pub label: String,
+    pub time: SystemTime,
+    pub content: ModuleContent
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum DiffLine {
+    Same(String),
+    Removed(String),
+    Added(String)
+}
+
+// Snapshots of the draft, taken on every load and by hand, newest first.
+pub struct DraftHistory {
+    snapshots: VecDeque<Snapshot>,
+    new_snapshot_label: String,
+    // The draft as it was last snapshotted or replaced, anything else is unsaved work.
+    saved_content: Option<ModuleContent>,
+    // Waiting for the user to confirm it may overwrite unsaved work.
+    pending_replacement: Option<(String, ModuleContent)>,
+    // Loaded code, snapshotted once the runtime accepts it.
+    loading: Option<ModuleContent>,
+    viewing: Option<usize>,
+    // (snapshot, draft hash, changed files), diffing every frame would be wasteful.
+    diff_cache: Option<(usize, u64, Vec<(String, Vec<DiffLine>)>)>
+}
+
+impl DraftHistory {
+    pub fn new() -> DraftHistory {
+        Self {
+            snapshots: VecDeque::new(),
+            new_snapshot_label: String::new(),
+            saved_content: None,
+            pending_replacement: None,
+            loading: None,
+            viewing: None,
+            diff_cache: None
+        }
+    }
+
+    pub fn take_snapshot(&mut self, label: &str, content: &ModuleContent) {
+        self.saved_content = Some(content.clone());
+
+        // Loading the same code twice shouldn't fill the history.
+        match self.snapshots.front() {
+            Some(newest) if newest.content == *content => return,
+            _ => ()
+        }
+
+        self.snapshots.push_front(Snapshot {
+            label: String::from(label),
+            time: SystemTime::now(),
+            content: content.clone()
+        });
+
+        if self.snapshots.len() > MAX_SNAPSHOTS {
+            self.snapshots.pop_back();
+        }
+
+        // Indices moved.
+        self.viewing = self.viewing.map(|index| index + 1).filter(|index| *index < self.snapshots.len());
+        self.diff_cache = None;
+    }
+
+    pub fn expect_load(&mut self, draft: &ModuleContent) {
+        self.loading = Some(draft.clone());
+    }
+
+    // Call every frame, takes the load snapshot when the module came online.
+    pub fn update_load(&mut self, state: &RuntimeState) {
+        match state {
+            RuntimeState::Refresh => (),
+            RuntimeState::Online => match self.loading.take() {
+                Some(content) => self.take_snapshot("Load", &content),
+                None => ()
+            },
+            _ => self.loading = None
+        }
+    }
+
+    pub fn has_unsaved_changes(&self, draft: &ModuleContent) -> bool {
+        return match &self.saved_content {
+            Some(saved) => saved != draft,
+            None => false
+        };
+    }
+
+    // Replaces the draft, asking first when that would lose unsaved work.
+    pub fn replace_draft(&mut self, label: &str, content: ModuleContent, draft: &mut ModuleContent) {
+        if self.has_unsaved_changes(draft) {
+            self.pending_replacement = Some((String::from(label), content));
+            return;
+        }
+
+        self.saved_content = Some(content.clone());
+        *draft = content;
+    }
+
+    pub fn is_viewing(&self) -> bool {
+        return self.viewing.is_some();
+    }
+
+    // Call every frame, the draft starts out saved.
+    pub fn track(&mut self, draft: &ModuleContent) {
+        if self.saved_content.is_none() {
+            self.saved_content = Some(draft.clone());
+        }
+    }
+
+    pub fn draw_menu(&mut self, ui: &mut Ui, draft: &mut ModuleContent) {
+        ui.menu_button("History", |ui| {
+            ui.set_min_width(HISTORY_MENU_WIDTH);
+
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut self.new_snapshot_label);
+                if ui.button("Snapshot").on_hover_text("Keep the draft as it is now.").clicked() {
+                    let label = match self.new_snapshot_label.trim() {
+                        "" => String::from("Snapshot"),
+                        label => String::from(label)
+                    };
+
+                    self.take_snapshot(&label, draft);
+                    self.new_snapshot_label.clear();
+                }
+            });
+
+            ui.separator();
+
+            if self.snapshots.is_empty() {
+                ui.label("No snapshots yet, loading the draft takes one.");
+                return;
+            }
+
+            let mut restored = None;
+            egui::Grid::new(HISTORY_DIFF_ID)
+                .num_columns(3)
+                .spacing([DEFAULT_SPACE * 2.0, DEFAULT_SPACE])
+                .show(ui, |ui| {
+                    for (index, snapshot) in self.snapshots.iter().enumerate() {
+                        ui.vertical(|ui| {
+                            ui.label(&snapshot.label);
+                            ui.weak(interface_utils::format_age(snapshot.time));
+                        });
+
+                        if ui.selectable_label(self.viewing == Some(index), "Diff").on_hover_text("Compare with the draft.").clicked() {
+                            self.viewing = Some(index);
+                            ui.close_menu();
+                        }
+
+                        if ui.button("Restore").clicked() {
+                            restored = Some(index);
+                            ui.close_menu();
+                        }
+
+                        ui.end_row();
+                    }
+                });
+
+            match restored {
+                Some(index) => self.restore(index, draft),
+                None => ()
+            }
+        });
+    }
+
+    // Asks before an example or the empty module overwrites unsaved work.
+    pub fn draw_confirmation(&mut self, ctx: &egui::Context, draft: &mut ModuleContent) {
+        let label = match &self.pending_replacement {
+            Some((label, _)) => label.clone(),
+            None => return
+        };
+
+        let mut choice = None;
+        egui::Window::new(CONFIRM_WINDOW_TITLE)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(format!("The draft has changes that aren't in any snapshot. Replace it with \"{}\"?", label));
+                ui.add_space(DEFAULT_SPACE);
+
+                ui.horizontal(|ui| {
+                    if ui.button("Snapshot and replace").clicked() {
+                        choice = Some(true);
+                    }
+                    if ui.button("Replace").clicked() {
+                        choice = Some(false);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.pending_replacement = None;
+                    }
+                });
+            });
+
+        match (choice, self.pending_replacement.take()) {
+            (Some(snapshot_first), Some((_, content))) => {
+                if snapshot_first {
+                    self.take_snapshot(&format!("Before {}", label), draft);
+                }
+
+                self.saved_content = Some(content.clone());
+                *draft = content;
+            },
+            (None, pending) => self.pending_replacement = pending,
+            _ => ()
+        }
+    }
+
+    // Shown instead of the editor while a snapshot is being compared.
+    pub fn draw_diff(&mut self, ui: &mut Ui, theme: &Theme, draft: &mut ModuleContent, height: f32) {
+        let index = match self.viewing {
+            Some(index) if index < self.snapshots.len() => index,
+            _ => {
+                self.viewing = None;
+                return;
+            }
+        };
+
+        let draft_hash = draft.generate_hash();
+        let cached = match &self.diff_cache {
+            Some((cached_index, cached_hash, _)) => *cached_index == index && *cached_hash == draft_hash,
+            None => false
+        };
+
+        if !cached {
+            self.diff_cache = Some((index, draft_hash, diff_content(&self.snapshots[index].content, draft)));
+        }
+
+        let mut restore = false;
+        ui.horizontal(|ui| {
+            let snapshot = &self.snapshots[index];
+            ui.label(format!("Changes from \"{}\" ({}) to the draft.", snapshot.label, interface_utils::format_age(snapshot.time)));
+
+            if ui.button("Restore").clicked() {
+                restore = true;
+            }
+            if ui.button("Close").clicked() {
+                self.viewing = None;
+            }
+        });
+
+        ui.separator();
+
+        let files = match &self.diff_cache {
+            Some((_, _, files)) => files,
+            None => return
+        };
+
+        egui::ScrollArea::vertical().id_salt(HISTORY_DIFF_ID).max_height(height).show(ui, |ui| {
+            if files.is_empty() {
+                ui.label("No changes.");
+            }
+
+            for (path, lines) in files {
+                ui.strong(path);
+
+                for line in lines {
+                    let (prefix, text, color) = match line {
+                        DiffLine::Same(text) => ("  ", text, theme.f_low),
+                        DiffLine::Removed(text) => ("- ", text, theme.f_med),
+                        DiffLine::Added(text) => ("+ ", text, theme.b_inv)
+                    };
+
+                    ui.label(egui::RichText::new(format!("{}{}", prefix, text)).monospace().color(color));
+                }
+
+                ui.add_space(DEFAULT_SPACE * 2.0);
+            }
+        });
+
+        if restore {
+            self.restore(index, draft);
+        }
+    }
+
+    fn restore(&mut self, index: usize, draft: &mut ModuleContent) {
+        let content = self.snapshots[index].content.clone();
+        let label = self.snapshots[index].label.clone();
+
+        // Restoring is undoable, whatever was unsaved becomes a snapshot.
+        if self.has_unsaved_changes(draft) {
+            self.take_snapshot(&format!("Before restoring {}", label), draft);
+        }
+
+        self.saved_content = Some(content.clone());
+        *draft = content;
+        self.viewing = None;
+    }
+}
+
+// Changed files only, as (path, lines).
+fn diff_content(old: &ModuleContent, new: &ModuleContent) -> Vec<(String, Vec<DiffLine>)> {
+    let old_scripts = old.all_scripts();
+    let new_scripts = new.all_scripts();
+    let mut files = Vec::new();
+
+    for (path, new_code) in &new_scripts {
+        let old_code = old_scripts.iter().find(|(p, _)| p == path).map_or("", |(_, code)| *code);
+        if old_code != *new_code {
+            files.push((String::from(*path), diff_lines(old_code, new_code)));
+        }
+    }
+
+    // Files the draft no longer has.
+    for (path, old_code) in &old_scripts {
+        if !new_scripts.iter().any(|(p, _)| p == path) {
+            files.push((String::from(*path), diff_lines(old_code, "")));
+        }
+    }
+
+    return files;
+}
+
+// Line diff through the longest common subsequence.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    if old.len() * new.len() > MAX_DIFF_CELLS {
+        return old.iter().map(|l| DiffLine::Removed(String::from(*l)))
+            .chain(new.iter().map(|l| DiffLine::Added(String::from(*l))))
+            .collect();
+    }
+
+    // common[i][j] is the LCS length of old[i..] and new[j..].
+    let width = new.len() + 1;
+    let mut common = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i * width + j] = if old[i] == new[j] {
+                common[(i + 1) * width + j + 1] + 1
+            } else {
+                common[(i + 1) * width + j].max(common[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(DiffLine::Same(String::from(old[i])));
+            i += 1;
+            j += 1;
+        } else if common[(i + 1) * width + j] >= common[i * width + j + 1] {
+            lines.push(DiffLine::Removed(String::from(old[i])));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(String::from(new[j])));
+            j += 1;
+        }
+    }
+
+    lines.extend(old[i..].iter().map(|l| DiffLine::Removed(String::from(*l))));
+    lines.extend(new[j..].iter().map(|l| DiffLine::Added(String::from(*l))));
+
+    return lines;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn content(run: &str) -> ModuleContent {
+        return ModuleContent::new(String::new(), String::new(), String::new(), String::from(run), String::new());
+    }
+
+    #[test]
+    fn diffs_changed_lines() {
+        let lines = diff_lines("a\nb\nc", "a\nx\nc\nd");
+        assert_eq!(lines, vec![
+            DiffLine::Same(String::from("a")),
+            DiffLine::Removed(String::from("b")),
+            DiffLine::Added(String::from("x")),
+            DiffLine::Same(String::from("c")),
+            DiffLine::Added(String::from("d"))
+        ]);
+    }
+
+    #[test]
+    fn identical_snapshots_are_skipped() {
+        let mut history = DraftHistory::new();
+        history.take_snapshot("Load", &content("a"));
+        history.take_snapshot("Load", &content("a"));
+        history.take_snapshot("Load", &content("b"));
+
+        assert_eq!(history.snapshots.len(), 2);
+        assert!(history.snapshots[0].content == content("b"));
+    }
+
+    #[test]
+    fn failed_loads_are_not_kept() {
+        let mut history = DraftHistory::new();
+        history.expect_load(&content("broken"));
+        history.update_load(&RuntimeState::Refresh);
+        history.update_load(&RuntimeState::Offline);
+        assert!(history.snapshots.is_empty());
+
+        history.expect_load(&content("working"));
+        history.update_load(&RuntimeState::Online);
+        assert_eq!(history.snapshots.len(), 1);
+    }
+
+    #[test]
+    fn replacing_unsaved_work_waits() {
+        let mut history = DraftHistory::new();
+        let mut draft = content("a");
+        history.track(&draft);
+
+        history.replace_draft("Example", content("b"), &mut draft);
+        assert!(draft == content("b"));
+
+        draft.run.push_str(" edited");
+        history.replace_draft("Example", content("c"), &mut draft);
+        assert!(draft == content("b edited"));
+        assert!(history.pending_replacement.is_some());
+    }
+
+    #[test]
+    fn restoring_keeps_unsaved_work() {
+        let mut history = DraftHistory::new();
+        let mut draft = content("a");
+        history.take_snapshot("Load", &draft);
+
+        draft.run = String::from("b");
+        history.restore(0, &mut draft);
+
+        assert!(draft == content("a"));
+        assert_eq!(history.snapshots.len(), 2);
+        assert!(history.snapshots[0].content == content("b"));
+    }
+}
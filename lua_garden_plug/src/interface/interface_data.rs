@@ -1,20 +1,33 @@
 use std::collections::BTreeMap;
 
 use super::InterfaceMode;
-use crate::{ runtime::{library, module_content::ModuleContent, parameter::Parameter, runtime_data::RuntimeState, workspace::Workspace}, RuntimeData };
+use crate::{ runtime::{library, module_content::ModuleContent, parameter::Parameter, rack::{ RackSlot, RackSource }, runtime_data::RuntimeState, workspace::Workspace}, RuntimeData };
 
+pub const DEFAULT_CROSSFADE_MS: f32 = 20.0;
 
 #[derive(Clone)]
 pub struct InterfaceData {
     pub mode: InterfaceMode,
     pub workspace: Option<Workspace>,
     pub draft_content: ModuleContent,
+    pub rack: Vec<RackSlot>,
+    pub user_libraries: BTreeMap<String, String>,
 
     pub runtime_target_state: RuntimeState,
+    // The load the runtime waits for while refreshing.
+    pub runtime_load_id: u64,
     pub runtime_clip: bool,
     pub runtime_input_noise: bool,
+    // How long loading, PANIC and failures take to fade from the old output to the new.
+    pub runtime_crossfade_ms: f32,
+    // Stop the module when its output goes bad, instead of muting it.
+    pub runtime_safety_stop: bool,
+    // Profile while the profiler is open, sample Lua functions when asked to.
+    pub runtime_profiling: bool,
+    pub runtime_sampling: bool,
 
-    pub parameters: BTreeMap<String, Parameter>,
+    // Per rack slot, drafts and workspaces only have the one.
+    pub parameters: Vec<BTreeMap<String, Parameter>>,
 
     pub change: u32,
     last_runtime_change: u32
@@ -26,12 +39,19 @@ impl InterfaceData {
             mode: InterfaceMode::Draft,
             workspace: None,
             draft_content: library::MODULE_EXAMPLES[0].0.to_module_content(),
+            rack: Vec::new(),
+            user_libraries: BTreeMap::new(),
 
             runtime_target_state: RuntimeState::Offline,
+            runtime_load_id: 0,
             runtime_clip: true,
             runtime_input_noise: false,
+            runtime_crossfade_ms: DEFAULT_CROSSFADE_MS,
+            runtime_safety_stop: false,
+            runtime_profiling: false,
+            runtime_sampling: false,
 
-            parameters: BTreeMap::new(),
+            parameters: Vec::new(),
 
             change: 0,
             last_runtime_change: 0
@@ -47,11 +67,40 @@ impl InterfaceData {
         self.last_runtime_change = runtime_data.change;
     }
 
+    // The modules to load for the current mode, user libraries included.
+    pub fn runtime_slots(&self) -> Vec<RackSlot> {
+        let mut slots = match (&self.mode, &self.workspace) {
+            (InterfaceMode::Draft, _) => vec![RackSlot::new(RackSource::Draft(self.draft_content.clone()))],
+            (InterfaceMode::Workspace, Some(workspace)) => vec![RackSlot::new(RackSource::Workspace(workspace.clone()))],
+            (InterfaceMode::Workspace, None) => Vec::new(),
+            (InterfaceMode::Rack, _) => self.rack.clone()
+        };
+
+        for slot in &mut slots {
+            slot.content_mut().add_libraries(&self.user_libraries);
+        }
+
+        return slots;
+    }
+
+    // Only the rack can be rearranged while running.
+    pub fn rack_order(&self) -> Vec<(u64, bool)> {
+        return match self.mode {
+            InterfaceMode::Rack => self.rack.iter().map(|slot| (slot.id, slot.bypassed)).collect(),
+            _ => Vec::new()
+        };
+    }
+
     pub fn set_runtime_target_state(&mut self, runtime_target_state: RuntimeState) {
         self.runtime_target_state = runtime_target_state;
         self.mark_changed();
     }
 
+    pub fn set_runtime_load(&mut self, runtime_load_id: u64) {
+        self.runtime_load_id = runtime_load_id;
+        self.set_runtime_target_state(RuntimeState::Refresh);
+    }
+
     pub fn set_runtime_clip(&mut self, runtime_clip: bool) {
         self.runtime_clip = runtime_clip;
         self.mark_changed();
@@ -62,6 +111,26 @@ impl InterfaceData {
         self.mark_changed();
     }
 
+    pub fn set_runtime_crossfade_ms(&mut self, runtime_crossfade_ms: f32) {
+        self.runtime_crossfade_ms = runtime_crossfade_ms;
+        self.mark_changed();
+    }
+
+    pub fn set_runtime_safety_stop(&mut self, runtime_safety_stop: bool) {
+        self.runtime_safety_stop = runtime_safety_stop;
+        self.mark_changed();
+    }
+
+    pub fn set_runtime_profiling(&mut self, runtime_profiling: bool) {
+        self.runtime_profiling = runtime_profiling;
+        self.mark_changed();
+    }
+
+    pub fn set_runtime_sampling(&mut self, runtime_sampling: bool) {
+        self.runtime_sampling = runtime_sampling;
+        self.mark_changed();
+    }
+
     pub fn mark_changed(&mut self) {
         self.change = self.change + 1;
     }
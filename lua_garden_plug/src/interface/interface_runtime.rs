@@ -1,13 +1,27 @@
-use super::{interface_module::InterfaceModule, DEFAULT_SPACE};
+use std::{ collections::BTreeMap, sync::RwLock };
+use super::{interface_module::InterfaceModule, telemetry_view, InterfaceMode, DEFAULT_SPACE};
 
 use nih_plug_egui::egui::{self, Ui} ;
-use crate::{ runtime::{ parameter::Parameter, runtime_data::RuntimeData }, InterfaceData };
+use crate::{ runtime::{ parameter::Parameter, preset::{ self, Preset }, runtime_data::RuntimeData, telemetry::TelemetryReceiver }, ConsoleReceiver, InterfaceData };
 
 const PARAMETER_GRID_ID: &str = "Central/Parameters";
+const PRESET_COMBO_ID: &str = "Central/Parameters/Presets";
+const PRESET_COMBO_WIDTH: f32 = 160.0;
+const AB_SLOT_NAMES: [&str; 2] = ["A", "B"];
 
 pub struct InterfaceRuntime {
     pub module: Option<InterfaceModule>,
-    pub view: InterfaceRuntimeView
+    pub view: InterfaceRuntimeView,
+    // Which module of a rack the parameters are shown for.
+    selected_module: usize,
+
+    selected_preset: Option<String>,
+    new_preset_name: String,
+    // Presets of the open workspace, read again when another workspace is opened.
+    workspace_presets: BTreeMap<String, Preset>,
+    workspace_presets_path: Option<String>,
+    ab_slots: [Preset; 2],
+    active_slot: usize
 }
 
 #[derive(PartialEq)]
@@ -20,28 +34,80 @@ impl InterfaceRuntime {
     pub fn new() -> InterfaceRuntime {
         Self {
             module: None,
-            view: InterfaceRuntimeView::Interface
+            view: InterfaceRuntimeView::Interface,
+            selected_module: 0,
+
+            selected_preset: None,
+            new_preset_name: String::new(),
+            workspace_presets: BTreeMap::new(),
+            workspace_presets_path: None,
+            ab_slots: [Preset::default(), Preset::default()],
+            active_slot: 0
         }
     }
 
-    pub fn draw(&mut self, ui: &mut Ui, runtime_data: &RuntimeData, interface_data: &mut InterfaceData) {
+    pub fn draw(&mut self, ui: &mut Ui, runtime_data: &RuntimeData, interface_data: &mut InterfaceData, draft_presets: &RwLock<BTreeMap<String, Preset>>, console: &mut ConsoleReceiver, telemetry: &TelemetryReceiver) {
         match self.view {
             InterfaceRuntimeView::Interface => {
-                // TODO
-                ui.label("To be implemented.");
+                telemetry_view::draw(ui, runtime_data, telemetry);
             },
             InterfaceRuntimeView::Parameters => {
-                self.draw_parameters(ui, runtime_data, interface_data);
+                self.draw_parameters(ui, runtime_data, interface_data, draft_presets, console);
             }
         }
     }
 
-    pub fn draw_parameters(&mut self, ui: &mut Ui, runtime_data: &RuntimeData, interface_data: &mut InterfaceData) {
+    pub fn draw_parameters(&mut self, ui: &mut Ui, runtime_data: &RuntimeData, interface_data: &mut InterfaceData, draft_presets: &RwLock<BTreeMap<String, Preset>>, console: &mut ConsoleReceiver) {
         ui.label("This section is experimental and may impact performance.");
         ui.separator();
+
+        if runtime_data.slots.len() > 1 {
+            ui.horizontal(|ui| {
+                ui.label("Module");
+                for (index, slot) in runtime_data.slots.iter().enumerate() {
+                    if ui.selectable_label(self.selected_module == index, &slot.name).clicked() && self.selected_module != index {
+                        // Presets and A/B belong to one module.
+                        self.selected_module = index;
+                        self.selected_preset = None;
+                        self.ab_slots = [Preset::default(), Preset::default()];
+                        self.active_slot = 0;
+                    }
+                }
+            });
+            ui.separator();
+        }
+
+        if self.selected_module >= interface_data.parameters.len() {
+            self.selected_module = 0;
+        }
+
+        let module_name = match runtime_data.slots.get(self.selected_module) {
+            Some(slot) if runtime_data.slots.len() > 1 => slot.name.clone(),
+            _ => runtime_data.module_name.clone()
+        };
+
+        // Workspaces keep presets in their folder, drafts in the plugin state.
+        let workspace_path = match (&interface_data.mode, &interface_data.workspace) {
+            (InterfaceMode::Workspace, Some(workspace)) => Some(workspace.path.clone()),
+            (InterfaceMode::Rack, _) => runtime_data.slots.get(self.selected_module)
+                .and_then(|status| interface_data.rack.iter().find(|slot| slot.id == status.id))
+                .and_then(|slot| slot.workspace_path()),
+            _ => None
+        };
+
+        let parameters = match interface_data.parameters.get_mut(self.selected_module) {
+            Some(parameters) => parameters,
+            None => {
+                ui.label("No module loaded.");
+                return;
+            }
+        };
+
+        let recalled = self.draw_presets(ui, parameters, workspace_path, draft_presets, console);
+        ui.separator();
         ui.label(format!("Module \"{name}\" has {parameter_count} parameter(s):", 
-            name = runtime_data.module_name, 
-            parameter_count = interface_data.parameters.len()));
+            name = module_name, 
+            parameter_count = parameters.len()));
 
         let mut changed = false;
         egui::ScrollArea::vertical()
@@ -50,7 +116,7 @@ impl InterfaceRuntime {
                     .num_columns(2)
                     .spacing([DEFAULT_SPACE * 4.0, DEFAULT_SPACE])
                     .show(ui, |ui| {
-                    for parameter in &mut interface_data.parameters {
+                    for parameter in parameters.iter_mut() {
                         self.draw_parameter(ui, parameter.1);
 
                         if parameter.1.changed {
@@ -60,11 +126,156 @@ impl InterfaceRuntime {
                 });
         });
 
+        // Applied after drawing, drawing a parameter clears its changed flag.
+        match recalled {
+            Some(preset) => {
+                preset.apply(parameters);
+                changed = true;
+            },
+            None => ()
+        }
+
         if changed {
             interface_data.mark_changed();
         }
     }
 
+    // Save, load and delete presets and switch between A/B, returns the values to recall.
+    fn draw_presets(&mut self, ui: &mut Ui, parameters: &BTreeMap<String, Parameter>, workspace_path: Option<String>, draft_presets: &RwLock<BTreeMap<String, Preset>>, console: &mut ConsoleReceiver) -> Option<Preset> {
+        // Another workspace was opened.
+        if workspace_path.is_some() && workspace_path != self.workspace_presets_path {
+            self.workspace_presets_path = workspace_path.clone();
+            self.selected_preset = None;
+            self.reload_workspace_presets(console);
+        }
+
+        let presets = match &workspace_path {
+            Some(_) => self.workspace_presets.clone(),
+            None => draft_presets.read().unwrap().clone()
+        };
+
+        let mut recalled = None;
+
+        ui.horizontal(|ui| {
+            ui.label("Preset");
+
+            let selected_text = self.selected_preset.clone().unwrap_or_default();
+            egui::ComboBox::from_id_salt(PRESET_COMBO_ID)
+                .width(PRESET_COMBO_WIDTH)
+                .selected_text(selected_text)
+                .show_ui(ui, |ui| {
+                    for (name, preset) in &presets {
+                        if ui.selectable_label(self.selected_preset.as_ref() == Some(name), name).clicked() {
+                            self.selected_preset = Some(name.clone());
+                            recalled = Some(preset.clone());
+                        }
+                    }
+                });
+
+            match self.selected_preset.clone() {
+                Some(name) => {
+                    if ui.button("Save").on_hover_text(format!("Overwrite \"{}\" with the current values.", name)).clicked() {
+                        self.save_preset(&name, parameters, &workspace_path, draft_presets, console);
+                    }
+
+                    if ui.button("Delete").clicked() {
+                        self.delete_preset(&name, &workspace_path, draft_presets, console);
+                    }
+                },
+                None => ()
+            }
+
+            ui.separator();
+            ui.text_edit_singleline(&mut self.new_preset_name);
+            if ui.button("Save as").clicked() {
+                match preset::validate_name(&self.new_preset_name) {
+                    Ok(name) => {
+                        self.save_preset(&name, parameters, &workspace_path, draft_presets, console);
+                        self.selected_preset = Some(name);
+                        self.new_preset_name.clear();
+                    },
+                    Err(e) => console.error(e)
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Compare");
+
+            for slot in 0..AB_SLOT_NAMES.len() {
+                if ui.selectable_label(self.active_slot == slot, AB_SLOT_NAMES[slot]).clicked() && self.active_slot != slot {
+                    // Keep what we had, then recall what the other slot had.
+                    self.ab_slots[self.active_slot] = Preset::from_parameters(parameters);
+                    self.active_slot = slot;
+
+                    if !self.ab_slots[slot].values.is_empty() {
+                        recalled = Some(self.ab_slots[slot].clone());
+                    }
+                }
+            }
+
+            if ui.button("Copy A to B").clicked() {
+                let a = match self.active_slot {
+                    0 => Preset::from_parameters(parameters),
+                    _ => self.ab_slots[0].clone()
+                };
+
+                self.ab_slots[1] = a.clone();
+                if self.active_slot == 1 {
+                    recalled = Some(a);
+                }
+            }
+        });
+
+        return recalled;
+    }
+
+    fn save_preset(&mut self, name: &str, parameters: &BTreeMap<String, Parameter>, workspace_path: &Option<String>, draft_presets: &RwLock<BTreeMap<String, Preset>>, console: &mut ConsoleReceiver) {
+        let preset = Preset::from_parameters(parameters);
+
+        match workspace_path {
+            Some(path) => {
+                match preset.write(path, name) {
+                    Ok(_) => (),
+                    Err(e) => console.error(e)
+                }
+                self.reload_workspace_presets(console);
+            },
+            None => {
+                draft_presets.write().unwrap().insert(String::from(name), preset);
+            }
+        }
+    }
+
+    fn delete_preset(&mut self, name: &str, workspace_path: &Option<String>, draft_presets: &RwLock<BTreeMap<String, Preset>>, console: &mut ConsoleReceiver) {
+        match workspace_path {
+            Some(path) => {
+                match Preset::delete(path, name) {
+                    Ok(_) => (),
+                    Err(e) => console.error(e)
+                }
+                self.reload_workspace_presets(console);
+            },
+            None => {
+                draft_presets.write().unwrap().remove(name);
+            }
+        }
+
+        self.selected_preset = None;
+    }
+
+    fn reload_workspace_presets(&mut self, console: &mut ConsoleReceiver) {
+        let path = match &self.workspace_presets_path {
+            Some(path) => path.clone(),
+            None => return
+        };
+
+        match Preset::read_all(&path) {
+            Ok(presets) => self.workspace_presets = presets,
+            Err(e) => console.error(e)
+        }
+    }
+
     fn draw_parameter(&mut self, ui: &mut Ui, parameter: &mut Parameter) {
         ui.label(&parameter.name);
         parameter.draw(ui);
@@ -3,14 +3,27 @@ pub mod interface_utils;
 pub mod interface_module;
 pub mod interface_runtime;
 pub mod parameter;
-
-use std::{ hash::Hash, sync::{ Arc, RwLock } };
+pub mod code_editor;
+pub mod script_checker;
+pub mod workspace_browser;
+pub mod draft_history;
+pub mod rack_editor;
+pub mod profiler_view;
+pub mod telemetry_view;
+
+use std::{ collections::BTreeSet, hash::Hash, sync::{ Arc, Mutex, RwLock } };
 use interface_runtime::{InterfaceRuntime, InterfaceRuntimeView};
 use mlem_egui_themes::Theme;
 use nih_plug::prelude::*;
-use nih_plug_egui::{ egui::{ self, Context, Ui }, EguiState };
+use nih_plug_egui::{ egui::{ self, Context, Ui }, widgets, EguiState };
 use interface_data::InterfaceData;
-use crate::{ consts, ConsoleReceiver, runtime::{library, workspace::Workspace}, LuaGardenParams, runtime::runtime_data::RuntimeState, RuntimeData };
+use code_editor::Completion;
+use script_checker::ScriptChecker;
+use workspace_browser::WorkspaceBrowser;
+use draft_history::DraftHistory;
+use rack_editor::RackEditor;
+use crate::{ consts, ConsoleReceiver, runtime::{api::{self, ApiSymbol}, bundle, errors::ScriptError, library, loader::{ RackHandoff, RackLoader }, rack::RackSource, repl::{ Repl, ReplCommand }, telemetry::TelemetryReceiver, workspace::Workspace}, LuaGardenParams, runtime::runtime_data::RuntimeState, RuntimeData };
+use mlem_console::{ self as console, ConsoleFilter, ConsoleView, LogLevel, LogSource };
 
 const DEFAULT_SPACE: f32 = 4.0;
 const TOP_ID: &str = "Top";
@@ -19,23 +32,62 @@ const DEFAULT_MENU_WIDTH: f32 = 128.0;
 const ABOUT_MENU_WIDTH: f32 = 320.0;
 const ABOUT_LICENSE_SCROLL_HEIGHT: f32 = 320.0;
 const CONSOLE_MAIN_ID: &str = "Central/Console/Main";
-const CONSOLE_HEIGHT: f32 = 128.0;
+const CONSOLE_HEIGHT: f32 = 160.0;
+const CONSOLE_LEVEL_ID: &str = "Central/Console/Level";
+const CONSOLE_LEVEL_WIDTH: f32 = 56.0;
+const CONSOLE_SEARCH_WIDTH: f32 = 160.0;
+const CONSOLE_EXPORT_NAME: &str = "console.txt";
 const CONSOLE_PREVIEW_CHARS: usize = 40;
+const CONSOLE_REPL_SLOT_ID: &str = "Central/Console/ReplSlot";
+const CONSOLE_REPL_SLOT_WIDTH: f32 = 120.0;
+const CONSOLE_REPL_HISTORY: usize = 64;
 const DRAFT_EDITOR_ID: &str = "Central/DraftEditor";
 const BAR_HEIGHT: f32 = 20.0;
 const LOAD_BUTTON_WIDTH: f32 = 64.0;
+const SIGNAL_SLIDER_WIDTH: f32 = 56.0;
+const MAX_CROSSFADE_MS: f32 = 500.0;
+const COMPLETION_ID: &str = "Central/DraftEditor/Completion";
+const COMPLETION_WIDTH: f32 = 320.0;
+const DRAFT_BUNDLE_NAME: &str = "Draft";
 
 pub struct Interface {
     pub console: ConsoleReceiver,
+    console_filter: ConsoleFilter,
+    console_view: ConsoleView,
+    console_export_path: String,
+    // Shared with the audio thread, which runs the snippets between blocks.
+    pub repl: Arc<Mutex<Repl>>,
+    repl_code: String,
+    repl_history: Vec<String>,
+    repl_history_index: usize,
+    repl_slot: Option<u64>,
+    // What modules publish with runtime.meter and runtime.watch.
+    pub telemetry: TelemetryReceiver,
+    // Builds what Load loads, off the audio thread.
+    loader: RackLoader,
 
     show_create_workspace: bool,
     show_open_workspace: bool,
     center_view: CenterView,
     show_console: bool,
     draft_code_selection: RuntimeCode,
+    new_library_name: String,
+    draft_history: DraftHistory,
+
+    api_symbols: Vec<ApiSymbol>,
+    api_globals: BTreeSet<String>,
+    completions: Vec<Completion>,
+    completion_index: usize,
+    completion_start: usize,
+    completion_cursor: usize,
+    completion_dismissed: Option<usize>,
+    script_checker: ScriptChecker,
 
     create_workspace_path: String,
     open_workspace_path: String,
+    workspace_browser: WorkspaceBrowser,
+    rack_editor: RackEditor,
+    bundle_path: String,
 
     interface_runtime: InterfaceRuntime,
 
@@ -46,13 +98,16 @@ pub struct Interface {
 #[derive(PartialEq)]
 pub enum CenterView {
     Code,
-    Interface
+    Interface,
+    Profiler
 }
 
 #[derive(PartialEq, Clone)]
 pub enum InterfaceMode {
     Draft,
-    Workspace
+    Workspace,
+    // Several drafts and workspaces in series.
+    Rack
 }
 
 #[derive(PartialEq)]
@@ -61,22 +116,52 @@ pub enum RuntimeCode {
     Reset,
     Run,
     Trigger,
-    Interface
+    Interface,
+    Library(String)
 }
 
 impl Interface {
-    pub fn new() -> Interface {
+    pub fn new(handoff: Arc<RackHandoff>) -> Interface {
+        let api_symbols = api::registry();
+        let api_globals = api::global_names(&api_symbols);
+        let mut console = ConsoleReceiver::new();
+        let loader = RackLoader::new(handoff, Some(console.create_sender()));
+
         return Self {
-            console: ConsoleReceiver::new(),
+            console: console,
+            console_filter: ConsoleFilter::new(),
+            console_view: ConsoleView::new(),
+            console_export_path: format!("{}/{}", library::default_workspaces_path(), CONSOLE_EXPORT_NAME),
+            repl: Arc::new(Mutex::new(Repl::new())),
+            repl_code: String::new(),
+            repl_history: Vec::new(),
+            repl_history_index: 0,
+            repl_slot: None,
+            telemetry: TelemetryReceiver::new(),
+            loader: loader,
 
             show_create_workspace: false,
             show_open_workspace: false,
             center_view: CenterView::Code,
             show_console: true,
             draft_code_selection: RuntimeCode::Run,
+            new_library_name: String::new(),
+            draft_history: DraftHistory::new(),
+
+            script_checker: ScriptChecker::new(api_globals.clone()),
+            api_symbols: api_symbols,
+            api_globals: api_globals,
+            completions: Vec::new(),
+            completion_index: 0,
+            completion_start: 0,
+            completion_cursor: 0,
+            completion_dismissed: None,
 
             create_workspace_path: library::default_workspaces_path(),
             open_workspace_path: library::default_workspaces_path(),
+            workspace_browser: WorkspaceBrowser::new(),
+            rack_editor: RackEditor::new(),
+            bundle_path: format!("{}/{}.{}", library::default_workspaces_path(), DRAFT_BUNDLE_NAME, bundle::BUNDLE_EXTENSION),
 
             interface_runtime: InterfaceRuntime::new(),
 
@@ -130,11 +215,13 @@ impl Interface {
         self.console.log(format!("{}", consts::MOTD));
     }
     
-    fn draw_interface(&mut self, egui_ctx: &Context, _setter: &ParamSetter, _state: &mut (), _params: Arc<LuaGardenParams>, runtime_data: Arc<RwLock<RuntimeData>>, interface_data: Arc<RwLock<InterfaceData>>) {    
+    fn draw_interface(&mut self, egui_ctx: &Context, setter: &ParamSetter, _state: &mut (), params: Arc<LuaGardenParams>, runtime_data: Arc<RwLock<RuntimeData>>, interface_data: Arc<RwLock<InterfaceData>>) {    
         let runtime_data = runtime_data.read().unwrap().clone();
         let mut interface_data = interface_data.write().unwrap();
         
         interface_data.update_from_runtime(&runtime_data);
+        self.draft_history.update_load(&interface_data.runtime_target_state);
+        self.telemetry.update();
 
         egui::TopBottomPanel::top(TOP_ID).show(egui_ctx, |ui| {
             ui.horizontal(|ui| {
@@ -144,6 +231,7 @@ impl Interface {
                 self.draw_center_view_selection(ui, &mut interface_data);
                 ui.separator();
                 self.draw_modules_menu(ui, &mut interface_data);
+                self.draw_bundle_menu(ui, &mut interface_data);
     
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Max), |ui| {
                     self.draw_panic_button(ui, &runtime_data, &mut interface_data);
@@ -164,11 +252,17 @@ impl Interface {
                         },
                         InterfaceMode::Workspace => {
                             self.draw_workspace_editor(ui, &runtime_data, &mut interface_data);
+                        },
+                        InterfaceMode::Rack => {
+                            self.draw_rack_editor(ui, &runtime_data, &mut interface_data);
                         }
                     }
                 },
                 CenterView::Interface => {
-                    self.draw_module_interface(ui, &runtime_data, &mut interface_data);
+                    self.draw_module_interface(ui, &runtime_data, &mut interface_data, &params);
+                },
+                CenterView::Profiler => {
+                    profiler_view::draw(ui, &runtime_data, &mut interface_data);
                 }
             }
 
@@ -182,10 +276,18 @@ impl Interface {
                 self.draw_console_toggle(ui);
 
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Max), |ui| {
-                    self.draw_signal_flow(ui, &runtime_data, &mut interface_data);
+                    self.draw_signal_flow(ui, setter, &params, &runtime_data, &mut interface_data);
                 });
             });
         });
+
+        self.draft_history.draw_confirmation(egui_ctx, &mut interface_data.draft_content);
+
+        // The runtime only profiles while the profiler is open.
+        let profiling = self.center_view == CenterView::Profiler;
+        if profiling != interface_data.runtime_profiling {
+            interface_data.set_runtime_profiling(profiling);
+        }
     }
     
     fn draw_darkmode_toggle(&mut self, egui_ctx: &Context, ui: &mut Ui) {
@@ -256,6 +358,12 @@ impl Interface {
                 ui.set_max_width(interface_utils::TOOLTIP_HOVER_WIDTH);
                 ui.monospace("Load a module to show interface.");
             });
+
+            ui.selectable_value(&mut self.center_view, CenterView::Profiler, "Profiler")
+            .on_disabled_hover_ui(|ui| {
+                ui.set_max_width(interface_utils::TOOLTIP_HOVER_WIDTH);
+                ui.monospace("Load a module to profile it.");
+            });
         });
     }
     
@@ -269,15 +377,18 @@ impl Interface {
             if ui.selectable_value(&mut interface_data.mode, InterfaceMode::Workspace, "Workspace").clicked() {
                 ui.close_menu();
             }
+            if ui.selectable_value(&mut interface_data.mode, InterfaceMode::Rack, "Rack").clicked() {
+                ui.close_menu();
+            }
         });
     }
 
     fn draw_modules_menu(&mut self, ui: &mut Ui, interface_data: &mut InterfaceData) {
-        if interface_data.mode == InterfaceMode::Workspace { return; }
+        if interface_data.mode != InterfaceMode::Draft { return; }
 
         ui.menu_button("Modules", |ui| {
             if ui.button("Empty").clicked() {
-                interface_data.draft_content = library::MODULE_DEFAULT.to_module_content();
+                self.draft_history.replace_draft("Empty", library::MODULE_DEFAULT.to_module_content(), &mut interface_data.draft_content);
                 ui.close_menu();
             }
 
@@ -286,7 +397,7 @@ impl Interface {
     
                 for e in 0..library::MODULE_EXAMPLES.len() {
                     if ui.button(format!("{index} {name}", index = e, name = library::MODULE_EXAMPLES[e].1)).clicked() {
-                        interface_data.draft_content = library::MODULE_EXAMPLES[e].0.to_module_content();
+                        self.draft_history.replace_draft(library::MODULE_EXAMPLES[e].1, library::MODULE_EXAMPLES[e].0.to_module_content(), &mut interface_data.draft_content);
                         ui.close_menu();
                     }
                 }
@@ -294,6 +405,32 @@ impl Interface {
         });
     }
 
+    fn draw_bundle_menu(&mut self, ui: &mut Ui, interface_data: &mut InterfaceData) {
+        ui.menu_button("Bundle", |ui| {
+            ui.set_max_width(DEFAULT_MENU_WIDTH * 2.0);
+            ui.label(format!("Share a whole module, with its files and resources, as one .{} file.", bundle::BUNDLE_EXTENSION));
+            ui.text_edit_singleline(&mut self.bundle_path);
+
+            ui.horizontal(|ui| {
+                if ui.button("Import").on_hover_text("Unpacks the bundle into a new workspace and opens it.").clicked() {
+                    self.import_bundle(interface_data);
+                    ui.close_menu();
+                }
+
+                let export_hint = match interface_data.mode {
+                    InterfaceMode::Draft => "Packs the draft.",
+                    InterfaceMode::Workspace => "Packs the open workspace.",
+                    InterfaceMode::Rack => "Racks can't be packed, export their modules one by one."
+                };
+
+                if ui.button("Export").on_hover_text(export_hint).clicked() {
+                    self.export_bundle(interface_data);
+                    ui.close_menu();
+                }
+            });
+        });
+    }
+
     fn draw_panic_button(&mut self, ui: &mut Ui, runtime_data: &RuntimeData, interface_data: &mut InterfaceData) {
         if runtime_data.state == RuntimeState::Online {
             if ui.button("\u{E4E4} PANIC").clicked() {
@@ -308,7 +445,8 @@ impl Interface {
         }
     }
 
-    fn draw_signal_flow(&mut self, ui: &mut Ui, runtime_data: &RuntimeData, interface_data: &mut InterfaceData) {
+    // Drawn right to left, the signal flows from the input on the left to the output on the right.
+    fn draw_signal_flow(&mut self, ui: &mut Ui, setter: &ParamSetter, params: &LuaGardenParams, runtime_data: &RuntimeData, interface_data: &mut InterfaceData) {
         let mut clip = interface_data.runtime_clip;
         interface_utils::toggle_value(ui, &mut clip, "\u{EA9E} Clip", "\u{EA9A} No clip", [LOAD_BUTTON_WIDTH, ui.available_height()]);
 
@@ -316,9 +454,24 @@ impl Interface {
             interface_data.set_runtime_clip(clip);
         }
 
+        let mut safety_stop = interface_data.runtime_safety_stop;
+        interface_utils::toggle_value(ui, &mut safety_stop, "Stop on fault", "Mute on fault", [LOAD_BUTTON_WIDTH * 1.5, ui.available_height()]);
+
+        if safety_stop != interface_data.runtime_safety_stop {
+            interface_data.set_runtime_safety_stop(safety_stop);
+        }
+
+        ui.add(widgets::ParamSlider::for_param(&params.output_gain, setter).with_width(SIGNAL_SLIDER_WIDTH))
+            .on_hover_text("Output gain, after mixing.");
+        ui.add(widgets::ParamSlider::for_param(&params.mix, setter).with_width(SIGNAL_SLIDER_WIDTH))
+            .on_hover_text("Dry/wet mix, the dry signal is delayed to match the module's latency.");
+
         match runtime_data.state {
             RuntimeState::Online => { 
                 ui.label("\u{E1D7}");
+                if runtime_data.muted {
+                    ui.strong("Muted").on_hover_text("The output went bad and was muted, see the console. Reset or reload to try again.");
+                }
                 ui.label(format!("{}", &runtime_data.module_name)).on_hover_text(format!("{name} by {author}\n\"{description}\"", 
                     name = &runtime_data.module_name,
                     author = &runtime_data.module_author,
@@ -332,11 +485,22 @@ impl Interface {
             }
         }
 
+        ui.add(widgets::ParamSlider::for_param(&params.input_gain, setter).with_width(SIGNAL_SLIDER_WIDTH))
+            .on_hover_text("Input gain, into the module. The dry signal doesn't get it.");
+
         let mut input_noise = interface_data.runtime_input_noise;
         interface_utils::toggle_value(ui, &mut input_noise, "\u{E1B4} Noise", "\u{E802} Input", [LOAD_BUTTON_WIDTH, ui.available_height()]);
         interface_data.set_runtime_input_noise(input_noise);
 
         ui.separator();
+
+        let mut crossfade_ms = interface_data.runtime_crossfade_ms;
+        ui.add(egui::DragValue::new(&mut crossfade_ms).clamp_range(0.0..=MAX_CROSSFADE_MS).max_decimals(0).suffix(" ms fade"))
+            .on_hover_text("Crossfade when loading, and fade to dry on PANIC or when the module fails. 0 switches right away.");
+
+        if crossfade_ms != interface_data.runtime_crossfade_ms {
+            interface_data.set_runtime_crossfade_ms(crossfade_ms);
+        }
     }
     
     fn draw_draft_editor(&mut self, ui: &mut Ui, runtime_data: &RuntimeData, interface_data: &mut InterfaceData) {
@@ -351,21 +515,61 @@ impl Interface {
             ui.selectable_value(&mut self.draft_code_selection, RuntimeCode::Trigger, "Trigger");
             ui.selectable_value(&mut self.draft_code_selection, RuntimeCode::Run, "Run");
             ui.selectable_value(&mut self.draft_code_selection, RuntimeCode::Interface, "Interface");
+            ui.separator();
+
+            self.draw_draft_libraries(ui, interface_data);
+            ui.separator();
+            self.draft_history.draw_menu(ui, &mut interface_data.draft_content);
             
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Max), |ui| {
                 self.draw_load_button(ui, runtime_data, interface_data);
+                self.draw_check_status(ui);
             });
         });
     
         ui.add_space(DEFAULT_SPACE);
+        self.script_checker.update(&interface_data.draft_content);
+        self.draft_history.track(&interface_data.draft_content);
+
+        if self.draft_history.is_viewing() {
+            let height = if self.show_console {
+                ui.available_height() - CONSOLE_HEIGHT - DEFAULT_SPACE
+            } else {
+                ui.available_height() - BAR_HEIGHT
+            };
+
+            let theme = self.get_theme();
+            self.draft_history.draw_diff(ui, &theme, &mut interface_data.draft_content, height);
+            return;
+        }
+
+        // The selected library might have been removed.
+        match &self.draft_code_selection {
+            RuntimeCode::Library(path) if !interface_data.draft_content.libraries.contains_key(path) => {
+                self.draft_code_selection = RuntimeCode::Run;
+            },
+            _ => ()
+        }
+
+        // Names from every draft file, so locals defined elsewhere complete too.
+        // Only worth collecting while the editor has a cursor.
+        let locals: BTreeSet<String> = if self.completion_cursor > 0 {
+            interface_data.draft_content.all_scripts().iter().flat_map(|(_, code)| code_editor::identifiers(code)).collect()
+        } else {
+            BTreeSet::new()
+        };
 
         egui::ScrollArea::vertical().show(ui, |ui| {
-            let code = match self.draft_code_selection {
+            let code = match &self.draft_code_selection {
                 RuntimeCode::Init => (&mut interface_data.draft_content.init, library::INIT_PATH),
                 RuntimeCode::Reset => (&mut interface_data.draft_content.reset, library::RESET_PATH),
                 RuntimeCode::Trigger => (&mut interface_data.draft_content.trigger, library::TRIGGER_PATH),
                 RuntimeCode::Run => (&mut interface_data.draft_content.run, library::RUN_PATH),
-                RuntimeCode::Interface => (&mut interface_data.draft_content.interface, library::INTERFACE_PATH)
+                RuntimeCode::Interface => (&mut interface_data.draft_content.interface, library::INTERFACE_PATH),
+                RuntimeCode::Library(path) => match interface_data.draft_content.libraries.get_mut(path) {
+                    Some(library) => (library, path.as_str()),
+                    None => (&mut interface_data.draft_content.run, library::RUN_PATH)
+                }
             };
 
             let height = if self.show_console {
@@ -373,16 +577,222 @@ impl Interface {
             } else {
                 ui.available_height() - BAR_HEIGHT
             };
-            ui.add_sized([ui.available_width(), height], egui::TextEdit::multiline(code.0)
+
+            // Only mark what happened in the file we're looking at.
+            let mut errors: Vec<ScriptError> = self.script_checker.errors_in(code.1).cloned().collect();
+            match &runtime_data.last_error {
+                Some(e) if e.path == code.1 => errors.push(e.clone()),
+                _ => ()
+            }
+            let warnings: Vec<ScriptError> = self.script_checker.warnings_in(code.1).cloned().collect();
+            let error_lines: BTreeSet<usize> = errors.iter().map(|e| e.line).collect();
+            let warning_lines: BTreeSet<usize> = warnings.iter().map(|w| w.line).collect();
+
+            let theme = self.get_theme();
+            let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+            let editor_id = egui::Id::new(format!("{prefix}/{id}", prefix = DRAFT_EDITOR_ID, id = code.1));
+
+            // The completion popup takes the keys before the editor sees them.
+            let mut accepted = None;
+            if !self.completions.is_empty() && ui.memory(|m| m.has_focus(editor_id)) {
+                let count = self.completions.len();
+                ui.input_mut(|i| {
+                    if i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown) {
+                        self.completion_index = (self.completion_index + 1) % count;
+                    }
+                    if i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp) {
+                        self.completion_index = (self.completion_index + count - 1) % count;
+                    }
+                    if i.consume_key(egui::Modifiers::NONE, egui::Key::Tab) || i.consume_key(egui::Modifiers::NONE, egui::Key::Enter) {
+                        accepted = self.completions.get(self.completion_index).cloned();
+                    }
+                    if i.consume_key(egui::Modifiers::NONE, egui::Key::Escape) {
+                        self.completion_dismissed = Some(self.completion_cursor);
+                    }
+                });
+            }
+
+            match accepted {
+                Some(completion) => {
+                    let start = char_to_byte(code.0, self.completion_start);
+                    let end = char_to_byte(code.0, self.completion_cursor);
+                    code.0.replace_range(start..end, &completion.insert);
+
+                    let cursor = self.completion_start + completion.insert.chars().count();
+                    let mut state = egui::TextEdit::load_state(ui.ctx(), editor_id).unwrap_or_default();
+                    state.cursor.set_char_range(Some(egui::text::CCursorRange::one(egui::text::CCursor::new(cursor))));
+                    state.store(ui.ctx(), editor_id);
+                    self.completion_cursor = cursor;
+                },
+                None => ()
+            }
+
+            let globals = &self.api_globals;
+            let mut layouter = |ui: &Ui, text: &str, wrap_width: f32| {
+                let mut job = code_editor::highlight(text, font_id.clone(), &theme, globals, &error_lines, &warning_lines);
+                job.wrap.max_width = wrap_width;
+                ui.fonts(|f| f.layout_job(job))
+            };
+
+            let output = egui::TextEdit::multiline(code.0)
                     .font(egui::TextStyle::Monospace)
                     .code_editor()
                     .lock_focus(true)
                     .desired_width(f32::INFINITY)
-                    .id(Into::into(format!("{prefix}/{id}", prefix = DRAFT_EDITOR_ID, id = code.1))),
-            );
+                    .min_size(egui::vec2(ui.available_width(), height))
+                    .layouter(&mut layouter)
+                    .id(editor_id)
+                    .show(ui);
+
+            self.update_completions(code.0, &output, &locals);
+            self.draw_completions(ui, code.0, &output, &font_id);
+
+            match output.response.hover_pos() {
+                Some(pointer) if !error_lines.is_empty() || !warning_lines.is_empty() => {
+                    // Paragraphs are the lines of the code, however they wrap.
+                    let offset = pointer - output.galley_pos;
+                    let over_text = offset.y >= 0.0 && offset.y < output.galley.size().y;
+                    let hovered_line = output.galley.cursor_from_pos(offset).pcursor.paragraph + 1;
+
+                    let messages: Vec<&str> = errors.iter().chain(warnings.iter())
+                        .filter(|e| e.line == hovered_line)
+                        .map(|e| e.message.as_str())
+                        .collect();
+
+                    if over_text && !messages.is_empty() {
+                        output.response.on_hover_text_at_pointer(messages.join("\n"));
+                    }
+                },
+                _ => ()
+            }
         });
     }
     
+    // Sums up the background check next to the load button.
+    fn draw_check_status(&self, ui: &mut Ui) {
+        let check = &self.script_checker.check;
+        let (label, problems) = if !check.errors.is_empty() {
+            (format!("\u{E4E0} {} error(s)", check.errors.len()), &check.errors)
+        } else if !check.warnings.is_empty() {
+            (format!("\u{E4E0} {} warning(s)", check.warnings.len()), &check.warnings)
+        } else {
+            return;
+        };
+
+        let details: Vec<String> = problems.iter()
+            .map(|p| format!("{}:{}: {}", p.path, p.line, p.message))
+            .collect();
+
+        ui.label(label).on_hover_text(details.join("\n"));
+    }
+
+    fn update_completions(&mut self, text: &str, output: &egui::text_edit::TextEditOutput, locals: &BTreeSet<String>) {
+        let cursor = match output.cursor_range {
+            Some(range) if output.response.has_focus() && range.primary.ccursor == range.secondary.ccursor => range.primary.ccursor.index,
+            _ => {
+                self.completions.clear();
+                self.completion_cursor = 0;
+                return;
+            }
+        };
+
+        // Escape hides the popup until the cursor moves.
+        if self.completion_dismissed.is_some() && self.completion_dismissed != Some(cursor) {
+            self.completion_dismissed = None;
+        }
+
+        let previous_cursor = self.completion_cursor;
+        let (start, word) = code_editor::word_before(text, cursor);
+        self.completion_start = start;
+        self.completion_cursor = cursor;
+
+        if self.completion_dismissed.is_some() {
+            self.completions.clear();
+            return;
+        }
+
+        self.completions = code_editor::completions(&self.api_symbols, locals, &word);
+        if cursor != previous_cursor || self.completion_index >= self.completions.len() {
+            self.completion_index = 0;
+        }
+    }
+
+    fn draw_completions(&self, ui: &mut Ui, text: &str, output: &egui::text_edit::TextEditOutput, font_id: &egui::FontId) {
+        if self.completions.is_empty() {
+            return;
+        }
+
+        // Monospace, so the cursor position is just line and column.
+        let before: String = text.chars().take(self.completion_start).collect();
+        let line = before.matches('\n').count();
+        let column = before.chars().rev().take_while(|c| *c != '\n').count();
+        let (row_height, glyph_width) = ui.fonts(|f| (f.row_height(font_id), f.glyph_width(font_id, ' ')));
+        let position = output.galley_pos + egui::vec2(column as f32 * glyph_width, (line + 1) as f32 * row_height);
+
+        // Not interactable, clicking would take the focus away from the editor.
+        egui::Area::new(egui::Id::new(COMPLETION_ID))
+            .order(egui::Order::Foreground)
+            .interactable(false)
+            .fixed_pos(position)
+            .show(ui.ctx(), |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_max_width(COMPLETION_WIDTH);
+
+                    for (index, completion) in self.completions.iter().enumerate() {
+                        let label = egui::RichText::new(&completion.label).monospace();
+                        ui.selectable_label(index == self.completion_index, label);
+                    }
+
+                    match self.completions.get(self.completion_index) {
+                        Some(completion) if !completion.doc.is_empty() => {
+                            ui.separator();
+                            ui.label(&completion.doc);
+                        },
+                        _ => ()
+                    }
+                });
+            });
+    }
+
+    fn draw_draft_libraries(&mut self, ui: &mut Ui, interface_data: &mut InterfaceData) {
+        let paths: Vec<String> = interface_data.draft_content.libraries.keys().cloned().collect();
+        for path in paths {
+            ui.selectable_value(&mut self.draft_code_selection, RuntimeCode::Library(path.clone()), path.trim_end_matches(".lua"));
+        }
+
+        ui.menu_button("+", |ui| {
+            ui.set_max_width(DEFAULT_MENU_WIDTH * 2.0);
+            ui.label("Add a file to require() from your scripts.");
+            ui.text_edit_singleline(&mut self.new_library_name);
+
+            if ui.button("Add").clicked() {
+                match library::library_path_from_name(&self.new_library_name) {
+                    Ok(path) if interface_data.draft_content.libraries.contains_key(&path) => {
+                        self.console.error(format!("Couldn't add file: \"{}\" already exists.", path));
+                    },
+                    Ok(path) => {
+                        let module_name = path.trim_end_matches(".lua").replace('/', ".");
+                        interface_data.draft_content.libraries.insert(path.clone(), format!("-- {}\n-- Load with require(\"{}\").\n\nlocal M = {{ }};\n\nreturn M;\n", path, module_name));
+                        self.draft_code_selection = RuntimeCode::Library(path);
+                        self.new_library_name.clear();
+                        ui.close_menu();
+                    },
+                    Err(e) => self.console.error(format!("Couldn't add file: {}", e))
+                }
+            }
+        });
+
+        match &self.draft_code_selection {
+            RuntimeCode::Library(path) => {
+                if ui.button("Remove").clicked() {
+                    interface_data.draft_content.libraries.remove(path);
+                    self.draft_code_selection = RuntimeCode::Run;
+                }
+            },
+            _ => ()
+        }
+    }
+
     fn draw_workspace_editor(&mut self, ui: &mut Ui, runtime_data: &RuntimeData, interface_data: &mut InterfaceData) {
         ui.horizontal(|ui| {
             ui.label("Workspace");
@@ -406,7 +816,7 @@ impl Interface {
                                 interface_data.workspace = Some(w);
                             },
                             Err(e) => {
-                                self.console.log(format!("Failed to create workspace: {}", e));
+                                self.console.error(format!("Failed to create workspace: {}", e));
                             }
                         }
                     }
@@ -421,7 +831,7 @@ impl Interface {
                                 interface_data.workspace = Some(w);
                             },
                             Err(e) => {
-                                self.console.log(format!("Failed to create workspace: {}", e));
+                                self.console.error(format!("Failed to create workspace: {}", e));
                             }
                         }
                     }
@@ -432,17 +842,8 @@ impl Interface {
                     }
 
                     if ui.button("Open").clicked() {
-                        let path = self.create_workspace_path.clone();
-                        match Workspace::load_from_path(path) {
-                            Ok(w) => {
-                                self.show_create_workspace(false);
-                                self.show_open_workspace(false);
-                                interface_data.workspace = Some(w);
-                            },
-                            Err(e) => {
-                                self.console.log(format!("Failed to open workspace: {}", e));
-                            }
-                        }
+                        let path = self.open_workspace_path.clone();
+                        self.open_workspace(path, interface_data);
                     }
                 } else {
                     if ui.button("Create").clicked() {
@@ -453,6 +854,7 @@ impl Interface {
                     if ui.button("Open").clicked() {
                         self.show_open_workspace(true);
                         self.show_create_workspace(false);
+                        self.workspace_browser.refresh();
                     }
                 }
             });
@@ -474,8 +876,15 @@ impl Interface {
             ui.horizontal(|ui| {
                 ui.label("Open a workspace at");
                 ui.text_edit_singleline(&mut self.open_workspace_path);
-                ui.label(".");
+                ui.label(", or pick one below.");
             });
+
+            ui.separator();
+
+            match self.workspace_browser.draw(ui) {
+                Some(path) => self.open_workspace(path, interface_data),
+                None => ()
+            }
         } else {
             match &interface_data.workspace {
                 Some(workspace) => {
@@ -484,6 +893,15 @@ impl Interface {
                         ui.monospace(format!("{}", workspace.path));
                         ui.label(".");
                     });
+
+                    if !workspace.content.libraries.is_empty() {
+                        ui.horizontal_wrapped(|ui| {
+                            ui.label("Library files:");
+                            for path in workspace.content.libraries.keys() {
+                                ui.monospace(path);
+                            }
+                        });
+                    }
                 },
                 None => {
                     ui.label("No workspace loaded. Create or open one.");
@@ -494,7 +912,25 @@ impl Interface {
         ui.add_space(DEFAULT_SPACE * 4.0);
     }
     
-    fn draw_module_interface(&mut self, ui: &mut Ui, runtime_data: &RuntimeData, interface_data: &mut InterfaceData) {
+    fn draw_rack_editor(&mut self, ui: &mut Ui, runtime_data: &RuntimeData, interface_data: &mut InterfaceData) {
+        ui.horizontal(|ui| {
+            ui.label("Rack");
+            interface_utils::help_label(ui, format!("In rack mode, {name} runs several modules in series, each in its own Lua state.\n\
+                Drafts are added as copies, workspaces are read from their folder when loading.", name = consts::NAME));
+
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Max), |ui| {
+                self.draw_load_button(ui, runtime_data, interface_data);
+            });
+        });
+
+        ui.add_space(DEFAULT_SPACE * 4.0);
+
+        self.rack_editor.draw(ui, runtime_data, interface_data, &mut self.console);
+
+        ui.add_space(DEFAULT_SPACE * 4.0);
+    }
+    
+    fn draw_module_interface(&mut self, ui: &mut Ui, runtime_data: &RuntimeData, interface_data: &mut InterfaceData, params: &LuaGardenParams) {
         ui.horizontal(|ui| {
             ui.selectable_value(&mut self.interface_runtime.view, InterfaceRuntimeView::Interface, "Interface");
             ui.separator();
@@ -510,25 +946,27 @@ impl Interface {
 
         ui.add_space(DEFAULT_SPACE);
 
-        self.interface_runtime.draw(ui, runtime_data, interface_data);
+        self.interface_runtime.draw(ui, runtime_data, interface_data, &params.draft_presets, &mut self.console, &self.telemetry);
     }
     
     fn draw_load_button(&mut self, ui: &mut Ui, runtime_data: &RuntimeData, interface_data: &mut InterfaceData) {
-        let enabled = interface_data.mode == InterfaceMode::Draft || interface_data.workspace != None;
+        let enabled = match interface_data.mode {
+            InterfaceMode::Draft => true,
+            InterfaceMode::Workspace => interface_data.workspace != None,
+            InterfaceMode::Rack => !interface_data.rack.is_empty()
+        };
 
         ui.add_enabled_ui(enabled, |ui| {
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Max), |ui| {
                 match runtime_data.state {
                     RuntimeState::Offline => {
                         if ui.add_sized([LOAD_BUTTON_WIDTH, ui.available_height()], egui::Button::new("\u{E52E} Load")).clicked() {
-                            self.update_workspace(interface_data);
-                            interface_data.set_runtime_target_state(RuntimeState::Refresh);
+                            self.load(runtime_data, interface_data);
                         }
                     },
                     RuntimeState::Online => {
                         if ui.add_sized([LOAD_BUTTON_WIDTH, ui.available_height()], egui::Button::new("\u{E522} Reload")).clicked() {
-                            self.update_workspace(interface_data);
-                            interface_data.set_runtime_target_state(RuntimeState::Refresh);
+                            self.load(runtime_data, interface_data);
                         }
                     }
                     _ => {
@@ -559,12 +997,27 @@ impl Interface {
                 ui.monospace(":");
         
                 let load = (runtime_data.run_ms / (runtime_data.buffer_size as f32 / runtime_data.sample_rate * 1000.0) * 100.0).floor();
-                let status = format!("({ms:.2}ms / {load:>3}%) at {rate}hz, {buff} samples, {channels} channels.", 
+                let status = format!("({ms:.2}ms / {load:>3}%) at {rate}hz, {buff} samples, {channels} channels, {latency} samples latency.", 
                     ms = runtime_data.run_ms,
                     load = load, 
                     rate = runtime_data.sample_rate,
                     buff = runtime_data.buffer_size,
-                    channels = runtime_data.channels);
+                    channels = runtime_data.channels,
+                    latency = runtime_data.latency_samples);
+
+                // A rack also shows what each of its modules costs.
+                let status = if runtime_data.slots.len() > 1 {
+                    let slots: Vec<String> = runtime_data.slots.iter()
+                        .map(|slot| match slot.bypassed {
+                            true => format!("{}: bypassed", slot.name),
+                            false => format!("{}: {:.2}ms", slot.name, slot.run_ms)
+                        })
+                        .collect();
+
+                    format!("{} [{}]", status, slots.join(", "))
+                } else {
+                    status
+                };
         
                 match runtime_data.state {
                     RuntimeState::Offline => {
@@ -579,18 +1032,175 @@ impl Interface {
                 }
             });
         
+            self.draw_console_tools(ui);
+            self.draw_console_input(ui, runtime_data);
+        
             ui.with_layout(egui::Layout::top_down_justified(egui::Align::LEFT).with_cross_justify(true), |ui| {
-                let log_string = self.console.get_log_string();
-                
-                egui::ScrollArea::vertical()
-                    .id_source(hash)
-                    .show(ui, |ui| {
-                        ui.monospace(format!("{}", log_string));
+                self.update_repl();
+                self.console.update();
+                let visuals = ui.visuals().clone();
+                let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+                let rows = self.console_view.rows(&self.console, &self.console_filter);
+
+                // One line per row and no wrapping, so only the rows in view get laid out.
+                egui::ScrollArea::both()
+                    .id_salt(hash)
+                    .show_rows(ui, row_height, rows.len(), |ui, range| {
+                        for &(index, line) in &rows[range] {
+                            let entry = &self.console.entries()[index];
+                            let formatted = self.console.format_entry(entry);
+                            let text = egui::RichText::new(formatted.lines().nth(line).unwrap_or_default()).monospace();
+                            let text = match entry.log.level {
+                                LogLevel::Info => text,
+                                LogLevel::Warn => text.color(visuals.warn_fg_color),
+                                LogLevel::Error => text.color(visuals.error_fg_color)
+                            };
+
+                            ui.add(egui::Label::new(text).extend());
+                        }
                 });
             });
         });
     }
 
+    fn draw_console_tools(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt(CONSOLE_LEVEL_ID)
+                .selected_text(self.console_filter.min_level.label())
+                .width(CONSOLE_LEVEL_WIDTH)
+                .show_ui(ui, |ui| {
+                    for level in [LogLevel::Info, LogLevel::Warn, LogLevel::Error] {
+                        ui.selectable_value(&mut self.console_filter.min_level, level, level.label());
+                    }
+                }).response.on_hover_text("The lowest level to show.");
+
+            ui.toggle_value(&mut self.console_filter.runtime, LogSource::Runtime.label());
+            ui.toggle_value(&mut self.console_filter.module, LogSource::Module.label());
+            ui.toggle_value(&mut self.console_filter.interface, LogSource::Interface.label());
+            ui.separator();
+
+            ui.add(egui::TextEdit::singleline(&mut self.console_filter.search).hint_text("Search").desired_width(CONSOLE_SEARCH_WIDTH));
+            ui.checkbox(&mut self.console.collapse_repeats, "Collapse repeats")
+                .on_hover_text("Counts repeats of the last line instead of adding them, for modules that log every block.");
+            ui.separator();
+
+            let mut capacity = self.console.capacity();
+            if ui.add(egui::DragValue::new(&mut capacity).clamp_range(console::MIN_CAPACITY..=console::MAX_CAPACITY).suffix(" lines")).on_hover_text("How many lines the console keeps.").changed() {
+                self.console.set_capacity(capacity);
+            }
+
+            if ui.button("Clear").clicked() {
+                self.console.clear();
+            }
+
+            ui.menu_button("Export", |ui| {
+                ui.set_max_width(DEFAULT_MENU_WIDTH * 2.0);
+                ui.text_edit_singleline(&mut self.console_export_path);
+
+                if ui.button("Export").clicked() {
+                    let path = self.console_export_path.clone();
+                    match self.console.export(&path) {
+                        Ok(_) => self.console.log(format!("Exported console to \"{}\".", path)),
+                        Err(e) => self.console.error(e)
+                    }
+                    ui.close_menu();
+                }
+            });
+        });
+    }
+
+    // Lua typed here runs in the module between blocks, the result shows up in the console.
+    fn draw_console_input(&mut self, ui: &mut Ui, runtime_data: &RuntimeData) {
+        ui.horizontal(|ui| {
+            ui.monospace(">");
+
+            if runtime_data.slots.len() > 1 {
+                let selected = runtime_data.slots.iter().find(|s| Some(s.id) == self.repl_slot).or(runtime_data.slots.first());
+                egui::ComboBox::from_id_salt(CONSOLE_REPL_SLOT_ID)
+                    .selected_text(selected.map_or("", |s| s.name.as_str()))
+                    .width(CONSOLE_REPL_SLOT_WIDTH)
+                    .show_ui(ui, |ui| {
+                        for slot in &runtime_data.slots {
+                            ui.selectable_value(&mut self.repl_slot, Some(slot.id), &slot.name);
+                        }
+                    }).response.on_hover_text("The module to run in.");
+            }
+
+            let response = ui.add(egui::TextEdit::singleline(&mut self.repl_code)
+                .code_editor()
+                .hint_text("Lua, runs in the module")
+                .desired_width(f32::INFINITY));
+
+            if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                self.send_repl_code(runtime_data);
+                response.request_focus();
+            }
+
+            if response.has_focus() && ui.input(|i| i.key_pressed(egui::Key::ArrowUp)) {
+                self.browse_repl_history(true);
+            }
+
+            if response.has_focus() && ui.input(|i| i.key_pressed(egui::Key::ArrowDown)) {
+                self.browse_repl_history(false);
+            }
+        });
+    }
+
+    fn send_repl_code(&mut self, runtime_data: &RuntimeData) {
+        let code = String::from(self.repl_code.trim());
+        if code.is_empty() { return; }
+
+        // A slot that went away falls back to the first module.
+        let slot_id = self.repl_slot.filter(|id| runtime_data.slots.iter().any(|s| s.id == *id));
+
+        match self.repl.lock() {
+            Ok(mut repl) => repl.send(ReplCommand { slot_id: slot_id, code: code.clone() }),
+            Err(_) => {
+                self.console.error(String::from("Couldn't send code to the module."));
+                return;
+            }
+        }
+
+        if self.repl_history.last() != Some(&code) {
+            self.repl_history.push(code);
+        }
+
+        if self.repl_history.len() > CONSOLE_REPL_HISTORY {
+            self.repl_history.remove(0);
+        }
+
+        self.repl_history_index = self.repl_history.len();
+        self.repl_code.clear();
+    }
+
+    // Past the newest entry is an empty line.
+    fn browse_repl_history(&mut self, back: bool) {
+        if self.repl_history.is_empty() { return; }
+
+        self.repl_history_index = match back {
+            true => self.repl_history_index.saturating_sub(1),
+            false => (self.repl_history_index + 1).min(self.repl_history.len())
+        };
+
+        self.repl_code = self.repl_history.get(self.repl_history_index).cloned().unwrap_or_default();
+    }
+
+    fn update_repl(&mut self) {
+        let results = match self.repl.lock() {
+            Ok(mut repl) => repl.take_results(),
+            Err(_) => return
+        };
+
+        for result in results {
+            self.console.log(format!("> {}", result.code));
+
+            match result.output {
+                Ok(output) => self.console.log(output),
+                Err(e) => self.console.error(e)
+            }
+        }
+    }
+
     fn draw_info(&mut self, ui: &mut Ui) {
         ui.vertical(|ui| {
             ui.heading(format!("{icon} {name}", icon = consts::ICON, name = consts::NAME));
@@ -602,6 +1212,48 @@ impl Interface {
         ui.label(consts::DISCLAIMER);
     }
 
+    fn import_bundle(&mut self, interface_data: &mut InterfaceData) {
+        match bundle::import(&self.bundle_path, &library::default_workspaces_path()) {
+            Ok(path) => {
+                self.console.log(format!("Imported bundle into \"{}\".", path));
+                self.open_workspace(path, interface_data);
+                interface_data.mode = InterfaceMode::Workspace;
+            },
+            Err(e) => self.console.error(format!("Failed to import bundle: {}", e))
+        }
+    }
+
+    fn export_bundle(&mut self, interface_data: &InterfaceData) {
+        let result = match (&interface_data.mode, &interface_data.workspace) {
+            (InterfaceMode::Workspace, Some(workspace)) => {
+                let folder_name = std::path::Path::new(&workspace.path).file_name().map_or(String::new(), |n| n.to_string_lossy().to_string());
+                let name = workspace.content.manifest.as_ref().and_then(|m| m.name.clone()).unwrap_or(folder_name);
+                bundle::export_workspace(&workspace.path, &name, &self.bundle_path)
+            },
+            (InterfaceMode::Workspace, None) => Err(String::from("No workspace loaded.")),
+            (InterfaceMode::Draft, _) => bundle::export_content(&interface_data.draft_content, DRAFT_BUNDLE_NAME, &self.bundle_path),
+            (InterfaceMode::Rack, _) => Err(String::from("Racks can't be packed, export their modules one by one."))
+        };
+
+        match result {
+            Ok(_) => self.console.log(format!("Exported bundle to \"{}\".", self.bundle_path)),
+            Err(e) => self.console.error(format!("Failed to export bundle: {}", e))
+        }
+    }
+
+    fn open_workspace(&mut self, path: String, interface_data: &mut InterfaceData) {
+        match Workspace::load_from_path(path) {
+            Ok(w) => {
+                self.show_create_workspace(false);
+                self.show_open_workspace(false);
+                interface_data.workspace = Some(w);
+            },
+            Err(e) => {
+                self.console.error(format!("Failed to open workspace: {}", e));
+            }
+        }
+    }
+
     fn show_create_workspace(&mut self, show: bool) {
         self.show_create_workspace = show;
     }
@@ -614,15 +1266,54 @@ impl Interface {
         return self.themes[self.theme];
     }
 
+    fn load(&mut self, runtime_data: &RuntimeData, interface_data: &mut InterfaceData) {
+        if interface_data.mode == InterfaceMode::Draft {
+            self.draft_history.expect_load(&interface_data.draft_content);
+        }
+
+        self.update_workspace(interface_data);
+        self.update_user_libraries(interface_data);
+
+        let load_id = self.loader.load(interface_data.runtime_slots(), runtime_data.sample_rate);
+        interface_data.set_runtime_load(load_id);
+    }
+
     fn update_workspace(&mut self, interface_data: &mut InterfaceData) {
         match &mut interface_data.workspace {
             Some(workspace) => {
                 match workspace.update() {
                     Ok(()) => (),
-                    Err(e) => self.console.log(format!("Couldn't update workspace: {}", e))
+                    Err(e) => self.console.error(format!("Couldn't update workspace: {}", e))
                 }
             },
             None => ()
         }
+
+        if interface_data.mode != InterfaceMode::Rack { return; }
+
+        // Workspaces in the rack are read again too.
+        for slot in &mut interface_data.rack {
+            match &mut slot.source {
+                RackSource::Workspace(workspace) => match workspace.update() {
+                    Ok(()) => (),
+                    Err(e) => self.console.error(format!("Couldn't update workspace: {}", e))
+                },
+                RackSource::Draft(_) => ()
+            }
+        }
+    }
+
+    fn update_user_libraries(&mut self, interface_data: &mut InterfaceData) {
+        match library::read_user_libraries() {
+            Ok(libraries) => interface_data.user_libraries = libraries,
+            Err(e) => self.console.error(format!("Couldn't read user library: {}", e))
+        }
     }
-}
\ No newline at end of file
+}
+
+fn char_to_byte(text: &str, char_index: usize) -> usize {
+    return match text.char_indices().nth(char_index) {
+        Some((index, _)) => index,
+        None => text.len()
+    };
+}
@@ -0,0 +1,116 @@
+use nih_plug_egui::egui::{ self, Ui };
+use crate::{ runtime::{ profiler::ProfileReport, runtime_data::RuntimeData }, InterfaceData };
+use super::DEFAULT_SPACE;
+
+const PROFILER_GRID_ID: &str = "Central/Profiler";
+const FUNCTIONS_GRID_ID: &str = "Central/Profiler/Functions";
+const HISTOGRAM_HEIGHT: f32 = 96.0;
+const FUNCTIONS_SCROLL_HEIGHT: f32 = 240.0;
+
+// Where a module's time goes, block by block.
+pub fn draw(ui: &mut Ui, runtime_data: &RuntimeData, interface_data: &mut InterfaceData) {
+    let report = &runtime_data.profile;
+
+    let mut sampling = interface_data.runtime_sampling;
+    if ui.checkbox(&mut sampling, "Sample Lua functions").on_hover_text("Counts which function Lua is in every so often. Slows the module down while on.").changed() {
+        interface_data.set_runtime_sampling(sampling);
+    }
+
+    ui.add_space(DEFAULT_SPACE);
+
+    if report.blocks == 0 {
+        ui.label("Waiting for the module to run.");
+        return;
+    }
+
+    egui::Grid::new(PROFILER_GRID_ID)
+        .num_columns(2)
+        .spacing([DEFAULT_SPACE * 4.0, DEFAULT_SPACE])
+        .show(ui, |ui| {
+            ui.label("Blocks");
+            ui.monospace(format!("{}", report.blocks));
+            ui.end_row();
+
+            ui.label("Over deadline");
+            ui.monospace(format!("{} ({:.1}%), deadline {:.2}ms", report.over_deadline, report.over_deadline as f32 / report.blocks as f32 * 100.0, report.deadline_ms))
+                .on_hover_text("Blocks that took longer to process than they last. Each one is a dropout, unless the host has time to spare.");
+            ui.end_row();
+
+            ui.label("Block time");
+            ui.monospace(format!("min {:.3}ms  avg {:.3}ms  p99 {:.3}ms  max {:.3}ms", report.min_ms, report.avg_ms, report.p99_ms, report.max_ms))
+                .on_hover_text("Over the recent blocks.");
+            ui.end_row();
+
+            ui.label("Copying buffers");
+            ui.monospace(format!("{:.3}ms", report.copy_ms));
+            ui.end_row();
+
+            ui.label("Running Lua");
+            ui.monospace(format!("{:.3}ms", report.lua_ms));
+            ui.end_row();
+
+            ui.label("Spectral");
+            ui.monospace(format!("{:.3}ms", report.spectral_ms));
+            ui.end_row();
+
+            ui.label("Lua memory");
+            ui.monospace(format!("{:.0} KB, about {:.1} KB allocated per block", report.memory_kb, report.allocated_kb))
+                .on_hover_text("Allocations turn into garbage the collector has to clean up, on the audio thread.");
+            ui.end_row();
+
+            ui.label("Init / Reset");
+            ui.monospace(format!("{:.2}ms / {:.2}ms", report.init_ms, report.reset_ms));
+            ui.end_row();
+        });
+
+    ui.add_space(DEFAULT_SPACE * 2.0);
+    draw_histogram(ui, report);
+    ui.add_space(DEFAULT_SPACE * 2.0);
+
+    if !interface_data.runtime_sampling { return; }
+
+    if report.functions.is_empty() {
+        ui.label("No samples yet.");
+        return;
+    }
+
+    egui::ScrollArea::vertical().max_height(FUNCTIONS_SCROLL_HEIGHT).show(ui, |ui| {
+        egui::Grid::new(FUNCTIONS_GRID_ID)
+            .num_columns(2)
+            .striped(true)
+            .spacing([DEFAULT_SPACE * 4.0, DEFAULT_SPACE])
+            .show(ui, |ui| {
+                for (function, share) in &report.functions {
+                    ui.monospace(format!("{:5.1}%", share * 100.0));
+                    ui.monospace(function.as_str());
+                    ui.end_row();
+                }
+            });
+    });
+}
+
+fn draw_histogram(ui: &mut Ui, report: &ProfileReport) {
+    let (rect, response) = ui.allocate_exact_size(egui::vec2(ui.available_width(), HISTOGRAM_HEIGHT), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    let visuals = ui.visuals();
+
+    let bins = report.histogram.len().max(1);
+    let highest = report.histogram.iter().copied().max().unwrap_or(0).max(1) as f32;
+    let bin_width = rect.width() / bins as f32;
+
+    for (bin, count) in report.histogram.iter().enumerate() {
+        let height = *count as f32 / highest * rect.height();
+        let left = rect.left() + bin as f32 * bin_width;
+        let bar = egui::Rect::from_min_max(egui::pos2(left + 1.0, rect.bottom() - height), egui::pos2(left + bin_width - 1.0, rect.bottom()));
+
+        // The right half is past the deadline.
+        let color = match bin < bins / 2 {
+            true => visuals.text_color(),
+            false => visuals.warn_fg_color
+        };
+        painter.rect_filled(bar, 0.0, color);
+    }
+
+    painter.vline(rect.center().x, rect.y_range(), visuals.widgets.noninteractive.bg_stroke);
+    response.on_hover_text(format!("Block times from 0 to {:.2}ms, the line marks the deadline.", report.deadline_ms * 2.0));
+}
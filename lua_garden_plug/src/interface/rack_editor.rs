@@ -0,0 +1,144 @@
+use nih_plug_egui::egui::{ self, Ui };
+use crate::{ runtime::{ library, rack::{ RackSlot, RackSource }, runtime_data::RuntimeData, workspace::Workspace }, ConsoleReceiver, InterfaceData };
+use super::{ interface_utils, DEFAULT_SPACE };
+
+const RACK_GRID_ID: &str = "Central/Rack";
+const SLOT_ACTION_WIDTH: f32 = 24.0;
+
+// Builds a chain of modules, each with its own Lua state.
+pub struct RackEditor {
+    workspace_path: String
+}
+
+enum SlotAction {
+    MoveUp,
+    MoveDown,
+    UpdateFromDraft,
+    Remove
+}
+
+impl RackEditor {
+    pub fn new() -> RackEditor {
+        Self {
+            workspace_path: library::default_workspaces_path()
+        }
+    }
+
+    pub fn draw(&mut self, ui: &mut Ui, runtime_data: &RuntimeData, interface_data: &mut InterfaceData, console: &mut ConsoleReceiver) {
+        ui.horizontal(|ui| {
+            if ui.button("Add draft").on_hover_text("Adds a copy of the draft as it is now.").clicked() {
+                interface_data.rack.push(RackSlot::new(RackSource::Draft(interface_data.draft_content.clone())));
+            }
+
+            ui.add_enabled_ui(interface_data.workspace.is_some(), |ui| {
+                if ui.button("Add open workspace").clicked() {
+                    match &interface_data.workspace {
+                        Some(workspace) => interface_data.rack.push(RackSlot::new(RackSource::Workspace(workspace.clone()))),
+                        None => ()
+                    }
+                }
+            });
+
+            ui.separator();
+            ui.text_edit_singleline(&mut self.workspace_path);
+            if ui.button("Add workspace").clicked() {
+                match Workspace::load_from_path(self.workspace_path.clone()) {
+                    Ok(workspace) => interface_data.rack.push(RackSlot::new(RackSource::Workspace(workspace))),
+                    Err(e) => console.error(format!("Couldn't add workspace to rack: {}", e))
+                }
+            }
+        });
+
+        ui.add_space(DEFAULT_SPACE);
+
+        if interface_data.rack.is_empty() {
+            ui.label("The rack is empty. Add the draft or a workspace, then load it.");
+            return;
+        }
+
+        ui.label("Audio flows from the top down. Bypass and order apply right away, added or removed modules when loading.");
+        ui.add_space(DEFAULT_SPACE);
+
+        let slot_count = interface_data.rack.len();
+        let mut action = None;
+        let mut changed = false;
+
+        egui::Grid::new(RACK_GRID_ID)
+            .num_columns(5)
+            .striped(true)
+            .spacing([DEFAULT_SPACE * 4.0, DEFAULT_SPACE])
+            .show(ui, |ui| {
+                for (index, slot) in interface_data.rack.iter_mut().enumerate() {
+                    ui.monospace(format!("{}", index + 1));
+
+                    ui.vertical(|ui| {
+                        ui.strong(slot.label());
+                        match &slot.source {
+                            RackSource::Draft(_) => ui.weak("Draft copy"),
+                            RackSource::Workspace(workspace) => ui.weak(&workspace.path)
+                        };
+                    });
+
+                    // Loaded slots show what they cost.
+                    match runtime_data.slots.iter().find(|s| s.id == slot.id) {
+                        Some(status) if !status.bypassed => ui.monospace(format!("{:.2}ms", status.run_ms)),
+                        Some(_) => ui.monospace("-"),
+                        None => ui.weak("Not loaded")
+                    };
+
+                    let mut active = !slot.bypassed;
+                    interface_utils::toggle_value(ui, &mut active, "On", "Bypassed", [SLOT_ACTION_WIDTH * 3.0, ui.available_height()]);
+                    if active == slot.bypassed {
+                        slot.bypassed = !active;
+                        changed = true;
+                    }
+
+                    ui.horizontal(|ui| {
+                        if ui.add_enabled(index > 0, egui::Button::new("Up")).clicked() {
+                            action = Some((index, SlotAction::MoveUp));
+                        }
+                        if ui.add_enabled(index + 1 < slot_count, egui::Button::new("Down")).clicked() {
+                            action = Some((index, SlotAction::MoveDown));
+                        }
+
+                        match &slot.source {
+                            RackSource::Draft(_) => {
+                                if ui.button("Update").on_hover_text("Replaces this copy with the draft as it is now.").clicked() {
+                                    action = Some((index, SlotAction::UpdateFromDraft));
+                                }
+                            },
+                            RackSource::Workspace(_) => ()
+                        }
+
+                        if ui.button("Remove").clicked() {
+                            action = Some((index, SlotAction::Remove));
+                        }
+                    });
+
+                    ui.end_row();
+                }
+            });
+
+        match action {
+            Some((index, SlotAction::MoveUp)) => {
+                interface_data.rack.swap(index, index - 1);
+                changed = true;
+            },
+            Some((index, SlotAction::MoveDown)) => {
+                interface_data.rack.swap(index, index + 1);
+                changed = true;
+            },
+            Some((index, SlotAction::UpdateFromDraft)) => {
+                interface_data.rack[index].source = RackSource::Draft(interface_data.draft_content.clone());
+            },
+            Some((index, SlotAction::Remove)) => {
+                interface_data.rack.remove(index);
+            },
+            None => ()
+        }
+
+        if changed {
+            interface_data.mark_changed();
+        }
+    }
+}
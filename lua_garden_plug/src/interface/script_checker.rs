@@ -0,0 +1,97 @@
+use std::{ collections::BTreeSet, sync::mpsc::{ self, Receiver, Sender }, thread };
+use mlua::prelude::*;
+use crate::runtime::{ errors::{ self, ScriptError }, library, lint, module_content::ModuleContent };
+
+const CHECKER_THREAD_NAME: &str = "Script checker";
+
+// What the checker found in one version of the code.
+#[derive(Clone, Default)]
+pub struct ScriptCheck {
+    pub errors: Vec<ScriptError>,
+    pub warnings: Vec<ScriptError>
+}
+
+// Compiles scripts without running them and lints them on a separate thread, so typing stays smooth.
+pub struct ScriptChecker {
+    sender: Sender<ModuleContent>,
+    receiver: Receiver<ScriptCheck>,
+    checked_content: Option<ModuleContent>,
+    pub check: ScriptCheck
+}
+
+impl ScriptChecker {
+    pub fn new(globals: BTreeSet<String>) -> ScriptChecker {
+        let (sender, content_receiver) = mpsc::channel::<ModuleContent>();
+        let (check_sender, receiver) = mpsc::channel::<ScriptCheck>();
+
+        // Stops by itself when the checker is dropped and the channel closes.
+        thread::Builder::new()
+            .name(String::from(CHECKER_THREAD_NAME))
+            .spawn(move || {
+                let lua = Lua::new();
+
+                while let Ok(mut content) = content_receiver.recv() {
+                    // Only the newest version is worth checking.
+                    while let Ok(newer) = content_receiver.try_recv() {
+                        content = newer;
+                    }
+
+                    if check_sender.send(check(&lua, &content, &globals)).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("Couldn't start the script checker.");
+
+        return Self {
+            sender: sender,
+            receiver: receiver,
+            checked_content: None,
+            check: ScriptCheck::default()
+        };
+    }
+
+    // Sends changed code off to be checked and picks up finished checks, call every frame.
+    pub fn update(&mut self, content: &ModuleContent) {
+        if self.checked_content.as_ref() != Some(content) {
+            self.checked_content = Some(content.clone());
+            let _ = self.sender.send(content.clone());
+        }
+
+        while let Ok(check) = self.receiver.try_recv() {
+            self.check = check;
+        }
+    }
+
+    pub fn errors_in<'a>(&'a self, path: &'a str) -> impl Iterator<Item = &'a ScriptError> {
+        return self.check.errors.iter().filter(move |e| e.path == path);
+    }
+
+    pub fn warnings_in<'a>(&'a self, path: &'a str) -> impl Iterator<Item = &'a ScriptError> {
+        return self.check.warnings.iter().filter(move |w| w.path == path);
+    }
+}
+
+fn check(lua: &Lua, content: &ModuleContent, globals: &BTreeSet<String>) -> ScriptCheck {
+    let scripts = content.all_scripts();
+    let mut errors = Vec::new();
+
+    for (path, code) in &scripts {
+        let compiled = lua.load(*code)
+            .set_name(library::user_chunk_name(path))
+            .into_function();
+
+        match compiled {
+            Ok(_) => (),
+            Err(e) => errors.push(match errors::locate(&e) {
+                Some(error) => error,
+                None => ScriptError { path: String::from(*path), line: 1, message: errors::describe(&e) }
+            })
+        }
+    }
+
+    return ScriptCheck {
+        errors: errors,
+        warnings: lint::lint(&scripts, globals)
+    };
+}
@@ -0,0 +1,95 @@
+use nih_plug_egui::egui::{ self, Ui };
+use crate::runtime::{ runtime_data::RuntimeData, telemetry::{ Reading, TelemetryReceiver, Watch } };
+use super::DEFAULT_SPACE;
+
+const TELEMETRY_GRID_ID: &str = "Central/Interface/Telemetry";
+const METER_WIDTH: f32 = 240.0;
+const GRAPH_WIDTH: f32 = 160.0;
+const GRAPH_HEIGHT: f32 = 20.0;
+
+// Meters and watches the modules publish, as bars, numbers with a small graph or text.
+pub fn draw(ui: &mut Ui, runtime_data: &RuntimeData, telemetry: &TelemetryReceiver) {
+    if telemetry.is_empty() {
+        ui.label("Nothing to show yet.");
+        ui.label("Modules can show values here with runtime.meter(name, value, min, max) and runtime.watch(name, value).");
+        return;
+    }
+
+    egui::Grid::new(TELEMETRY_GRID_ID)
+        .num_columns(2)
+        .striped(true)
+        .spacing([DEFAULT_SPACE * 4.0, DEFAULT_SPACE])
+        .show(ui, |ui| {
+            for watch in telemetry.watches() {
+                ui.label(label(watch, runtime_data));
+
+                match &watch.reading {
+                    Reading::Meter { value, min, max } => {
+                        ui.add(egui::ProgressBar::new(watch.fraction()).desired_width(METER_WIDTH).text(format!("{:.2}", value)))
+                            .on_hover_text(format!("From {} to {}.", min, max));
+                    },
+                    Reading::Number(number) => {
+                        ui.horizontal(|ui| {
+                            draw_graph(ui, watch);
+                            ui.monospace(format!("{:.3}", number));
+                        });
+                    },
+                    Reading::Text(text) => {
+                        ui.monospace(text);
+                    }
+                }
+
+                ui.end_row();
+            }
+        });
+
+    if telemetry.dropped() > 0 {
+        ui.add_space(DEFAULT_SPACE);
+        ui.weak(format!("Dropped {} values, they came in faster than the interface could keep up.", telemetry.dropped()));
+    }
+}
+
+// A rack has several modules publishing, so their names go in front.
+fn label(watch: &Watch, runtime_data: &RuntimeData) -> String {
+    if runtime_data.slots.len() < 2 {
+        return watch.name.clone();
+    }
+
+    return match runtime_data.slots.iter().find(|s| s.id == watch.slot_id) {
+        Some(slot) => format!("{} / {}", slot.name, watch.name),
+        None => watch.name.clone()
+    };
+}
+
+// Scaled to fit whatever the history spans.
+fn draw_graph(ui: &mut Ui, watch: &Watch) {
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(GRAPH_WIDTH, GRAPH_HEIGHT), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    let visuals = ui.visuals();
+
+    painter.rect_stroke(rect, 0.0, visuals.widgets.noninteractive.bg_stroke);
+    if watch.history.len() < 2 { return; }
+
+    let low = watch.history.iter().copied().fold(f32::INFINITY, f32::min);
+    let high = watch.history.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    if !low.is_finite() || !high.is_finite() { return; }
+
+    let range = match high - low {
+        r if r > 0.0 => r,
+        _ => 1.0
+    };
+
+    let step = rect.width() / (watch.history.len() - 1) as f32;
+    let points = watch.history.iter().enumerate()
+        .map(|(index, value)| {
+            // Values that aren't numbers sit at the bottom.
+            let value = match value.is_finite() {
+                true => *value,
+                false => low
+            };
+            egui::pos2(rect.left() + index as f32 * step, rect.bottom() - (value - low) / range * rect.height())
+        })
+        .collect();
+
+    painter.add(egui::Shape::line(points, visuals.widgets.noninteractive.fg_stroke));
+}
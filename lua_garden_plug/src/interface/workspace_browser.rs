@@ -0,0 +1,170 @@
+use std::{ sync::mpsc::{ self, Receiver }, thread };
+use nih_plug_egui::egui::{ self, Ui };
+use crate::runtime::{ library, workspace::{ Workspace, WorkspaceInfo } };
+use super::{ interface_utils, DEFAULT_SPACE };
+
+const BROWSER_THREAD_NAME: &str = "Workspace browser";
+const BROWSER_GRID_ID: &str = "Central/WorkspaceBrowser";
+const ABOUT_WIDTH: f32 = 320.0;
+
+// Lists the workspaces in the workspaces folder and any folders added by the user.
+pub struct WorkspaceBrowser {
+    pub folders: Vec<String>,
+    new_folder: String,
+    workspaces: Vec<WorkspaceInfo>,
+    problems: Vec<String>,
+    receiver: Option<Receiver<(Vec<WorkspaceInfo>, Vec<String>)>>
+}
+
+impl WorkspaceBrowser {
+    pub fn new() -> WorkspaceBrowser {
+        Self {
+            folders: vec![library::default_workspaces_path()],
+            new_folder: String::new(),
+            workspaces: Vec::new(),
+            problems: Vec::new(),
+            receiver: None
+        }
+    }
+
+    // Reading a workspace runs its init.lua in a sandbox, which can take a while, so scanning happens off the interface thread.
+    pub fn refresh(&mut self) {
+        let folders = self.folders.clone();
+        let (sender, receiver) = mpsc::channel();
+
+        let spawned = thread::Builder::new()
+            .name(String::from(BROWSER_THREAD_NAME))
+            .spawn(move || {
+                let mut workspaces = Vec::new();
+                let mut problems = Vec::new();
+
+                for folder in folders {
+                    match Workspace::find_in_folder(&folder) {
+                        Ok(paths) => workspaces.extend(paths.into_iter().map(Workspace::read_info)),
+                        Err(e) => problems.push(format!("Couldn't read \"{}\": {}", folder, e))
+                    }
+                }
+
+                let _ = sender.send((workspaces, problems));
+            });
+
+        match spawned {
+            Ok(_) => self.receiver = Some(receiver),
+            Err(e) => self.problems = vec![format!("Couldn't scan for workspaces: {}", e)]
+        }
+    }
+
+    // Returns the path of the workspace to open, if one was clicked.
+    pub fn draw(&mut self, ui: &mut Ui) -> Option<String> {
+        let finished = match &self.receiver {
+            Some(receiver) => receiver.try_recv().ok(),
+            None => None
+        };
+
+        match finished {
+            Some((workspaces, problems)) => {
+                self.workspaces = workspaces;
+                self.problems = problems;
+                self.receiver = None;
+            },
+            None => ()
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Look in");
+            ui.text_edit_singleline(&mut self.new_folder);
+
+            if ui.button("Add folder").clicked() && !self.new_folder.trim().is_empty() {
+                let folder = String::from(self.new_folder.trim());
+                if !self.folders.contains(&folder) {
+                    self.folders.push(folder);
+                }
+
+                self.new_folder.clear();
+                self.refresh();
+            }
+
+            if ui.button("Refresh").clicked() {
+                self.refresh();
+            }
+
+            if self.receiver.is_some() {
+                ui.spinner();
+            }
+        });
+
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Folders:");
+            let mut removed = None;
+            for (index, folder) in self.folders.iter().enumerate() {
+                ui.monospace(folder);
+                if ui.small_button("Remove").on_hover_text("Stop looking here.").clicked() {
+                    removed = Some(index);
+                }
+            }
+
+            match removed {
+                Some(index) => {
+                    self.folders.remove(index);
+                    self.refresh();
+                },
+                None => ()
+            }
+        });
+
+        for problem in &self.problems {
+            ui.label(problem);
+        }
+
+        ui.add_space(DEFAULT_SPACE);
+
+        if self.workspaces.is_empty() && self.receiver.is_none() {
+            ui.label("No workspaces found.");
+            return None;
+        }
+
+        let mut opened = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new(BROWSER_GRID_ID)
+                .num_columns(4)
+                .striped(true)
+                .spacing([DEFAULT_SPACE * 4.0, DEFAULT_SPACE])
+                .show(ui, |ui| {
+                    for workspace in &self.workspaces {
+                        if ui.button("Open").on_hover_text(&workspace.path).clicked() {
+                            opened = Some(workspace.path.clone());
+                        }
+
+                        ui.vertical(|ui| {
+                            match &workspace.version {
+                                Some(version) => ui.strong(format!("{} {}", workspace.name, version)),
+                                None => ui.strong(&workspace.name)
+                            };
+                            ui.label(&workspace.authors);
+                        });
+
+                        ui.vertical(|ui| {
+                            ui.set_max_width(ABOUT_WIDTH);
+                            match &workspace.error {
+                                Some(e) => ui.label(format!("Couldn't read module: {}", e)),
+                                None => ui.label(&workspace.about)
+                            };
+
+                            if !workspace.tags.is_empty() {
+                                ui.weak(workspace.tags.join(", "));
+                            }
+                        });
+
+                        ui.label(match workspace.modified {
+                            Some(modified) => interface_utils::format_age(modified),
+                            None => String::new()
+                        });
+
+                        ui.end_row();
+                    }
+                });
+        });
+
+        return opened;
+    }
+}
@@ -1,26 +1,40 @@
 pub mod consts;
 pub mod runtime;
 pub mod interface;
-pub mod console;
 
-use console::ConsoleReceiver;
-use runtime::{ Runtime, runtime_data::RuntimeData, runtime_data::RuntimeState };
+use mlem_console::ConsoleReceiver;
+use runtime::{ Runtime, dry_wet::DryWet, preset::Preset, repl::{ Repl, ReplResult }, runtime_data::RuntimeData, runtime_data::RuntimeState };
 use interface::{ interface_data::InterfaceData, Interface };
 use nih_plug::prelude::*;
-use std::sync::{ Arc, RwLock };
+use std::{ collections::BTreeMap, sync::{ Arc, Mutex, RwLock } };
 use nih_plug_egui::EguiState;
 
 pub struct LuaGarden {
     runtime: Runtime,
+    dry_wet: DryWet,
+    latency_samples: u32,
     params: Arc<LuaGardenParams>,
     runtime_data: Arc<RwLock<RuntimeData>>,
-    interface_data: Arc<RwLock<InterfaceData>>
+    interface_data: Arc<RwLock<InterfaceData>>,
+    // Console snippets from the editor, while it is open.
+    repl: Option<Arc<Mutex<Repl>>>
 }
 
 #[derive(Params)]
 pub struct LuaGardenParams {
     #[persist = "editor-state"]
     editor_state: Arc<EguiState>,
+    // Drafts keep their presets here, workspaces in their presets folder.
+    #[persist = "draft-presets"]
+    draft_presets: Arc<RwLock<BTreeMap<String, Preset>>>,
+
+    // Around the module, so any module works as a parallel effect.
+    #[id = "input-gain"]
+    input_gain: FloatParam,
+    #[id = "mix"]
+    mix: FloatParam,
+    #[id = "output-gain"]
+    output_gain: FloatParam,
 }
 
 impl Default for LuaGarden {
@@ -29,9 +43,12 @@ impl Default for LuaGarden {
 
         Self {
             runtime: runtime,
+            dry_wet: DryWet::new(),
+            latency_samples: 0,
             params: Arc::new(LuaGardenParams::default()),
             runtime_data: Arc::from(RwLock::new(RuntimeData::new())),
-            interface_data: Arc::from(RwLock::new(InterfaceData::new()))
+            interface_data: Arc::from(RwLock::new(InterfaceData::new())),
+            repl: None
         }
     }
 }
@@ -39,39 +56,96 @@ impl Default for LuaGarden {
 impl Default for LuaGardenParams {
     fn default() -> Self {
         Self {
-            editor_state: EguiState::from_size(consts::WINDOW_SIZE_WIDTH, consts::WINDOW_SIZE_HEIGHT)
+            editor_state: EguiState::from_size(consts::WINDOW_SIZE_WIDTH, consts::WINDOW_SIZE_HEIGHT),
+            draft_presets: Arc::new(RwLock::new(BTreeMap::new())),
+
+            input_gain: gain_param("Input Gain"),
+            mix: FloatParam::new("Mix", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(consts::PARAMETER_SMOOTHING_MS))
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            output_gain: gain_param("Output Gain")
         }
     }
 }
 
+fn gain_param(name: &str) -> FloatParam {
+    return FloatParam::new(name, util::db_to_gain(0.0), FloatRange::Skewed {
+            min: util::db_to_gain(-consts::GAIN_RANGE_DB),
+            max: util::db_to_gain(consts::GAIN_RANGE_DB),
+            factor: FloatRange::gain_skew_factor(-consts::GAIN_RANGE_DB, consts::GAIN_RANGE_DB)
+        })
+        .with_smoother(SmoothingStyle::Logarithmic(consts::PARAMETER_SMOOTHING_MS))
+        .with_unit(" dB")
+        .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+        .with_string_to_value(formatters::s2v_f32_gain_to_db());
+}
+
 impl LuaGarden {
-    fn update_runtime_status(&self, runtime_data: &mut RuntimeData) {
+    fn update_runtime_status(&mut self, runtime_data: &mut RuntimeData) {
         runtime_data.sample_rate = self.runtime.get_sample_rate();
         runtime_data.buffer_size = self.runtime.get_buffer_size();
         runtime_data.channels = self.runtime.get_channels();
         runtime_data.run_ms = self.runtime.get_run_ms();
+        runtime_data.latency_samples = self.latency_samples;
+        runtime_data.muted = self.runtime.is_muted();
+        self.runtime.update_slot_status(&mut runtime_data.slots);
+        self.runtime.update_profile(&mut runtime_data.profile);
+
+        if runtime_data.last_error != self.runtime.last_error {
+            runtime_data.last_error = self.runtime.last_error.clone();
+        }
+    }
+
+    fn clear_runtime_module(&mut self){
+        self.runtime.clear();
     }
 
-    fn refresh_runtime_module(&mut self, interface_data: &InterfaceData) {
-        match interface_data.mode.clone() {
-            interface::InterfaceMode::Draft => {
-                let content = interface_data.draft_content.clone();
-                self.runtime.load_new_module(content);
+    // Between blocks, so snippets never see a module halfway through a run.
+    // One per block, the editor may be holding the lock, then the snippets wait for the next block.
+    fn process_repl(&mut self) {
+        let repl_lock = match &self.repl {
+            Some(r) => r,
+            None => return
+        };
+
+        let mut repl = match repl_lock.try_lock() {
+            Ok(r) => r,
+            Err(_) => return
+        };
+
+        match repl.take_command() {
+            Some(command) => {
+                let output = self.runtime.evaluate(&command);
+                repl.add_result(ReplResult { code: command.code, output: output });
             },
-            interface::InterfaceMode::Workspace => {
-                match &interface_data.workspace {
-                    Some(w) => {
-                        let content = w.content.clone();
-                        self.runtime.load_new_module(content);
-                    },
-                    None => ()
-                }
+            None => ()
+        }
+    }
+
+    // Keeps the dry signal from before the input gain, delayed to line up with the module.
+    fn process_input(&mut self, buffer: &mut Buffer) {
+        self.dry_wet.set_latency(self.runtime.get_latency_samples() as usize);
+        self.dry_wet.capture(buffer.as_slice_immutable());
+
+        for channel_samples in buffer.iter_samples() {
+            let gain = self.params.input_gain.smoothed.next();
+            for sample in channel_samples {
+                *sample *= gain;
             }
         }
     }
 
-    fn clear_runtime_module(&mut self){
-        self.runtime.load_module(None);
+    fn process_output(&mut self, buffer: &mut Buffer) {
+        for (index, channel_samples) in buffer.iter_samples().enumerate() {
+            let mix = self.params.mix.smoothed.next();
+            let gain = self.params.output_gain.smoothed.next();
+
+            for (channel, sample) in channel_samples.into_iter().enumerate() {
+                *sample = (self.dry_wet.dry(channel, index) * (1.0 - mix) + *sample * mix) * gain;
+            }
+        }
     }
 }
 
@@ -109,9 +183,11 @@ impl Plugin for LuaGarden {
         let params = self.params.clone();
         let runtime_status = self.runtime_data.clone();
         let interface_data = self.interface_data.clone();
-        let interface = Interface::new();
+        let mut interface = Interface::new(self.runtime.handoff());
         
         self.runtime.console = Some(interface.console.create_sender());
+        self.runtime.telemetry = Some(interface.telemetry.create_sender());
+        self.repl = Some(interface.repl.clone());
         let editor = interface.create_interface(editor_state, params, runtime_status, interface_data);
 
         return editor;
@@ -125,6 +201,10 @@ impl Plugin for LuaGarden {
     ) -> bool {
         let _ = self.runtime.init(Some(_buffer_config.sample_rate));
 
+        let channels = _audio_io_layout.main_output_channels.map_or(0, |c| c.get() as usize);
+        self.dry_wet.prepare(channels, _buffer_config.max_buffer_size as usize);
+        self.runtime.prepare(channels, _buffer_config.max_buffer_size as usize);
+
         return true;
     }
 
@@ -136,6 +216,7 @@ impl Plugin for LuaGarden {
             let runtime_success = self.runtime.reset();
         
             if !runtime_success {
+                self.runtime.fail();
                 runtime_data.set_state(RuntimeState::Offline);
             }
         }
@@ -145,29 +226,35 @@ impl Plugin for LuaGarden {
         &mut self,
         buffer: &mut Buffer,
         _aux: &mut AuxiliaryBuffers,
-        _context: &mut impl ProcessContext<Self>,
+        context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
         let runtime_data_lock = self.runtime_data.clone();
         let mut runtime_data = runtime_data_lock.write().unwrap();
         let interface_data = self.interface_data.read().unwrap().clone();
 
         runtime_data.update_from_interface(&interface_data);
+        self.runtime.arrange(&runtime_data.rack_order);
+        self.runtime.set_crossfade_ms(runtime_data.crossfade_ms);
 
         match runtime_data.state {
-            RuntimeState::Refresh => {
-                self.refresh_runtime_module(&interface_data);
-                runtime_data.set_state(RuntimeState::Online);
-    
-                let runtime_success = self.runtime.init(None);            
-                if !runtime_success {
-                    runtime_data.set_state(RuntimeState::Offline);
-                }
-    
-                let runtime_success = self.runtime.reset();
-            
-                if !runtime_success {
-                    runtime_data.set_state(RuntimeState::Offline);
-                }
+            RuntimeState::Refresh => match self.runtime.swap_in_loaded(runtime_data.load_id) {
+                Some(initialized) => {
+                    runtime_data.set_state(RuntimeState::Online);
+
+                    if !initialized {
+                        self.runtime.fail();
+                        runtime_data.set_state(RuntimeState::Offline);
+                    }
+
+                    let runtime_success = self.runtime.reset();
+
+                    if !runtime_success {
+                        self.runtime.fail();
+                        runtime_data.set_state(RuntimeState::Offline);
+                    }
+                },
+                // Still loading.
+                None => ()
             },
             RuntimeState::Clear => {
                 self.clear_runtime_module();
@@ -176,14 +263,37 @@ impl Plugin for LuaGarden {
             _ => ()
         }
 
-        if runtime_data.state == RuntimeState::Online {
+        self.process_repl();
+        self.process_input(buffer);
+
+        let running = match runtime_data.state {
+            RuntimeState::Online => true,
+            // The loader is building the new rack, the old one keeps playing until it is swapped in.
+            RuntimeState::Refresh => self.runtime.is_running(),
+            _ => false
+        };
+
+        if running {
             self.runtime.set_clip(runtime_data.clip);
             self.runtime.set_input_noise(runtime_data.input_noise);
+            self.runtime.set_safety_stop(runtime_data.safety_stop);
+            self.runtime.set_profiling(runtime_data.profiling, runtime_data.sampling);
             let runtime_success = self.runtime.run(buffer);
     
-            if !runtime_success {
+            if !runtime_success && runtime_data.state == RuntimeState::Online {
                 runtime_data.set_state(RuntimeState::Offline);
             }
+        } else {
+            // Whatever was playing before PANIC or a failure fades into the dry signal.
+            self.runtime.fade_out(buffer);
+        }
+
+        self.process_output(buffer);
+
+        let latency_samples = self.runtime.get_latency_samples();
+        if latency_samples != self.latency_samples {
+            context.set_latency_samples(latency_samples);
+            self.latency_samples = latency_samples;
         }
         
         runtime_data.update_from_runtime(&mut self.runtime, &interface_data);
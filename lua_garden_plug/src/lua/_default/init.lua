@@ -6,6 +6,20 @@
 -- MODULE_NAME - This module's name.
 -- MODULE_AUTHORS - Who made this module.
 -- MODULE_ABOUT - A desciption of the module.
+--                In a workspace, module.toml can set these instead, along with a version, tags and parameter defaults.
+-- require - Loads other .lua files, e.g. require("dsp.filters") loads dsp/filters.lua from the workspace,
+--           then from the user library folder. Drafts can add files with the + tab.
+-- resources - Load audio files relative to the workspace, e.g. resources.load_wav("kick.wav", true).
+--             Pass true to resample to SAMPLE_RATE. Only available in workspace mode.
+-- osc - Band-limited oscillators, e.g. osc.saw(110), osc.pulse(220, 0.25), osc.wavetable(samples) or osc.noise("pink").
+--       Call :run() once per sample, optionally with a new frequency and phase offset.
+-- envelope - Envelope generators, e.g. envelope.adsr(10, 100, 0.5, 200), envelope.ar(5, 50) or envelope.one_shot(1, 10, 100).
+--            Times are in ms. Use :gate_on() and :gate_off() from run.lua or trigger.lua, and :run() once per sample.
+-- random - Seedable random numbers, e.g. random:uniform(), random:bipolar(), random:gaussian(0, 0.5) or random:integer(1, 6).
+--          Set RANDOM_SEED = 1234 at the top of init.lua for the same sequence on every load and reset.
+--          random:new(seed) creates an independent generator.
+-- TUNING - Scale and reference pitch used by pitch.*, e.g. TUNING:set_scale({ 0, 2, 4, 5, 7, 9, 11 }) or TUNING:equal_temperament(19).
+--          Load Scala files with pitch.load_scala("scale.scl", "mapping.kbm"), the mapping is optional.
 
 MODULE_NAME = "Empty module";
 MODULE_AUTHORS = "???";
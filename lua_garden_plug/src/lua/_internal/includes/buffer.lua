@@ -5,6 +5,7 @@ Buffer = {
     channels = 0,
 }
 
+-- An empty buffer, index it with buffer[channel][sample].
 function Buffer:new(size, channels)
     self.__index = self;
     return setmetatable({
@@ -13,6 +14,7 @@ function Buffer:new(size, channels)
     }, self);
 end
 
+-- Copies a raw buffer table into a new Buffer.
 function Buffer:copy(from_buffer, from_buffer_size, from_buffer_channels)
     local buffer = Buffer:new();
     buffer.size = from_buffer_size;
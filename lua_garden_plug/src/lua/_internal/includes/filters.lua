@@ -12,6 +12,7 @@ SVF = {
     damp = 0.0
 }
 
+-- State variable filter, cutoff in hz and resonance 0..1.
 function SVF:new (cutoff, resonance)
     self.__index = self;
     local svf = setmetatable({
@@ -22,6 +23,7 @@ function SVF:new (cutoff, resonance)
     return svf
 end
 
+-- Filters one sample, returns a table with low, high, band and notch outputs.
 function SVF:run (input)
     self:compute_coeff();
     local low = 0.0;
@@ -1,11 +1,14 @@
 -- Audio generators. Things that produce sound.
+-- These are naive and alias at high frequencies. Use the band-limited osc.* oscillators for audible tones.
 
 gen = { };
 
+-- Sine for a phase in 0..2.
 function gen.sine (phase)
     return math.sin(phase * math.pi)
 end
 
+-- Triangle for a phase in 0..2.
 function gen.tri (phase)
     local f = phase % 2
     if f < 0.5 then
@@ -17,6 +20,7 @@ function gen.tri (phase)
     end
 end
 
+-- Square for a phase in 0..2.
 function gen.square (phase)
     if phase % 2 < 1 then
         return 1
@@ -25,15 +29,18 @@ function gen.square (phase)
     end
 end
 
+-- Rising saw for a phase in 0..2.
 function gen.sawUp (phase)
     local f = (phase + 1) % 2
     return f - 1
 end
 
+-- Falling saw for a phase in 0..2.
 function gen.sawDown (phase)
     return -gen.sawUp(phase)
 end
 
+-- White noise from the module's random generator.
 function gen.noise ()
-    return math.random(-1, 1);
+    return random:bipolar();
 end
\ No newline at end of file
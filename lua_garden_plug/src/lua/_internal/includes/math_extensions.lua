@@ -1,5 +1,6 @@
 -- Additions to lua's math library.
 
+-- 1 for positive values, -1 otherwise.
 function math.sign (value)
     if value > 0.0 then
         return 1.0;
@@ -8,14 +9,17 @@ function math.sign (value)
     end
 end
 
+-- value raised to power.
 function math.pow (value, power)
     return value ^ power;
 end
 
+-- Rounds to the nearest whole number, halves round away from zero.
 function math.round (value)
     return math.floor(value + 0.5 * math.sign(value));
 end
 
+-- Keeps value between min and max.
 function math.clamp(value, min, max)
     if value < min then
         return min;
@@ -26,20 +30,24 @@ function math.clamp(value, min, max)
     end
 end
 
+-- Keeps value between 0 and 1.
 function math.clamp01 (value)
     return math.clamp(value, 0, 1);
 end
 
+-- Linear interpolation from a to b, t is clamped to 0..1.
 function math.lerp (a, b, t) 
     return a + (b - a) * math.clamp01(t);
 end
 
+-- Where value sits between a and b, as 0..1.
 function math.inverse_lerp (a, b, value)
     if a ~= b then
         return math.clamp01((value - a) / (b - a));
     end
 end
 
+-- Interpolates from a to b along an exponential curve, tension blends between ease in and ease out.
 function math.tense_lerp (a, b, t, tension)
     local c = 1.0;
     local d = 1.0;
@@ -54,6 +62,7 @@ function math.tense_lerp (a, b, t, tension)
     return math.lerp(a, b, math.lerp(t0, t1, tension));
 end
 
+-- Moves current towards target by at most maxDelta.
 function math.move_towards (current, target, maxDelta)
     if math.abs(target - current) <= maxDelta then
         return target;
@@ -62,6 +71,7 @@ function math.move_towards (current, target, maxDelta)
     return current + math.sign(target - current) * maxDelta;
 end
 
+-- Converts a linear gain to decibels, silence is -114dB.
 function math.linear_to_db (linear)
     if linear <= 0.0 then
         return -114.0;
@@ -70,6 +80,7 @@ function math.linear_to_db (linear)
     return 20.0 * math.log(linear, 10);
 end
 
+-- Converts decibels to a linear gain.
 function math.db_to_linear (db)
     return 10.0 ^ (db / 20.0);
 end
\ No newline at end of file
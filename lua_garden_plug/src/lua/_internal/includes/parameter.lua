@@ -16,6 +16,7 @@ Parameter = {
     set_tick = 0
 }
 
+-- A parameter shown in the interface, call from init.lua. Names must be unique.
 function Parameter:new (name, value, min, max, step_size, smoothing_ms)
     self.__index = self;
     local parameter = setmetatable({
@@ -36,9 +37,10 @@ function Parameter:new (name, value, min, max, step_size, smoothing_ms)
     return parameter
 end
 
+-- Makes the parameter visible to the interface, done by Parameter:new.
 function Parameter:register ()
     if PARAMETERS[self.name] ~= nil then
-        runtime.log(string.format("A parameter with the name \"%s\" is already registered. Parameter names must be unique. The new parameter will not be registered.", self.name));
+        runtime.warn(string.format("A parameter with the name \"%s\" is already registered. Parameter names must be unique. The new parameter will not be registered.", self.name));
         return;
     end
     
@@ -46,12 +48,20 @@ function Parameter:register ()
     PARAMETERS[self.name] = self;
 end
 
+-- Jumps to a value without smoothing, used for the defaults in module.toml.
+function Parameter:set_default (value)
+    self.value = math.clamp(value, self.min, self.max);
+    self.old_value = self.value;
+end
+
+-- Sets a new value, get_smoothed glides towards it.
 function Parameter:set_value (value)
     self.value = math.clamp(value, self.min, self.max);
     self.old_value = self:get_smoothed();
     self.set_tick = TICK;
 end
 
+-- The value smoothed over smoothing_ms, call once per sample.
 function Parameter:get_smoothed ()
     local smoothing_samples = SAMPLE_RATE / 1000.0 * self.smoothing_ms;
     local t = math.clamp((TICK - self.set_tick) / smoothing_samples, 0.0, 1.0);
@@ -64,6 +74,7 @@ function Parameter:get_smoothed ()
     end
 end
 
+-- The value without smoothing.
 function Parameter:get_raw ()
     if self.step_size > 0 then
         return self.value;
@@ -72,6 +83,7 @@ function Parameter:get_raw ()
     end
 end
 
+-- Applies values changed in the interface, done before every run.
 function Parameter.update_values_from_global ()
     if PARAMETER_VALUE_UPDATES == nil then
         return;
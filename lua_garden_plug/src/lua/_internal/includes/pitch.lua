@@ -1,46 +1,95 @@
--- Chromatic pitch calculations.
--- TODO test. Lua's indexes are fucking with me. No clue if this is right.
+-- Pitch calculations on top of the native TUNING, supports Scala scales and keyboard mappings.
 
 pitch = {
-    tuning_frequency = 440.0,
-    base_octave = 4,
-    notes_per_octave = 12,
-    note_names = { "A", "A#", "B", "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#" }
+    tuning = TUNING,
+    base_octave = 4
 };
 
 pitch.min_audible_frequency = 20.0;     -- Purves D, Augustine GJ, Fitzpatrick D, et al., editors. Sunderland(MA) : Sinauer Associates; 2001.
 pitch.max_audible_frequency = 20000.0;  -- Neuroscience. 2nd edition.
 
+-- Keep the old fields working, they now live in the tuning.
+setmetatable(pitch, {
+    __index = function (table, key)
+        if key == "tuning_frequency" then
+            return TUNING.reference_frequency;
+        elseif key == "notes_per_octave" then
+            return TUNING.notes_per_octave;
+        end
+    end,
+    __newindex = function (table, key, value)
+        if key == "tuning_frequency" then
+            TUNING.reference_frequency = value;
+        else
+            rawset(table, key, value);
+        end
+    end
+});
+
+-- The note TUNING.reference_frequency is tuned to.
 function pitch.base_note ()
-    return pitch.base_octave * pitch.notes_per_octave;
+    return TUNING.reference_note;
 end
 
+-- Frequency of a note, fractional notes glide between scale steps.
 function pitch.note_hz (note)
-    return pitch.tuning_frequency * 2.0 ^ ((note - pitch.base_note()) / pitch.notes_per_octave);
+    return TUNING:note_hz(note);
 end
 
-
+-- Playback rate for a sample shifted by note steps.
 function pitch.note_to_playback (note)
     if note == 0.0 then
        return 1.0;
     end
 
-    return 1.0 * 2.0 ^ (note / pitch.notes_per_octave);
+    return TUNING:note_hz(pitch.base_note() + note) / TUNING:note_hz(pitch.base_note());
 end
 
+-- The note closest to hz.
 function pitch.closest_note (hz)
-    return math.floor(pitch.notes_per_octave * math.log(hz / pitch.tuning_frequency, 2.0) + pitch.base_note());
+    return TUNING:closest_note(hz);
+end
+
+-- Frequency of the note closest to hz.
+function pitch.closest_frequency (hz)
+    return TUNING:closest_frequency(hz);
+end
+
+-- Snaps hz to the closest note in the active scale.
+function pitch.quantize (hz)
+    return TUNING:quantize(hz);
+end
+
+-- Snaps a note to the closest note in the active scale.
+function pitch.quantize_note (note)
+    return TUNING:quantize_note(note);
+end
+
+-- Limits quantizing to these scale degrees, e.g. { 0, 2, 4, 5, 7, 9, 11 }.
+function pitch.set_scale (degrees)
+    TUNING:set_scale(degrees);
 end
 
-function pitch.closest_frequency (hz) 
-    return pitch.note_hz(pitch.closest_note(hz));
+-- Names for each step of the scale, starting at the reference note.
+function pitch.set_note_names (names)
+    TUNING:set_note_names(names);
 end
 
+-- Octave number of a note.
 function pitch.note_to_octave (note)
-    return math.floor(note / pitch.notes_per_octave);
+    return pitch.base_octave + TUNING:octave(note);
 end
 
+-- Name of a note, like "A".
 function pitch.note_name (note)
-    local note_in_octave = math.floor(note) % pitch.notes_per_octave;
-    return pitch.note_names[note_in_octave];
-end
\ No newline at end of file
+    return TUNING:note_name(note);
+end
+
+-- Only works in init.lua, paths are relative to the workspace.
+function pitch.load_scala (scl_path, kbm_path)
+    TUNING:load_scl(resources.load_text(scl_path));
+
+    if kbm_path ~= nil then
+        TUNING:load_kbm(resources.load_text(kbm_path));
+    end
+end
@@ -2,14 +2,65 @@
 
 LOGS = { };
 LOG_COUNT = 0;
+METERS = { };
+WATCHES = { };
+STFT = nil;
 
 runtime = { };
 
-function runtime.log (log)
+local function add_log (level, log)
     LOG_COUNT = LOG_COUNT + 1;
-    LOGS[LOG_COUNT] = tostring(log);
+    LOGS[LOG_COUNT] = { level = level, message = tostring(log) };
+end
+
+-- Prints to the console.
+function runtime.log (log)
+    add_log("info", log);
+end
+
+-- Prints a warning to the console.
+function runtime.warn (log)
+    add_log("warn", log);
+end
+
+-- Prints an error to the console, the module keeps running.
+function runtime.error (log)
+    add_log("error", log);
+end
+
+-- Shows value as a bar in the Interface view, between min and max (0 and 1 by default).
+function runtime.meter (name, value, min, max)
+    name = tostring(name);
+
+    -- Reused every block, so metering doesn't make garbage.
+    local meter = METERS[name];
+    if meter == nil then
+        meter = { };
+        METERS[name] = meter;
+    end
+
+    meter.value = tonumber(value) or 0;
+    meter.min = tonumber(min) or 0;
+    meter.max = tonumber(max) or 1;
+    meter.fresh = true;
+end
+
+-- Shows value in the Interface view, numbers get a small history graph.
+function runtime.watch (name, value)
+    WATCHES[tostring(name)] = value;
+end
+
+-- Enables spectral processing. Call from init.lua and define a spectral(frame) function.
+-- Each frame holds magnitude and phase tables with frame.bins entries for frame.channel.
+function runtime.stft (fft_size, hop_size, window)
+    STFT = {
+        fft_size = fft_size,
+        hop_size = hop_size or math.floor(fft_size / 4),
+        window = window or "hann"
+    };
 end
 
+-- Calls tick(sample) for every sample in the buffer and keeps TICK up to date.
 function runtime.iterate (tick)
     local start_tick = TICK;
     for b = 1, BUFFER.size do
@@ -17,7 +68,7 @@ function runtime.iterate (tick)
 
         if INPUT_NOISE then
             for c = 1, BUFFER.channels do 
-                BUFFER[c][b] = gen.noise() * 0.1;
+                BUFFER[c][b] = random:bipolar() * 0.1;
             end
         end
 
@@ -0,0 +1,18 @@
+MODULE_NAME = "Spectral Gate";
+MODULE_AUTHORS = "lua_garden";
+MODULE_ABOUT = [[Silences every frequency below a threshold.
+Uses the STFT, so it adds latency.]];
+
+Threshold = Parameter:new("threshold", -60, -114, 0, 0);
+
+runtime.stft(2048, 512, "hann");
+
+function spectral(frame)
+    local threshold = math.db_to_linear(Threshold:get_smoothed());
+
+    for bin = 1, frame.bins do
+        if frame.magnitude[bin] < threshold then
+            frame.magnitude[bin] = 0.0;
+        end
+    end
+end
@@ -0,0 +1,223 @@
+use std::collections::BTreeSet;
+use super::{ envelopes, library, oscillators, random, require, resources, tuning };
+
+// Goes up when modules written for this version stop working on older ones, checked against module.toml.
+pub const API_VERSION: u32 = 1;
+
+// Globals the runtime sets itself, everything else comes from the native modules and the internal includes.
+const RUNTIME_API: [(&str, &str); 11] = [
+    ("BUFFER", "Audio of the current block, BUFFER[channel][sample]. Write to it in run.lua."),
+    ("BUFFER_SIZE", "Samples per channel in the current block."),
+    ("CHANNELS", "Number of channels in the current block."),
+    ("SAMPLE_RATE", "The sample rate the plugin is running at."),
+    ("TICK", "Samples processed since the module was loaded."),
+    ("INPUT_NOISE", "True when the input is replaced by noise."),
+    ("MODULE_NAME", "This module's name, set it in init.lua."),
+    ("MODULE_AUTHORS", "Who made this module, set it in init.lua."),
+    ("MODULE_ABOUT", "A description of the module, set it in init.lua."),
+    ("RANDOM_SEED", "Set in init.lua to make random numbers the same on every load and reset."),
+    ("spectral(frame)", "Define to process STFT frames, enable with runtime.stft(fft_size, hop_size, window).")
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SymbolKind {
+    Value,
+    Function,
+    Method
+}
+
+#[derive(Clone)]
+pub struct ApiSymbol {
+    pub name: String,
+    pub signature: String,
+    pub doc: String,
+    pub kind: SymbolKind
+}
+
+impl ApiSymbol {
+    // Takes a signature like "osc.saw(frequency?)" or "Oscillator:run()" or a plain name like "BUFFER".
+    pub fn new(signature: &str, doc: &str) -> ApiSymbol {
+        let name = match signature.find('(') {
+            Some(index) => &signature[..index],
+            None => signature
+        };
+
+        let kind = if name.contains(':') {
+            SymbolKind::Method
+        } else if signature.contains('(') {
+            SymbolKind::Function
+        } else {
+            SymbolKind::Value
+        };
+
+        Self {
+            name: String::from(name.trim()),
+            signature: String::from(signature),
+            doc: String::from(doc),
+            kind: kind
+        }
+    }
+
+    // "Oscillator:run" -> "run"
+    pub fn method_name(&self) -> &str {
+        return match self.name.rfind(':') {
+            Some(index) => &self.name[index + 1..],
+            None => &self.name
+        };
+    }
+}
+
+// Every symbol a module can use, with signatures and one line docs.
+pub fn registry() -> Vec<ApiSymbol> {
+    let mut symbols: Vec<ApiSymbol> = Vec::new();
+
+    let native_api: [&[(&str, &str)]; 7] = [
+        &RUNTIME_API,
+        &oscillators::API,
+        &envelopes::API,
+        &random::API,
+        &resources::API,
+        &tuning::API,
+        &require::API
+    ];
+
+    for api in native_api {
+        for (signature, doc) in api {
+            symbols.push(ApiSymbol::new(signature, doc));
+        }
+    }
+
+    for include in library::INTERNAL_INCLUDES {
+        symbols.extend(parse_lua_symbols(include.0));
+    }
+
+    symbols.sort_by(|a, b| a.name.cmp(&b.name));
+    symbols.dedup_by(|a, b| a.name == b.name);
+
+    return symbols;
+}
+
+// Top level names, for highlighting.
+pub fn global_names(symbols: &[ApiSymbol]) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+
+    for symbol in symbols {
+        match symbol.kind {
+            SymbolKind::Method => (),
+            _ => {
+                if !symbol.name.contains('.') {
+                    names.insert(symbol.name.clone());
+                }
+            }
+        }
+    }
+
+    return names;
+}
+
+// Reads "function foo.bar (a, b)" definitions and top level "NAME = " tables from an include.
+// The comment lines right above a definition become its doc.
+fn parse_lua_symbols(source: &str) -> Vec<ApiSymbol> {
+    let mut symbols = Vec::new();
+    let mut doc_lines: Vec<&str> = Vec::new();
+
+    for line in source.lines() {
+        if line.starts_with("--") {
+            doc_lines.push(line.trim_start_matches('-').trim());
+            continue;
+        }
+
+        let doc = doc_lines.join(" ");
+
+        if line.starts_with("function ") {
+            let definition = line["function ".len()..].trim();
+            match definition.find(')') {
+                Some(index) => {
+                    let signature = definition[..index + 1].replace(" (", "(");
+                    symbols.push(ApiSymbol::new(&signature, &doc));
+                },
+                None => ()
+            }
+        } else if !line.starts_with(' ') && !line.starts_with("local") {
+            match line.split_once('=') {
+                Some((name, _)) => {
+                    let name = name.trim();
+                    if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                        symbols.push(ApiSymbol::new(name, &doc));
+                    }
+                },
+                None => ()
+            }
+        }
+
+        doc_lines.clear();
+    }
+
+    return symbols;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ collections::BTreeMap, sync::{ atomic::AtomicBool, Arc } };
+    use mlua::prelude::*;
+    use super::*;
+
+    // Everything RuntimeModule::new registers natively, without running the includes.
+    fn registered_lua() -> Lua {
+        let lua = Lua::new();
+        random::register(&lua).unwrap();
+        require::register(&lua, &BTreeMap::new()).unwrap();
+        resources::register(&lua, None, Arc::new(AtomicBool::new(false))).unwrap();
+        oscillators::register(&lua, 48000.0).unwrap();
+        envelopes::register(&lua, 48000.0).unwrap();
+        tuning::register(&lua).unwrap();
+
+        return lua;
+    }
+
+    #[test]
+    fn every_native_symbol_is_registered() {
+        let lua = registered_lua();
+        let native_api: [&[(&str, &str)]; 6] = [&oscillators::API, &envelopes::API, &random::API, &resources::API, &tuning::API, &require::API];
+
+        for api in native_api {
+            for (signature, _) in api {
+                let symbol = ApiSymbol::new(signature, "");
+                let path = symbol.name.replace(':', ".");
+                let root = path.split('.').next().unwrap_or_default();
+                let exists = |path: &str| !lua.load(format!("return {}", path)).eval::<LuaValue>().unwrap().is_nil();
+
+                // Methods of types that only exist as instances, like Oscillator:run, can't be looked up.
+                if symbol.kind == SymbolKind::Method && !exists(root) { continue; }
+
+                assert!(exists(&path), "{} isn't registered.", symbol.name);
+            }
+        }
+    }
+
+    #[test]
+    fn every_registered_global_has_a_doc() {
+        let bare = Lua::new();
+        let lua = registered_lua();
+        let docs: BTreeMap<String, String> = registry().into_iter().map(|s| (s.name, s.doc)).collect();
+        let documented = |name: &str| docs.get(name).is_some_and(|doc| !doc.is_empty());
+
+        for pair in lua.globals().pairs::<String, LuaValue>() {
+            let (name, value) = pair.unwrap();
+            if bare.globals().contains_key(name.as_str()).unwrap() { continue; }
+
+            assert!(documented(&name), "{} has no doc.", name);
+
+            // The functions in the tables the modules registered, like osc.sine.
+            match value {
+                LuaValue::Table(table) => {
+                    for field in table.pairs::<String, LuaValue>() {
+                        let (field, _) = field.unwrap();
+                        assert!(documented(&format!("{}.{}", name, field)), "{}.{} has no doc.", name, field);
+                    }
+                },
+                _ => ()
+            }
+        }
+    }
+}
@@ -0,0 +1,371 @@
+use std::{ fs::{ self, File }, io::{ self, Read, Write }, path::{ Path, PathBuf } };
+use serde::{ Deserialize, Serialize };
+use zip::{ write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter };
+use super::{ library, module_content::ModuleContent };
+
+// A .garden file is a zip archive:
+//   garden.toml   - BundleHeader, always the first entry
+//   module/...    - the workspace as it is on disk, scripts, module.toml, required files, resources and presets
+// Bump BUNDLE_FORMAT_VERSION when this layout changes in a way older versions can't read.
+pub const BUNDLE_EXTENSION: &str = "garden";
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+const BUNDLE_HEADER_PATH: &str = "garden.toml";
+const BUNDLE_MODULE_FOLDER: &str = "module/";
+// Bundles come from elsewhere, these keep a broken or hostile one from filling the disk.
+const MAX_ENTRIES: usize = 4096;
+const MAX_UNPACKED_BYTES: u64 = 1024 * 1024 * 1024;
+// Hidden, so the workspace browser doesn't show a half unpacked import.
+const UNPACKING_PREFIX: &str = ".unpacking ";
+
+#[derive(Serialize, Deserialize)]
+struct BundleHeader {
+    format: u32,
+    name: String,
+    created_with: String
+}
+
+// Packs every file in a workspace folder, hidden files excepted.
+pub fn export_workspace(workspace_path: &str, name: &str, bundle_path: &str) -> Result<(), String> {
+    let mut files = Vec::new();
+
+    match list_files(Path::new(workspace_path), "", &mut files) {
+        Ok(_) => (),
+        Err(e) => return Err(format!("Couldn't read workspace: {}", e))
+    }
+
+    let mut contents = Vec::new();
+    for path in files {
+        match fs::read(Path::new(workspace_path).join(&path)) {
+            Ok(bytes) => contents.push((path, bytes)),
+            Err(e) => return Err(format!("Couldn't read \"{}\": {}", path, e))
+        }
+    }
+
+    return write_bundle(&contents, name, bundle_path);
+}
+
+// Packs a draft, which has no resources on disk.
+pub fn export_content(content: &ModuleContent, name: &str, bundle_path: &str) -> Result<(), String> {
+    let mut contents: Vec<(String, Vec<u8>)> = content.all_scripts().iter()
+        .map(|(path, code)| (String::from(*path), code.as_bytes().to_vec()))
+        .collect();
+
+    match &content.manifest {
+        Some(manifest) => match toml::to_string_pretty(manifest) {
+            Ok(text) => contents.push((String::from(library::MANIFEST_PATH), text.into_bytes())),
+            Err(e) => return Err(format!("Couldn't write {}: {}", library::MANIFEST_PATH, e))
+        },
+        None => ()
+    }
+
+    return write_bundle(&contents, name, bundle_path);
+}
+
+// Unpacks a bundle into a new folder inside workspaces_folder, returns the new workspace's path.
+pub fn import(bundle_path: &str, workspaces_folder: &str) -> Result<String, String> {
+    return import_within(bundle_path, workspaces_folder, MAX_ENTRIES, MAX_UNPACKED_BYTES);
+}
+
+// Unpacks next to where the workspace goes and moves it there once everything is out,
+// so a bundle that fails halfway leaves nothing behind.
+fn import_within(bundle_path: &str, workspaces_folder: &str, max_entries: usize, max_bytes: u64) -> Result<String, String> {
+    let file = match File::open(bundle_path) {
+        Ok(f) => f,
+        Err(e) => return Err(format!("Couldn't open \"{}\": {}", bundle_path, e))
+    };
+
+    let mut archive = match ZipArchive::new(file) {
+        Ok(a) => a,
+        Err(e) => return Err(format!("\"{}\" isn't a {} bundle: {}", bundle_path, BUNDLE_EXTENSION, e))
+    };
+
+    if archive.len() > max_entries {
+        return Err(format!("Bundle has {} entries, more than the {} a bundle can have.", archive.len(), max_entries));
+    }
+
+    let header = read_header(&mut archive)?;
+    if header.format > BUNDLE_FORMAT_VERSION {
+        return Err(format!("This bundle was made with a newer version of {} (format {}), this version reads up to format {}.",
+            header.created_with, header.format, BUNDLE_FORMAT_VERSION));
+    }
+
+    let parent = Path::new(workspaces_folder);
+    let target = free_folder(parent, &header.name);
+    let unpacking = parent.join(format!("{}{}", UNPACKING_PREFIX, target.file_name().unwrap_or_default().to_string_lossy()));
+    let _ = fs::remove_dir_all(&unpacking);
+
+    let unpacked = unpack(&mut archive, &unpacking, max_bytes).and_then(|_| {
+        fs::rename(&unpacking, &target).map_err(|e| format!("Couldn't move the workspace into place: {}", e))
+    });
+
+    return match unpacked {
+        Ok(_) => Ok(target.to_string_lossy().to_string()),
+        Err(e) => {
+            let _ = fs::remove_dir_all(&unpacking);
+            Err(e)
+        }
+    };
+}
+
+// Only what is in module/, anything else in the bundle is left alone.
+fn unpack(archive: &mut ZipArchive<File>, folder: &Path, max_bytes: u64) -> Result<(), String> {
+    let mut files = 0;
+    let mut unpacked_bytes = 0;
+
+    for index in 0..archive.len() {
+        let entry = match archive.by_index(index) {
+            Ok(e) => e,
+            Err(e) => return Err(format!("Couldn't read bundle: {}", e))
+        };
+
+        // enclosed_name refuses paths like ../../somewhere.
+        let relative = match entry.enclosed_name() {
+            Some(path) => match path.strip_prefix(BUNDLE_MODULE_FOLDER) {
+                Ok(relative) => relative.to_path_buf(),
+                Err(_) => continue
+            },
+            None => return Err(format!("Bundle contains an unsafe path: \"{}\"", entry.name()))
+        };
+
+        if entry.is_dir() || relative.as_os_str().is_empty() {
+            continue;
+        }
+
+        // The sizes in the archive can't be trusted, so reading stops just past what is left.
+        let left = max_bytes - unpacked_bytes;
+        let mut bytes = Vec::new();
+        match entry.take(left + 1).read_to_end(&mut bytes) {
+            Ok(_) => (),
+            Err(e) => return Err(format!("Couldn't unpack \"{}\": {}", relative.display(), e))
+        }
+
+        if bytes.len() as u64 > left {
+            return Err(format!("Bundle unpacks to more than {} MB.", max_bytes / 1024 / 1024));
+        }
+        unpacked_bytes += bytes.len() as u64;
+
+        match write_file(&folder.join(&relative), &bytes) {
+            Ok(_) => files += 1,
+            Err(e) => return Err(format!("Couldn't unpack \"{}\": {}", relative.display(), e))
+        }
+    }
+
+    if files == 0 {
+        return Err(format!("Bundle has nothing in {}.", BUNDLE_MODULE_FOLDER));
+    }
+
+    return Ok(());
+}
+
+fn write_bundle(contents: &[(String, Vec<u8>)], name: &str, bundle_path: &str) -> Result<(), String> {
+    let header = BundleHeader {
+        format: BUNDLE_FORMAT_VERSION,
+        name: String::from(name),
+        created_with: format!("{} {}", crate::consts::NAME, crate::consts::VERSION)
+    };
+
+    let header_text = match toml::to_string_pretty(&header) {
+        Ok(text) => text,
+        Err(e) => return Err(format!("Couldn't write bundle header: {}", e))
+    };
+
+    let result = File::create(bundle_path).map_err(|e| e.to_string()).and_then(|file| {
+        let mut writer = ZipWriter::new(file);
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        writer.start_file(BUNDLE_HEADER_PATH, options).map_err(|e| e.to_string())?;
+        writer.write_all(header_text.as_bytes()).map_err(|e| e.to_string())?;
+
+        for (path, bytes) in contents {
+            writer.start_file(format!("{}{}", BUNDLE_MODULE_FOLDER, path), options).map_err(|e| e.to_string())?;
+            writer.write_all(bytes).map_err(|e| e.to_string())?;
+        }
+
+        writer.finish().map_err(|e| e.to_string())?;
+        Ok(())
+    });
+
+    return match result {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Couldn't write \"{}\": {}", bundle_path, e))
+    };
+}
+
+fn read_header(archive: &mut ZipArchive<File>) -> Result<BundleHeader, String> {
+    let mut text = String::new();
+
+    match archive.by_name(BUNDLE_HEADER_PATH) {
+        Ok(mut entry) => match entry.read_to_string(&mut text) {
+            Ok(_) => (),
+            Err(e) => return Err(format!("Couldn't read {}: {}", BUNDLE_HEADER_PATH, e))
+        },
+        Err(_) => return Err(format!("Bundle has no {}.", BUNDLE_HEADER_PATH))
+    }
+
+    return toml::from_str(&text).map_err(|e| format!("Couldn't read {}: {}", BUNDLE_HEADER_PATH, e));
+}
+
+// "Crusher", then "Crusher 2" and so on, so importing never overwrites a workspace.
+fn free_folder(parent: &Path, name: &str) -> PathBuf {
+    let name: String = name.chars()
+        .map(|c| if c.is_alphanumeric() || c == ' ' || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    let name = match name.trim() {
+        "" => "module",
+        trimmed => trimmed
+    };
+
+    let mut folder = parent.join(name);
+    let mut count = 2;
+    while folder.exists() {
+        folder = parent.join(format!("{} {}", name, count));
+        count += 1;
+    }
+
+    return folder;
+}
+
+fn write_file(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) => fs::create_dir_all(parent)?,
+        None => ()
+    }
+
+    return fs::write(path, bytes);
+}
+
+fn list_files(directory: &Path, prefix: &str, files: &mut Vec<String>) -> io::Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+
+        // Skip hidden files and folders, like .git.
+        if file_name.starts_with('.') { continue; }
+
+        let relative_path = format!("{}{}", prefix, file_name);
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            list_files(&entry.path(), &format!("{}/", relative_path), files)?;
+        } else if file_type.is_file() {
+            files.push(relative_path);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_folder(name: &str) -> PathBuf {
+        let folder = std::env::temp_dir().join(format!("lua_garden_bundle_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+
+        return folder;
+    }
+
+    #[test]
+    fn workspace_round_trip() {
+        let root = temp_folder("round_trip");
+        let workspace = root.join("source");
+        write_file(&workspace.join("init.lua"), b"MODULE_NAME = \"Test\";").unwrap();
+        write_file(&workspace.join("dsp/filters.lua"), b"return { };").unwrap();
+        write_file(&workspace.join("kick.wav"), &[0, 1, 2, 255]).unwrap();
+        write_file(&workspace.join(".git/HEAD"), b"ignored").unwrap();
+
+        let bundle = root.join("test.garden");
+        export_workspace(workspace.to_str().unwrap(), "Test", bundle.to_str().unwrap()).unwrap();
+
+        let imported = PathBuf::from(import(bundle.to_str().unwrap(), root.to_str().unwrap()).unwrap());
+        assert_eq!(imported, root.join("Test"));
+        assert_eq!(fs::read(imported.join("dsp/filters.lua")).unwrap(), b"return { };");
+        assert_eq!(fs::read(imported.join("kick.wav")).unwrap(), vec![0, 1, 2, 255]);
+        assert!(!imported.join(".git").exists());
+
+        // Importing again doesn't overwrite.
+        let again = PathBuf::from(import(bundle.to_str().unwrap(), root.to_str().unwrap()).unwrap());
+        assert_eq!(again, root.join("Test 2"));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn newer_format_is_refused() {
+        let root = temp_folder("newer_format");
+        let bundle = root.join("future.garden");
+
+        let mut writer = ZipWriter::new(File::create(&bundle).unwrap());
+        writer.start_file(BUNDLE_HEADER_PATH, SimpleFileOptions::default()).unwrap();
+        writer.write_all(format!("format = {}\nname = \"Future\"\ncreated_with = \"x\"\n", BUNDLE_FORMAT_VERSION + 1).as_bytes()).unwrap();
+        writer.finish().unwrap();
+
+        assert!(import(bundle.to_str().unwrap(), root.to_str().unwrap()).is_err());
+        assert!(!root.join("Future").exists());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    fn write_test_bundle(path: &Path, files: &[(&str, &[u8])]) {
+        let mut writer = ZipWriter::new(File::create(path).unwrap());
+        writer.start_file(BUNDLE_HEADER_PATH, SimpleFileOptions::default()).unwrap();
+        writer.write_all(format!("format = {}\nname = \"Test\"\ncreated_with = \"x\"\n", BUNDLE_FORMAT_VERSION).as_bytes()).unwrap();
+
+        for (name, bytes) in files {
+            writer.start_file(*name, SimpleFileOptions::default()).unwrap();
+            writer.write_all(bytes).unwrap();
+        }
+
+        writer.finish().unwrap();
+    }
+
+    fn folder_names(folder: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(folder).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().to_string()).collect();
+        names.sort();
+
+        return names;
+    }
+
+    #[test]
+    fn refuses_bundles_without_a_module() {
+        let root = temp_folder("without_module");
+        let bundle = root.join("empty.garden");
+        write_test_bundle(&bundle, &[("readme.txt", b"hello")]);
+
+        assert!(import(bundle.to_str().unwrap(), root.to_str().unwrap()).is_err());
+        assert_eq!(folder_names(&root), vec!["empty.garden"]);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn limits_entries_and_size_and_leaves_nothing_behind() {
+        let root = temp_folder("limits");
+        let bundle = root.join("big.garden");
+        write_test_bundle(&bundle, &[("module/init.lua", &[b'-'; 600]), ("module/b.lua", &[b'-'; 600])]);
+        let (bundle_path, root_path) = (bundle.to_str().unwrap(), root.to_str().unwrap());
+
+        assert!(import_within(bundle_path, root_path, 2, 2000).unwrap_err().contains("entries"));
+        assert!(import_within(bundle_path, root_path, 3, 1000).unwrap_err().contains("more than"));
+        assert_eq!(folder_names(&root), vec!["big.garden"]);
+
+        let imported = import_within(bundle_path, root_path, 3, 1200).unwrap();
+        assert_eq!(PathBuf::from(imported), root.join("Test"));
+        assert_eq!(folder_names(&root), vec!["Test", "big.garden"]);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn not_a_bundle() {
+        let root = temp_folder("not_a_bundle");
+        let bundle = root.join("text.garden");
+        fs::write(&bundle, "hello").unwrap();
+
+        assert!(import(bundle.to_str().unwrap(), root.to_str().unwrap()).is_err());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
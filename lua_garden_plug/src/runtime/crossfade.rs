@@ -0,0 +1,81 @@
+use super::loader::LoadedRack;
+
+// What the output fades away from.
+pub enum FadeFrom {
+    // The unprocessed input, when nothing was playing before.
+    Dry,
+    // The last output frame, when the modules stopped working and can't be asked for more.
+    // The runtime holds on to it, so failing doesn't allocate.
+    Held,
+    // Modules that keep running on a copy of the input until the fade is over.
+    // They stay in the rack they were swapped out into, which goes back to the loader afterwards.
+    Modules(Box<LoadedRack>)
+}
+
+pub struct Crossfade {
+    pub from: FadeFrom,
+    position: usize,
+    length: usize
+}
+
+impl Crossfade {
+    pub fn new(from: FadeFrom, length: usize) -> Crossfade {
+        Self {
+            from: from,
+            position: 0,
+            length: length
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        return self.position >= self.length;
+    }
+
+    // Mixes from outgoing(channel, sample) into incoming, linearly over the fade.
+    pub fn apply(&self, incoming: &mut [&mut [f32]], outgoing: impl Fn(usize, usize) -> f32) {
+        for (channel, samples) in incoming.iter_mut().enumerate() {
+            for (index, sample) in samples.iter_mut().enumerate() {
+                let amount = ((self.position + index) as f32 / self.length as f32).min(1.0);
+                *sample = outgoing(channel, index) * (1.0 - amount) + *sample * amount;
+            }
+        }
+    }
+
+    pub fn advance(&mut self, samples: usize) {
+        self.position = (self.position + samples).min(self.length);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fades_across_blocks() {
+        let mut fade = Crossfade::new(FadeFrom::Dry, 4);
+        let mut block = [1.0, 1.0];
+
+        fade.apply(&mut [&mut block[..]], |_, _| 0.0);
+        fade.advance(2);
+        assert_eq!(block, [0.0, 0.25]);
+        assert!(!fade.is_finished());
+
+        let mut block = [1.0, 1.0, 1.0];
+        fade.apply(&mut [&mut block[..]], |_, _| 0.0);
+        fade.advance(3);
+        assert_eq!(block, [0.5, 0.75, 1.0]);
+        assert!(fade.is_finished());
+    }
+
+    #[test]
+    fn holds_the_last_frame() {
+        let fade = Crossfade::new(FadeFrom::Held, 2);
+        let mut left = [0.0, 0.0];
+        let mut right = [0.0, 0.0];
+        let held = vec![0.5, -0.5];
+
+        fade.apply(&mut [&mut left[..], &mut right[..]], |channel, _| held[channel]);
+        assert_eq!(left, [0.5, 0.25]);
+        assert_eq!(right, [-0.5, -0.25]);
+    }
+}
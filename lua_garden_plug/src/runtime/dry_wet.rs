@@ -0,0 +1,119 @@
+use super::spectral;
+
+// The most latency a module can report, its largest FFT.
+const MAX_LATENCY: usize = spectral::MAX_FFT_SIZE;
+
+// Keeps the input of a block around for mixing back in after the module ran.
+// The dry signal is delayed by the module's latency, so dry and wet line up.
+pub struct DryWet {
+    dry: Vec<Vec<f32>>,
+    // Always hold the last MAX_LATENCY samples, so the latency can change without allocating.
+    delay_lines: Vec<Vec<f32>>,
+    delay_position: usize,
+    latency: usize
+}
+
+impl DryWet {
+    pub fn new() -> DryWet {
+        Self {
+            dry: Vec::new(),
+            delay_lines: Vec::new(),
+            delay_position: 0,
+            latency: 0
+        }
+    }
+
+    // Allocates up front, so capturing doesn't allocate on the audio thread.
+    pub fn prepare(&mut self, channels: usize, max_samples: usize) {
+        self.dry = vec![vec![0.0; max_samples]; channels];
+        self.delay_lines = vec![vec![0.0; MAX_LATENCY + 1]; channels];
+        self.delay_position = 0;
+    }
+
+    // Only moves where the delay lines are read, what was already captured lines up right away.
+    pub fn set_latency(&mut self, latency: usize) {
+        self.latency = latency.min(MAX_LATENCY);
+    }
+
+    pub fn capture(&mut self, channels: &[&mut [f32]]) {
+        let samples = channels.first().map_or(0, |c| c.len());
+        let length = MAX_LATENCY + 1;
+
+        for (channel, input) in channels.iter().enumerate() {
+            if channel >= self.dry.len() || self.dry[channel].len() < input.len() { continue; }
+
+            let dry = &mut self.dry[channel];
+            let line = &mut self.delay_lines[channel];
+            let mut position = self.delay_position;
+            for (index, sample) in input.iter().enumerate() {
+                line[position] = *sample;
+                dry[index] = line[(position + length - self.latency) % length];
+                position = (position + 1) % length;
+            }
+        }
+
+        self.delay_position = (self.delay_position + samples) % length;
+    }
+
+    // The captured sample, silence for channels that weren't prepared.
+    pub fn dry(&self, channel: usize, sample: usize) -> f32 {
+        return match self.dry.get(channel) {
+            Some(dry) => dry.get(sample).copied().unwrap_or(0.0),
+            None => 0.0
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passes_through_without_latency() {
+        let mut dry_wet = DryWet::new();
+        dry_wet.prepare(1, 4);
+
+        let mut samples = [1.0, 2.0, 3.0];
+        dry_wet.capture(&[&mut samples[..]]);
+
+        assert_eq!(dry_wet.dry(0, 1), 2.0);
+        assert_eq!(dry_wet.dry(1, 1), 0.0);
+    }
+
+    #[test]
+    fn delays_by_latency_across_blocks() {
+        let mut dry_wet = DryWet::new();
+        dry_wet.prepare(2, 4);
+        dry_wet.set_latency(3);
+
+        let mut left = [1.0, 2.0];
+        let mut right = [5.0, 6.0];
+        dry_wet.capture(&[&mut left[..], &mut right[..]]);
+        assert_eq!((dry_wet.dry(0, 0), dry_wet.dry(0, 1)), (0.0, 0.0));
+
+        let mut left = [3.0, 4.0];
+        let mut right = [7.0, 8.0];
+        dry_wet.capture(&[&mut left[..], &mut right[..]]);
+        assert_eq!((dry_wet.dry(0, 0), dry_wet.dry(0, 1)), (0.0, 1.0));
+        assert_eq!((dry_wet.dry(1, 0), dry_wet.dry(1, 1)), (0.0, 5.0));
+    }
+
+    #[test]
+    fn changes_latency_without_losing_the_signal() {
+        let mut dry_wet = DryWet::new();
+        dry_wet.prepare(1, 4);
+
+        let mut samples = [1.0, 2.0, 3.0, 4.0];
+        dry_wet.capture(&[&mut samples[..]]);
+        dry_wet.set_latency(2);
+
+        let mut samples = [5.0, 6.0, 7.0, 8.0];
+        dry_wet.capture(&[&mut samples[..]]);
+        assert_eq!((dry_wet.dry(0, 0), dry_wet.dry(0, 3)), (3.0, 6.0));
+
+        dry_wet.set_latency(MAX_LATENCY * 2);
+        let mut samples = [9.0];
+        dry_wet.capture(&[&mut samples[..]]);
+        assert_eq!(dry_wet.dry(0, 0), 0.0);
+    }
+}
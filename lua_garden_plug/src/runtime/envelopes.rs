@@ -0,0 +1,398 @@
+use mlua::prelude::*;
+
+pub const LUA_ENVELOPES_KEY: &str = "envelope";
+const EXPONENTIAL_STEEPNESS: f32 = 5.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Curve {
+    Linear,
+    Exponential
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Stage {
+    Idle,
+    Attack,
+    Hold,
+    Decay,
+    Sustain,
+    Release
+}
+
+pub struct Envelope {
+    pub attack_ms: f32,
+    pub hold_ms: f32,
+    pub decay_ms: f32,
+    pub sustain: f32,
+    pub release_ms: f32,
+    pub curve: Curve,
+    pub one_shot: bool,
+
+    sample_rate: f32,
+    stage: Stage,
+    value: f32,
+    stage_start: f32,
+    stage_progress: f32
+}
+
+impl Curve {
+    pub fn from_name(name: &str) -> Option<Curve> {
+        return match name {
+            "linear" | "lin" => Some(Curve::Linear),
+            "exponential" | "exp" => Some(Curve::Exponential),
+            _ => None
+        };
+    }
+
+    fn shape(&self, t: f32) -> f32 {
+        return match self {
+            Curve::Linear => t,
+            Curve::Exponential => (1.0 - f32::exp(-EXPONENTIAL_STEEPNESS * t)) / (1.0 - f32::exp(-EXPONENTIAL_STEEPNESS))
+        };
+    }
+}
+
+impl Stage {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Stage::Idle => "idle",
+            Stage::Attack => "attack",
+            Stage::Hold => "hold",
+            Stage::Decay => "decay",
+            Stage::Sustain => "sustain",
+            Stage::Release => "release"
+        };
+    }
+}
+
+impl Envelope {
+    pub fn new(sample_rate: f32, curve: Curve) -> Envelope {
+        Self {
+            attack_ms: 0.0,
+            hold_ms: 0.0,
+            decay_ms: 0.0,
+            sustain: 1.0,
+            release_ms: 0.0,
+            curve: curve,
+            one_shot: false,
+
+            sample_rate: sample_rate,
+            stage: Stage::Idle,
+            value: 0.0,
+            stage_start: 0.0,
+            stage_progress: 0.0
+        }
+    }
+
+    pub fn gate_on(&mut self) {
+        // Start from the current value so retriggering doesn't click.
+        self.enter_stage(Stage::Attack);
+    }
+
+    pub fn gate_off(&mut self) {
+        if self.one_shot || self.stage == Stage::Idle || self.stage == Stage::Release { return; }
+
+        self.enter_stage(Stage::Release);
+    }
+
+    pub fn reset(&mut self) {
+        self.stage = Stage::Idle;
+        self.value = 0.0;
+        self.stage_start = 0.0;
+        self.stage_progress = 0.0;
+    }
+
+    pub fn is_active(&self) -> bool {
+        return self.stage != Stage::Idle;
+    }
+
+    pub fn run(&mut self) -> f32 {
+        match self.stage {
+            Stage::Idle => {
+                self.value = 0.0;
+            },
+            Stage::Attack => {
+                if self.advance(self.attack_ms, 1.0) {
+                    if self.hold_ms > 0.0 {
+                        self.enter_stage(Stage::Hold);
+                    } else {
+                        self.enter_stage(self.after_hold());
+                    }
+                }
+            },
+            Stage::Hold => {
+                self.value = 1.0;
+                if self.advance_time(self.hold_ms) {
+                    self.enter_stage(self.after_hold());
+                }
+            },
+            Stage::Decay => {
+                if self.advance(self.decay_ms, self.sustain) {
+                    self.enter_stage(Stage::Sustain);
+                }
+            },
+            Stage::Sustain => {
+                self.value = self.sustain;
+            },
+            Stage::Release => {
+                if self.advance(self.release_ms, 0.0) {
+                    self.enter_stage(Stage::Idle);
+                }
+            }
+        }
+
+        return self.value;
+    }
+
+    fn after_hold(&self) -> Stage {
+        return if self.one_shot { Stage::Release } else { Stage::Decay };
+    }
+
+    fn enter_stage(&mut self, stage: Stage) {
+        self.stage = stage;
+        self.stage_start = self.value;
+        self.stage_progress = 0.0;
+    }
+
+    // Moves the value towards target, returns whether the stage is done.
+    fn advance(&mut self, time_ms: f32, target: f32) -> bool {
+        let done = self.advance_time(time_ms);
+        self.value = self.stage_start + (target - self.stage_start) * self.curve.shape(self.stage_progress);
+
+        return done;
+    }
+
+    fn advance_time(&mut self, time_ms: f32) -> bool {
+        let samples = self.sample_rate / 1000.0 * time_ms;
+
+        if samples < 1.0 {
+            self.stage_progress = 1.0;
+        } else {
+            self.stage_progress = f32::min(self.stage_progress + 1.0 / samples, 1.0);
+        }
+
+        return self.stage_progress >= 1.0;
+    }
+}
+
+impl LuaUserData for Envelope {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("value", |_, this| Ok(this.value));
+        fields.add_field_method_get("stage", |_, this| Ok(this.stage.name()));
+        fields.add_field_method_get("active", |_, this| Ok(this.is_active()));
+
+        fields.add_field_method_get("attack", |_, this| Ok(this.attack_ms));
+        fields.add_field_method_set("attack", |_, this, attack_ms: f32| {
+            this.attack_ms = f32::max(attack_ms, 0.0);
+            Ok(())
+        });
+        fields.add_field_method_get("hold", |_, this| Ok(this.hold_ms));
+        fields.add_field_method_set("hold", |_, this, hold_ms: f32| {
+            this.hold_ms = f32::max(hold_ms, 0.0);
+            Ok(())
+        });
+        fields.add_field_method_get("decay", |_, this| Ok(this.decay_ms));
+        fields.add_field_method_set("decay", |_, this, decay_ms: f32| {
+            this.decay_ms = f32::max(decay_ms, 0.0);
+            Ok(())
+        });
+        fields.add_field_method_get("sustain", |_, this| Ok(this.sustain));
+        fields.add_field_method_set("sustain", |_, this, sustain: f32| {
+            this.sustain = f32::clamp(sustain, 0.0, 1.0);
+            Ok(())
+        });
+        fields.add_field_method_get("release", |_, this| Ok(this.release_ms));
+        fields.add_field_method_set("release", |_, this, release_ms: f32| {
+            this.release_ms = f32::max(release_ms, 0.0);
+            Ok(())
+        });
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method_mut("gate_on", |_, this, ()| {
+            this.gate_on();
+            Ok(())
+        });
+
+        methods.add_method_mut("gate_off", |_, this, ()| {
+            this.gate_off();
+            Ok(())
+        });
+
+        methods.add_method_mut("gate", |_, this, open: bool| {
+            if open {
+                this.gate_on();
+            } else {
+                this.gate_off();
+            }
+            Ok(())
+        });
+
+        methods.add_method_mut("trigger", |_, this, ()| {
+            this.gate_on();
+            Ok(())
+        });
+
+        methods.add_method_mut("reset", |_, this, ()| {
+            this.reset();
+            Ok(())
+        });
+
+        methods.add_method_mut("run", |_, this, ()| Ok(this.run()));
+    }
+}
+
+pub const API: [(&str, &str); 11] = [
+    ("envelope", "Envelope generators, times are in ms."),
+    ("envelope.adsr(attack, decay, sustain, release, curve?)", "Attack, decay, sustain and release. curve is \"linear\" or \"exponential\"."),
+    ("envelope.ahdsr(attack, hold, decay, sustain, release, curve?)", "Like adsr, holds at full level after the attack."),
+    ("envelope.ar(attack, release, curve?)", "Attack while the gate is on, release when it goes off."),
+    ("envelope.one_shot(attack, hold, release, curve?)", "Runs all stages on every trigger, no gate_off needed."),
+    ("Envelope:gate_on()", "Starts the attack from the current value."),
+    ("Envelope:gate_off()", "Starts the release."),
+    ("Envelope:gate(open)", "gate_on when open is true, gate_off otherwise."),
+    ("Envelope:trigger()", "Same as gate_on, reads better for one shots."),
+    ("Envelope:reset()", "Back to idle at 0."),
+    ("Envelope:run()", "Next envelope value, call once per sample.")
+];
+
+pub fn register(lua: &Lua, sample_rate: f32) -> LuaResult<()> {
+    let envelopes = lua.create_table()?;
+
+    envelopes.set("adsr", lua.create_function(move |_, (attack_ms, decay_ms, sustain, release_ms, curve): (f32, f32, f32, f32, Option<String>)| {
+        let mut envelope = Envelope::new(sample_rate, parse_curve(curve)?);
+        envelope.attack_ms = f32::max(attack_ms, 0.0);
+        envelope.decay_ms = f32::max(decay_ms, 0.0);
+        envelope.sustain = f32::clamp(sustain, 0.0, 1.0);
+        envelope.release_ms = f32::max(release_ms, 0.0);
+
+        Ok(envelope)
+    })?)?;
+
+    envelopes.set("ahdsr", lua.create_function(move |_, (attack_ms, hold_ms, decay_ms, sustain, release_ms, curve): (f32, f32, f32, f32, f32, Option<String>)| {
+        let mut envelope = Envelope::new(sample_rate, parse_curve(curve)?);
+        envelope.attack_ms = f32::max(attack_ms, 0.0);
+        envelope.hold_ms = f32::max(hold_ms, 0.0);
+        envelope.decay_ms = f32::max(decay_ms, 0.0);
+        envelope.sustain = f32::clamp(sustain, 0.0, 1.0);
+        envelope.release_ms = f32::max(release_ms, 0.0);
+
+        Ok(envelope)
+    })?)?;
+
+    envelopes.set("ar", lua.create_function(move |_, (attack_ms, release_ms, curve): (f32, f32, Option<String>)| {
+        let mut envelope = Envelope::new(sample_rate, parse_curve(curve)?);
+        envelope.attack_ms = f32::max(attack_ms, 0.0);
+        envelope.release_ms = f32::max(release_ms, 0.0);
+
+        Ok(envelope)
+    })?)?;
+
+    // Runs attack, hold and release on every trigger, no gate_off needed.
+    envelopes.set("one_shot", lua.create_function(move |_, (attack_ms, hold_ms, release_ms, curve): (f32, f32, f32, Option<String>)| {
+        let mut envelope = Envelope::new(sample_rate, parse_curve(curve)?);
+        envelope.attack_ms = f32::max(attack_ms, 0.0);
+        envelope.hold_ms = f32::max(hold_ms, 0.0);
+        envelope.release_ms = f32::max(release_ms, 0.0);
+        envelope.one_shot = true;
+
+        Ok(envelope)
+    })?)?;
+
+    return lua.globals().set(LUA_ENVELOPES_KEY, envelopes);
+}
+
+fn parse_curve(curve: Option<String>) -> LuaResult<Curve> {
+    let curve_name = curve.unwrap_or(String::from("linear"));
+
+    return match Curve::from_name(&curve_name) {
+        Some(c) => Ok(c),
+        None => Err(LuaError::runtime(format!("Unknown envelope curve \"{}\". Use \"linear\" or \"exponential\".", curve_name)))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One sample per ms, with stage lengths that step through exactly.
+    const SAMPLE_RATE: f32 = 1000.0;
+
+    fn adsr() -> Envelope {
+        let mut envelope = Envelope::new(SAMPLE_RATE, Curve::Linear);
+        envelope.attack_ms = 8.0;
+        envelope.decay_ms = 16.0;
+        envelope.sustain = 0.5;
+        envelope.release_ms = 16.0;
+
+        return envelope;
+    }
+
+    fn run(envelope: &mut Envelope, samples: usize) -> f32 {
+        let mut value = 0.0;
+        for _ in 0..samples {
+            value = envelope.run();
+        }
+
+        return value;
+    }
+
+    #[test]
+    fn attack_reaches_peak_in_time() {
+        let mut envelope = adsr();
+        envelope.gate_on();
+
+        assert_eq!(run(&mut envelope, 4), 0.5);
+        assert_eq!(run(&mut envelope, 3), 0.875);
+        assert_eq!(run(&mut envelope, 1), 1.0);
+        assert!(envelope.stage == Stage::Decay);
+
+        assert_eq!(run(&mut envelope, 16), 0.5);
+        assert!(envelope.stage == Stage::Sustain);
+        assert_eq!(run(&mut envelope, 100), 0.5);
+    }
+
+    #[test]
+    fn releases_from_where_the_attack_stopped() {
+        let mut envelope = adsr();
+        envelope.gate_on();
+        assert_eq!(run(&mut envelope, 4), 0.5);
+
+        envelope.gate_off();
+        assert_eq!(run(&mut envelope, 8), 0.25);
+        assert_eq!(run(&mut envelope, 8), 0.0);
+        assert!(!envelope.is_active());
+    }
+
+    #[test]
+    fn retriggers_without_jumping_to_zero() {
+        let mut envelope = adsr();
+        envelope.gate_on();
+        assert_eq!(run(&mut envelope, 24), 0.5);
+
+        envelope.gate_on();
+        assert_eq!(run(&mut envelope, 1), 0.5625);
+        assert_eq!(run(&mut envelope, 7), 1.0);
+        assert!(envelope.stage == Stage::Decay);
+    }
+
+    #[test]
+    fn one_shot_ignores_gate_off() {
+        let mut envelope = Envelope::new(SAMPLE_RATE, Curve::Exponential);
+        envelope.attack_ms = 8.0;
+        envelope.hold_ms = 4.0;
+        envelope.release_ms = 8.0;
+        envelope.one_shot = true;
+
+        envelope.gate_on();
+        run(&mut envelope, 2);
+        envelope.gate_off();
+        assert!(envelope.stage == Stage::Attack);
+
+        assert_eq!(run(&mut envelope, 6), 1.0);
+        assert_eq!(run(&mut envelope, 4), 1.0);
+        assert!(envelope.stage == Stage::Release);
+        assert_eq!(run(&mut envelope, 8), 0.0);
+        assert!(!envelope.is_active());
+    }
+}
@@ -0,0 +1,100 @@
+use mlua::prelude::*;
+use super::library;
+
+const TRACEBACK_HEADER: &str = "stack traceback:";
+
+// Where a script failed, for marking the line in the editor.
+#[derive(Clone, PartialEq)]
+pub struct ScriptError {
+    pub path: String,
+    pub line: usize,
+    pub message: String
+}
+
+// Turns a Lua error into something like "run.lua:12: attempt to index a nil value",
+// followed by the traceback frames that point into user code.
+pub fn describe(error: &LuaError) -> String {
+    let (message, traceback) = split(error);
+    let frames = user_frames(&traceback);
+
+    if frames.is_empty() {
+        return message;
+    }
+
+    return format!("{message}\n{header}\n\t{frames}",
+        message = message,
+        header = TRACEBACK_HEADER,
+        frames = frames.join("\n\t"));
+}
+
+// Finds the user file and line to blame, falls back to the first user frame when the error was raised internally.
+pub fn locate(error: &LuaError) -> Option<ScriptError> {
+    let (message, traceback) = split(error);
+
+    match parse_location(&message) {
+        Some((path, line)) => return Some(ScriptError { path: path, line: line, message: message }),
+        None => ()
+    }
+
+    for frame in user_frames(&traceback) {
+        match parse_location(&frame) {
+            Some((path, line)) => return Some(ScriptError { path: path, line: line, message: message }),
+            None => ()
+        }
+    }
+
+    return None;
+}
+
+// "run.lua:12: attempt to ..." -> ("run.lua", 12)
+fn parse_location(text: &str) -> Option<(String, usize)> {
+    let mut parts = text.splitn(3, ':');
+    let path = parts.next()?.trim();
+    let line = parts.next()?.trim().parse::<usize>().ok()?;
+    parts.next()?;
+
+    if path.is_empty() || path.starts_with(library::INTERNAL_CHUNK_PREFIX) || path.starts_with('[') {
+        return None;
+    }
+
+    return Some((String::from(path), line));
+}
+
+fn split(error: &LuaError) -> (String, String) {
+    return match error {
+        LuaError::RuntimeError(message) => split_message(message),
+        LuaError::SyntaxError { message, .. } => split_message(message),
+        LuaError::CallbackError { traceback, cause } => {
+            let (message, cause_traceback) = split(cause);
+
+            // The innermost traceback is the most precise one.
+            if cause_traceback.is_empty() {
+                (message, traceback.clone())
+            } else {
+                (message, cause_traceback)
+            }
+        },
+        LuaError::WithContext { context, cause } => {
+            let (message, traceback) = split(cause);
+            (format!("{}: {}", context, message), traceback)
+        },
+        _ => split_message(&error.to_string())
+    };
+}
+
+fn split_message(message: &str) -> (String, String) {
+    return match message.find(TRACEBACK_HEADER) {
+        Some(index) => (String::from(message[..index].trim_end()), String::from(&message[index + TRACEBACK_HEADER.len()..])),
+        None => (String::from(message.trim_end()), String::new())
+    };
+}
+
+fn user_frames(traceback: &str) -> Vec<String> {
+    return traceback.lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .filter(|l| !l.starts_with("[C]") && !l.starts_with("(...tail calls...)"))
+        .filter(|l| !l.starts_with(library::INTERNAL_CHUNK_PREFIX))
+        .map(|l| String::from(l))
+        .collect();
+}
@@ -0,0 +1,221 @@
+pub const LUA_KEYWORDS: [&str; 22] = [
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if",
+    "in", "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while"
+];
+
+// Longest first, so "..." isn't read as ".." and ".".
+const LUA_OPERATORS: [&str; 10] = ["...", "..", "==", "~=", "<=", ">=", "::", "//", "<<", ">>"];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TokenKind {
+    Space,
+    Name,
+    Keyword,
+    String,
+    Number,
+    Comment,
+    Symbol
+}
+
+// A piece of Lua code, line is 1-based like Lua's own line numbers.
+#[derive(Clone, Copy, Debug)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    pub line: usize
+}
+
+impl<'a> Token<'a> {
+    pub fn is(&self, kind: TokenKind, text: &str) -> bool {
+        return self.kind == kind && self.text == text;
+    }
+
+    pub fn is_symbol(&self, text: &str) -> bool {
+        return self.is(TokenKind::Symbol, text);
+    }
+
+    pub fn is_keyword(&self, text: &str) -> bool {
+        return self.is(TokenKind::Keyword, text);
+    }
+}
+
+// Splits code into tokens, together they cover the whole text so it can be put back together.
+pub fn tokenize(text: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut line = 1;
+
+    while start < text.len() {
+        let (end, kind) = next_token(text, start);
+        let token_text = &text[start..end];
+
+        tokens.push(Token { kind: kind, text: token_text, line: line });
+
+        line += token_text.matches('\n').count();
+        start = end;
+    }
+
+    return tokens;
+}
+
+// The tokens that mean something, without spaces and comments.
+pub fn significant<'a>(tokens: &[Token<'a>]) -> Vec<Token<'a>> {
+    return tokens.iter()
+        .filter(|t| t.kind != TokenKind::Space && t.kind != TokenKind::Comment)
+        .copied()
+        .collect();
+}
+
+// The content of a string literal without escapes, None for long or escaped strings.
+pub fn string_value<'a>(token: &Token<'a>) -> Option<&'a str> {
+    if token.kind != TokenKind::String || token.text.len() < 2 || token.text.contains('\\') {
+        return None;
+    }
+
+    let quote = &token.text[..1];
+    if (quote != "\"" && quote != "'") || !token.text.ends_with(quote) {
+        return None;
+    }
+
+    return Some(&token.text[1..token.text.len() - 1]);
+}
+
+fn next_token(text: &str, start: usize) -> (usize, TokenKind) {
+    let bytes = text.as_bytes();
+    let c = bytes[start];
+
+    if c.is_ascii_whitespace() {
+        let mut end = start;
+        while end < bytes.len() && bytes[end].is_ascii_whitespace() {
+            end += 1;
+        }
+
+        return (end, TokenKind::Space);
+    }
+
+    // Comments
+    if text[start..].starts_with("--") {
+        match long_bracket_level(text, start + 2) {
+            Some(level) => return (long_bracket_end(text, start + 2, level), TokenKind::Comment),
+            None => return (line_end(text, start), TokenKind::Comment)
+        }
+    }
+
+    // Strings
+    if c == b'"' || c == b'\'' {
+        return (quoted_string_end(text, start), TokenKind::String);
+    }
+
+    if c == b'[' {
+        match long_bracket_level(text, start) {
+            Some(level) => return (long_bracket_end(text, start, level), TokenKind::String),
+            None => ()
+        }
+    }
+
+    // Numbers, including .5 and 0x1F
+    if c.is_ascii_digit() || (c == b'.' && bytes.get(start + 1).map_or(false, |n| n.is_ascii_digit())) {
+        return (number_end(text, start), TokenKind::Number);
+    }
+
+    // Names
+    if c.is_ascii_alphabetic() || c == b'_' {
+        let mut end = start;
+        while end < bytes.len() && (bytes[end].is_ascii_alphanumeric() || bytes[end] == b'_') {
+            end += 1;
+        }
+
+        if LUA_KEYWORDS.contains(&&text[start..end]) {
+            return (end, TokenKind::Keyword);
+        }
+
+        return (end, TokenKind::Name);
+    }
+
+    for operator in LUA_OPERATORS {
+        if text[start..].starts_with(operator) {
+            return (start + operator.len(), TokenKind::Symbol);
+        }
+    }
+
+    // Everything else, one character at a time.
+    let mut end = start + 1;
+    while !text.is_char_boundary(end) {
+        end += 1;
+    }
+
+    return (end, TokenKind::Symbol);
+}
+
+fn line_end(text: &str, start: usize) -> usize {
+    return match text[start..].find('\n') {
+        Some(index) => start + index,
+        None => text.len()
+    };
+}
+
+// [[ is level 0, [==[ is level 2.
+fn long_bracket_level(text: &str, start: usize) -> Option<usize> {
+    let bytes = text.as_bytes();
+    if bytes.get(start) != Some(&b'[') { return None; }
+
+    let mut level = 0;
+    while bytes.get(start + 1 + level) == Some(&b'=') {
+        level += 1;
+    }
+
+    return match bytes.get(start + 1 + level) {
+        Some(b'[') => Some(level),
+        _ => None
+    };
+}
+
+fn long_bracket_end(text: &str, start: usize, level: usize) -> usize {
+    let closing = format!("]{}]", "=".repeat(level));
+    let content_start = start + level + 2;
+
+    return match text[content_start..].find(&closing) {
+        Some(index) => content_start + index + closing.len(),
+        None => text.len()
+    };
+}
+
+fn quoted_string_end(text: &str, start: usize) -> usize {
+    let bytes = text.as_bytes();
+    let quote = bytes[start];
+    let mut end = start + 1;
+
+    while end < bytes.len() {
+        match bytes[end] {
+            b'\\' => end += 2,
+            b'\n' => return end, // Unfinished string, don't color the rest of the file.
+            b if b == quote => return end + 1,
+            _ => end += 1
+        }
+    }
+
+    return text.len();
+}
+
+fn number_end(text: &str, start: usize) -> usize {
+    let bytes = text.as_bytes();
+    let hex = text[start..].starts_with("0x") || text[start..].starts_with("0X");
+    let mut end = if hex { start + 2 } else { start };
+
+    while end < bytes.len() {
+        let c = bytes[end];
+        let exponent = if hex { c == b'p' || c == b'P' } else { c == b'e' || c == b'E' };
+
+        if exponent && (bytes.get(end + 1) == Some(&b'-') || bytes.get(end + 1) == Some(&b'+')) {
+            end += 2;
+        } else if c == b'.' && bytes.get(end + 1) == Some(&b'.') {
+            break; // Concatenation like 1..2
+        } else if c.is_ascii_digit() || c == b'.' || exponent || (hex && c.is_ascii_hexdigit()) {
+            end += 1;
+        } else {
+            break;
+        }
+    }
+
+    return end.min(text.len());
+}
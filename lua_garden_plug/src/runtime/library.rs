@@ -1,4 +1,4 @@
-use std::env;
+use std::{ collections::BTreeMap, env, fs, io, path::Path };
 use super::module_content::ConstModuleContent;
 
 pub const INTERNAL_INCLUDES: [(&str, &str); 7] = [
@@ -11,6 +11,8 @@ pub const INTERNAL_INCLUDES: [(&str, &str); 7] = [
     (include_str!("../lua/_internal/includes/filters.lua"), "filters.lua")
 ];
 
+pub const INTERNAL_CHUNK_PREFIX: &str = "lua_garden/";
+
 pub const INIT_HEADER: &str = include_str!("../lua/_internal/headers/init_header.lua");
 pub const RESET_HEADER: &str = include_str!("../lua/_internal/headers/reset_header.lua");
 pub const TRIGGER_HEADER: &str = include_str!("../lua/_internal/headers/trigger_header.lua");
@@ -20,11 +22,27 @@ pub const RESET_FOOTER: &str = include_str!("../lua/_internal/footers/reset_foot
 pub const TRIGGER_FOOTER: &str = include_str!("../lua/_internal/footers/trigger_footer.lua");
 pub const RUN_FOOTER: &str = include_str!("../lua/_internal/footers/run_footer.lua");
 
+pub const INIT_HEADER_NAME: &str = "init_header.lua";
+pub const RESET_HEADER_NAME: &str = "reset_header.lua";
+pub const TRIGGER_HEADER_NAME: &str = "trigger_header.lua";
+pub const RUN_HEADER_NAME: &str = "run_header.lua";
+
+pub const INIT_FOOTER_NAME: &str = "init_footer.lua";
+pub const RESET_FOOTER_NAME: &str = "reset_footer.lua";
+pub const TRIGGER_FOOTER_NAME: &str = "trigger_footer.lua";
+pub const RUN_FOOTER_NAME: &str = "run_footer.lua";
+
 pub const INIT_PATH: &str = "init.lua";
 pub const RESET_PATH: &str = "reset.lua";
 pub const TRIGGER_PATH: &str = "trigger.lua";
 pub const RUN_PATH: &str = "run.lua";
 pub const INTERFACE_PATH: &str = "interface.lua";
+pub const MODULE_PATHS: [&str; 5] = [INIT_PATH, RESET_PATH, TRIGGER_PATH, RUN_PATH, INTERFACE_PATH];
+pub const LUA_EXTENSION: &str = "lua";
+pub const MANIFEST_PATH: &str = "module.toml";
+pub const DEFAULT_MODULE_VERSION: &str = "0.1.0";
+pub const PRESETS_FOLDER: &str = "presets";
+pub const PRESET_EXTENSION: &str = "toml";
 
 pub const DEFAULT_INIT_CONTENT: &str = include_str!("../lua/_default/init.lua");
 pub const DEFAULT_RESET_CONTENT: &str = include_str!("../lua/_default/reset.lua");
@@ -39,7 +57,7 @@ pub const MODULE_DEFAULT: ConstModuleContent = ConstModuleContent::new(
     include_str!("../lua/_default/run.lua"),
     include_str!("../lua/_default/interface.lua"));
 
-pub const MODULE_EXAMPLES: [(ConstModuleContent, &str); 4] = [
+pub const MODULE_EXAMPLES: [(ConstModuleContent, &str); 5] = [
     (ConstModuleContent::new(
         include_str!("../lua/examples/0_noise/init.lua"),
         DEFAULT_RESET_CONTENT,
@@ -71,26 +89,23 @@ pub const MODULE_EXAMPLES: [(ConstModuleContent, &str); 4] = [
         include_str!("../lua/examples/3_waveshaper/run.lua"),
         DEFAULT_INTERFACE_CONTENT), // TODO
         "Waveshaper"),
+
+    (ConstModuleContent::new(
+        include_str!("../lua/examples/4_spectral_gate/init.lua"),
+        DEFAULT_RESET_CONTENT,
+        DEFAULT_TRIGGER_CONTENT,
+        DEFAULT_RUN_CONTENT,
+        DEFAULT_INTERFACE_CONTENT), // TODO
+        "Spectral Gate"),
 ];
 
-pub fn internal_includes() -> String {
-    let mut includes = String::new();
-
-    for include in INTERNAL_INCLUDES {
-        includes.push_str(&format!(
-            "\n\
-            -- ==== --\n\
-            -- INCLUDE {}\n\
-            -- ↓↓↓↓ --\n", include.1));
-        includes.push_str(include.0);
-        includes.push_str(&format!(
-            "\n\
-            -- ↑↑↑↑ --\n\
-            -- INCLUDE {}\n\
-            -- ==== --\n", include.1));
-    }
+// Internal chunks show up as lua_garden/name.lua in errors, so they're easy to tell apart from user code.
+pub fn internal_chunk_name(name: &str) -> String {
+    return format!("={prefix}{name}", prefix = INTERNAL_CHUNK_PREFIX, name = name);
+}
 
-    return  includes;
+pub fn user_chunk_name(path: &str) -> String {
+    return format!("@{}", path);
 }
 
 pub fn default_workspaces_path () -> String {
@@ -104,4 +119,78 @@ pub fn default_workspaces_path () -> String {
         Some(p) => return String::from(p),
         None => return String::new()
     }
-}
\ No newline at end of file
+}
+
+pub fn default_user_library_path () -> String {
+    let mut workdir_path = match env::current_dir() {
+        Ok(path) => path,
+        Err(_e) => return String::new(),
+    };
+
+    workdir_path.push("library");
+    match workdir_path.to_str() {
+        Some(p) => return String::from(p),
+        None => return String::new()
+    }
+}
+
+// Turns a name like "dsp/filters" or "dsp.filters" into a library path like "dsp/filters.lua".
+pub fn library_path_from_name(name: &str) -> Result<String, String> {
+    let name = name.trim().trim_end_matches(".lua");
+    if name.is_empty() {
+        return Err(String::from("File name is empty."));
+    }
+
+    let path = format!("{}.{}", name.replace('.', "/"), LUA_EXTENSION);
+    if path.split('/').any(|p| p.is_empty() || p.starts_with('.')) || path.contains('\\') {
+        return Err(format!("\"{}\" isn't a valid file name.", name));
+    }
+
+    if MODULE_PATHS.contains(&path.as_str()) {
+        return Err(format!("\"{}\" is reserved for the module itself.", path));
+    }
+
+    return Ok(path);
+}
+
+// Lua files in the user library folder, shared by every module.
+pub fn read_user_libraries() -> io::Result<BTreeMap<String, String>> {
+    let path = default_user_library_path();
+    if path.is_empty() || !Path::new(&path).is_dir() {
+        return Ok(BTreeMap::new());
+    }
+
+    return read_lua_files(&path, false);
+}
+
+// Reads every .lua file below root, keyed by their path relative to root like "dsp/filters.lua".
+pub fn read_lua_files(root: &str, skip_module_files: bool) -> io::Result<BTreeMap<String, String>> {
+    let mut files = BTreeMap::new();
+    read_lua_files_recursive(Path::new(root), "", skip_module_files, &mut files)?;
+
+    return Ok(files);
+}
+
+fn read_lua_files_recursive(directory: &Path, prefix: &str, skip_module_files: bool, files: &mut BTreeMap<String, String>) -> io::Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+
+        // Skip hidden files and folders, like .git.
+        if file_name.starts_with('.') { continue; }
+
+        let relative_path = format!("{prefix}{name}", prefix = prefix, name = file_name);
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            read_lua_files_recursive(&entry.path(), &format!("{}/", relative_path), skip_module_files, files)?;
+        } else if file_type.is_file() {
+            if entry.path().extension().and_then(|e| e.to_str()) != Some(LUA_EXTENSION) { continue; }
+            if skip_module_files && MODULE_PATHS.contains(&relative_path.as_str()) { continue; }
+
+            files.insert(relative_path, fs::read_to_string(entry.path())?);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,325 @@
+use std::{ collections::BTreeSet, ops::Range };
+use super::{ errors::ScriptError, lexer::{ self, Token, TokenKind } };
+
+const PARAMETER_TABLE: &str = "PARAMETERS";
+const PARAMETER_CLASS: &str = "Parameter";
+const PARAMETER_CONSTRUCTOR: &str = "new";
+const RUNTIME_TABLE: &str = "runtime";
+const ITERATE_FUNCTION: &str = "iterate";
+
+// Looks for mistakes Lua only notices while running, or never.
+// scripts are (path, code) pairs, globals are the names the runtime already provides.
+pub fn lint(scripts: &[(&str, &str)], globals: &BTreeSet<String>) -> Vec<ScriptError> {
+    let files: Vec<(&str, Vec<Token>)> = scripts.iter()
+        .map(|(path, code)| (*path, lexer::significant(&lexer::tokenize(code))))
+        .collect();
+
+    let mut registered = BTreeSet::new();
+    let mut declared = globals.clone();
+
+    for (_, tokens) in &files {
+        registered.extend(registered_parameters(tokens));
+
+        let bodies = per_sample_bodies(tokens);
+        let outside = (0..tokens.len()).filter(|i| !bodies.iter().any(|b| b.contains(i)));
+        declared.extend(assigned_globals(tokens, outside));
+    }
+
+    let mut warnings = Vec::new();
+
+    for (path, tokens) in &files {
+        warnings.extend(unknown_parameters(path, tokens, &registered));
+
+        let bodies = per_sample_bodies(tokens);
+        let mut file_locals = BTreeSet::new();
+        for index in (0..tokens.len()).filter(|i| !bodies.iter().any(|b| b.contains(i))) {
+            file_locals.extend(declared_locals(tokens, index));
+        }
+
+        for body in bodies {
+            warnings.extend(check_per_sample(path, tokens, body, &declared, &file_locals));
+        }
+    }
+
+    warnings.sort_by(|a, b| (&a.path, a.line).cmp(&(&b.path, b.line)));
+    warnings.dedup();
+
+    return warnings;
+}
+
+// Names passed to Parameter:new("name", ...)
+fn registered_parameters(tokens: &[Token]) -> Vec<String> {
+    let mut names = Vec::new();
+
+    for window in tokens.windows(5) {
+        let constructor = window[0].is(TokenKind::Name, PARAMETER_CLASS)
+            && (window[1].is_symbol(":") || window[1].is_symbol("."))
+            && window[2].is(TokenKind::Name, PARAMETER_CONSTRUCTOR)
+            && window[3].is_symbol("(");
+
+        match lexer::string_value(&window[4]) {
+            Some(name) if constructor => names.push(String::from(name)),
+            _ => ()
+        }
+    }
+
+    return names;
+}
+
+// PARAMETERS["name"] and PARAMETERS.name for names nobody registered.
+fn unknown_parameters(path: &str, tokens: &[Token], registered: &BTreeSet<String>) -> Vec<ScriptError> {
+    let mut warnings = Vec::new();
+
+    for (index, token) in tokens.iter().enumerate() {
+        if !token.is(TokenKind::Name, PARAMETER_TABLE) {
+            continue;
+        }
+
+        let name = match (tokens.get(index + 1), tokens.get(index + 2)) {
+            (Some(open), Some(key)) if open.is_symbol("[") => lexer::string_value(key),
+            (Some(dot), Some(key)) if dot.is_symbol(".") && key.kind == TokenKind::Name => Some(key.text),
+            _ => None
+        };
+
+        match name {
+            Some(name) if !registered.contains(name) => warnings.push(ScriptError {
+                path: String::from(path),
+                line: token.line,
+                message: format!("There is no parameter named \"{}\", register it with Parameter:new in init.lua.", name)
+            }),
+            _ => ()
+        }
+    }
+
+    return warnings;
+}
+
+// The functions given to runtime.iterate, from their function keyword up to their end.
+fn per_sample_bodies(tokens: &[Token]) -> Vec<Range<usize>> {
+    let mut bodies = Vec::new();
+
+    for index in 0..tokens.len() {
+        let iterate = tokens[index].is(TokenKind::Name, RUNTIME_TABLE)
+            && tokens.get(index + 1).map_or(false, |t| t.is_symbol("."))
+            && tokens.get(index + 2).map_or(false, |t| t.is(TokenKind::Name, ITERATE_FUNCTION))
+            && tokens.get(index + 3).map_or(false, |t| t.is_symbol("("));
+
+        if !iterate {
+            continue;
+        }
+
+        let function_index = match tokens.get(index + 4) {
+            Some(t) if t.is_keyword("function") => Some(index + 4),
+            Some(t) if t.kind == TokenKind::Name => find_function(tokens, t.text),
+            _ => None
+        };
+
+        match function_index.and_then(|i| function_body(tokens, i)) {
+            Some(body) => bodies.push(body),
+            None => ()
+        }
+    }
+
+    return bodies;
+}
+
+// "function name(", "local function name(" or "name = function(".
+fn find_function(tokens: &[Token], name: &str) -> Option<usize> {
+    for index in 0..tokens.len() {
+        let named = tokens[index].is_keyword("function")
+            && tokens.get(index + 1).map_or(false, |t| t.is(TokenKind::Name, name))
+            && tokens.get(index + 2).map_or(false, |t| t.is_symbol("("));
+
+        let assigned = tokens[index].is(TokenKind::Name, name)
+            && tokens.get(index + 1).map_or(false, |t| t.is_symbol("="))
+            && tokens.get(index + 2).map_or(false, |t| t.is_keyword("function"));
+
+        if named {
+            return Some(index);
+        }
+
+        if assigned {
+            return Some(index + 2);
+        }
+    }
+
+    return None;
+}
+
+fn function_body(tokens: &[Token], function_index: usize) -> Option<Range<usize>> {
+    let mut depth = 0;
+
+    for index in function_index..tokens.len() {
+        let token = &tokens[index];
+        if token.kind != TokenKind::Keyword {
+            continue;
+        }
+
+        // while and for open their block with do.
+        match token.text {
+            "function" | "if" | "do" => depth += 1,
+            "end" => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(function_index..index + 1);
+                }
+            },
+            _ => ()
+        }
+    }
+
+    return None;
+}
+
+fn check_per_sample(path: &str, tokens: &[Token], body: Range<usize>, declared: &BTreeSet<String>, file_locals: &BTreeSet<String>) -> Vec<ScriptError> {
+    let mut warnings = Vec::new();
+    let mut locals = file_locals.clone();
+    let mut brace_depth = 0;
+
+    for index in body {
+        let token = &tokens[index];
+        locals.extend(declared_locals(tokens, index));
+
+        if token.is_symbol("{") {
+            brace_depth += 1;
+            warnings.push(ScriptError {
+                path: String::from(path),
+                line: token.line,
+                message: String::from("This table is created on every sample, create it once in init.lua and reuse it.")
+            });
+        } else if token.is_symbol("}") {
+            brace_depth -= 1;
+        }
+
+        // Inside a table constructor, name = value is a field.
+        if brace_depth == 0 && is_global_write(tokens, index) && !locals.contains(token.text) && !declared.contains(token.text) {
+            warnings.push(ScriptError {
+                path: String::from(path),
+                line: token.line,
+                message: format!("\"{}\" is written on every sample but never declared, make it local or set it up in init.lua.", token.text)
+            });
+        }
+    }
+
+    return warnings;
+}
+
+fn assigned_globals(tokens: &[Token], indices: impl Iterator<Item = usize>) -> Vec<String> {
+    let mut names = Vec::new();
+
+    for index in indices {
+        let named_function = tokens[index].is_keyword("function")
+            && tokens.get(index + 1).map_or(false, |t| t.kind == TokenKind::Name)
+            && !(index > 0 && tokens[index - 1].is_keyword("local"));
+
+        if named_function {
+            names.push(String::from(tokens[index + 1].text));
+        } else if is_global_write(tokens, index) {
+            names.push(String::from(tokens[index].text));
+        }
+    }
+
+    return names;
+}
+
+// name = value, but not table.name = value, local name = value or a == b.
+fn is_global_write(tokens: &[Token], index: usize) -> bool {
+    if tokens[index].kind != TokenKind::Name || !tokens.get(index + 1).map_or(false, |t| t.is_symbol("=")) {
+        return false;
+    }
+
+    return match index.checked_sub(1).map(|i| &tokens[i]) {
+        Some(previous) => !(previous.is_symbol(".") || previous.is_symbol(":") || previous.is_symbol(",")
+            || previous.is_keyword("local") || previous.is_keyword("for")),
+        None => true
+    };
+}
+
+// Names a token declares: local lists, local functions, function parameters and loop variables.
+fn declared_locals(tokens: &[Token], index: usize) -> Vec<String> {
+    let mut names = Vec::new();
+    let token = &tokens[index];
+
+    let (start, stop): (usize, fn(&Token) -> bool) = if token.is_keyword("local") {
+        (index + 1, |t: &Token| t.is_symbol("=") || (t.kind != TokenKind::Name && !t.is_symbol(",") && !t.is_keyword("function")))
+    } else if token.is_keyword("for") {
+        (index + 1, |t: &Token| t.is_symbol("=") || t.is_keyword("in"))
+    } else if token.is_keyword("function") {
+        match tokens[index..].iter().position(|t| t.is_symbol("(")) {
+            Some(open) => (index + open + 1, |t: &Token| t.is_symbol(")")),
+            None => return names
+        }
+    } else {
+        return names;
+    };
+
+    for t in tokens.iter().skip(start) {
+        if stop(t) {
+            break;
+        }
+
+        if t.kind == TokenKind::Name {
+            names.push(String::from(t.text));
+        }
+    }
+
+    return names;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint_run(run: &str) -> Vec<ScriptError> {
+        let globals = BTreeSet::from([String::from("BUFFER"), String::from("runtime")]);
+        return lint(&[("init.lua", "gain = 0.5;\nlocal cutoff = Parameter:new(\"Cutoff\", 1000, 20, 20000);"), ("run.lua", run)], &globals);
+    }
+
+    #[test]
+    fn clean_module_has_no_warnings() {
+        let run = "local phase = 0;\nruntime.iterate(function(sample)\n    local x = phase * gain;\n    phase = phase + 1;\n    gain = 0.4;\nend);";
+        assert!(lint_run(run).is_empty());
+    }
+
+    #[test]
+    fn undeclared_global_write() {
+        let run = "runtime.iterate(function(sample)\n    for c = 1, 2 do\n        levl = c;\n    end\nend);";
+        let warnings = lint_run(run);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line, 3);
+        assert!(warnings[0].message.contains("levl"));
+    }
+
+    #[test]
+    fn named_callback_is_checked() {
+        let run = "local function tick(sample)\n    state = 1;\nend\n\nruntime.iterate(tick);";
+        let warnings = lint_run(run);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line, 2);
+    }
+
+    #[test]
+    fn table_constructor_per_sample() {
+        let run = "local t = { 1, 2 };\nruntime.iterate(function(sample)\n    local pair = { a = 1, b = 2 };\nend);";
+        let warnings = lint_run(run);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line, 3);
+    }
+
+    #[test]
+    fn unknown_parameter_name() {
+        let run = "local a = PARAMETERS[\"Cutoff\"];\nlocal b = PARAMETERS[\"Cutof\"];\nlocal c = PARAMETERS.Resonance;";
+        let warnings = lint_run(run);
+
+        assert_eq!(warnings.iter().map(|w| w.line).collect::<Vec<_>>(), vec![2, 3]);
+    }
+
+    #[test]
+    fn comments_and_strings_are_ignored() {
+        let run = "runtime.iterate(function(sample)\n    -- x = { }\n    local s = \"y = {\";\nend);";
+        assert!(lint_run(run).is_empty());
+    }
+}
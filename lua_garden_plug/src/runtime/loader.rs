@@ -0,0 +1,250 @@
+use std::{ ptr, sync::{ atomic::{ AtomicPtr, AtomicU64, Ordering }, mpsc::{ self, RecvTimeoutError, Sender }, Arc }, thread, time::Duration };
+use mlem_console::{ ConsoleSender, LogLevel, LogMessage, LogSource };
+use mlua::prelude::*;
+use super::{ errors::{ self, ScriptError }, module::RuntimeModule, rack::{ RackModule, RackSlot }, utils::Timer };
+
+const LOADER_THREAD_NAME: &str = "Rack loader";
+// How often the loader drops the racks the audio thread gave back.
+const RETIRE_INTERVAL: Duration = Duration::from_millis(100);
+// A load and a fade each give back one rack at a time, the loader empties these long before they fill up.
+const RETIRED_SLOTS: usize = 4;
+
+// Shared by every loader, so a rack left over from a closed editor never matches a newer load.
+static NEXT_LOAD_ID: AtomicU64 = AtomicU64::new(1);
+
+// A rack built and initialized on the loader thread, ready to swap in.
+pub struct LoadedRack {
+    // Which load it answers, the audio thread only swaps in the one the interface asked for last.
+    pub id: u64,
+    pub modules: Vec<RackModule>,
+    pub name: String,
+    pub author: String,
+    pub description: String,
+    pub init_ms: f32,
+    pub initialized: bool,
+    pub last_error: Option<ScriptError>
+}
+
+// Passes racks to the audio thread and back without locking, so the audio thread never builds or drops one.
+pub struct RackHandoff {
+    loaded: AtomicPtr<LoadedRack>,
+    retired: [AtomicPtr<LoadedRack>; RETIRED_SLOTS]
+}
+
+// Builds racks on its own thread when the interface loads, and drops the ones the audio thread is done with.
+pub struct RackLoader {
+    sender: Sender<LoadRequest>
+}
+
+struct LoadRequest {
+    id: u64,
+    slots: Vec<RackSlot>,
+    sample_rate: f32
+}
+
+impl LoadedRack {
+    pub fn new(id: u64) -> LoadedRack {
+        Self {
+            id: id,
+            modules: Vec::new(),
+            name: String::new(),
+            author: String::new(),
+            description: String::new(),
+            init_ms: 0.0,
+            initialized: false,
+            last_error: None
+        }
+    }
+}
+
+impl RackHandoff {
+    pub fn new() -> RackHandoff {
+        Self {
+            loaded: AtomicPtr::new(ptr::null_mut()),
+            retired: Default::default()
+        }
+    }
+
+    // Returns the rack it replaces, when the audio thread didn't get to that one.
+    pub fn put(&self, rack: Box<LoadedRack>) -> Option<Box<LoadedRack>> {
+        return from_raw(self.loaded.swap(Box::into_raw(rack), Ordering::AcqRel));
+    }
+
+    pub fn take(&self) -> Option<Box<LoadedRack>> {
+        return from_raw(self.loaded.swap(ptr::null_mut(), Ordering::AcqRel));
+    }
+
+    // Gives a rack back to be dropped off the audio thread.
+    // Only when nothing emptied the slots, with the editor closed, it is dropped right away.
+    pub fn retire(&self, rack: Box<LoadedRack>) {
+        let raw = Box::into_raw(rack);
+
+        for slot in &self.retired {
+            if slot.compare_exchange(ptr::null_mut(), raw, Ordering::AcqRel, Ordering::Relaxed).is_ok() {
+                return;
+            }
+        }
+
+        drop(from_raw(raw));
+    }
+
+    pub fn drop_retired(&self) {
+        for slot in &self.retired {
+            drop(from_raw(slot.swap(ptr::null_mut(), Ordering::AcqRel)));
+        }
+    }
+}
+
+impl Drop for RackHandoff {
+    fn drop(&mut self) {
+        drop(self.take());
+        self.drop_retired();
+    }
+}
+
+impl RackLoader {
+    pub fn new(handoff: Arc<RackHandoff>, console: Option<ConsoleSender>) -> RackLoader {
+        let (sender, receiver) = mpsc::channel::<LoadRequest>();
+
+        // Stops by itself when the loader is dropped and the channel closes.
+        thread::Builder::new()
+            .name(String::from(LOADER_THREAD_NAME))
+            .spawn(move || {
+                loop {
+                    match receiver.recv_timeout(RETIRE_INTERVAL) {
+                        Ok(mut request) => {
+                            // Only the newest load is worth building.
+                            while let Ok(newer) = receiver.try_recv() {
+                                request = newer;
+                            }
+
+                            let rack = load(request, &console);
+                            drop(handoff.put(Box::new(rack)));
+                        },
+                        Err(RecvTimeoutError::Timeout) => (),
+                        Err(RecvTimeoutError::Disconnected) => break
+                    }
+
+                    handoff.drop_retired();
+                }
+            })
+            .expect("Couldn't start the rack loader.");
+
+        return Self {
+            sender: sender
+        };
+    }
+
+    // Returns the id of the load, for the audio thread to wait for.
+    pub fn load(&self, slots: Vec<RackSlot>, sample_rate: f32) -> u64 {
+        let id = NEXT_LOAD_ID.fetch_add(1, Ordering::Relaxed);
+        let _ = self.sender.send(LoadRequest { id: id, slots: slots, sample_rate: sample_rate });
+
+        return id;
+    }
+}
+
+// Runs init.lua of each module in order, a rack is named after its modules.
+pub fn init_modules(modules: &mut [RackModule], console: &Option<ConsoleSender>) -> LuaResult<(String, String, String)> {
+    super::send(console, LogLevel::Info, LogSource::Runtime, LogMessage::new("Setting up Lua state..."));
+
+    if modules.is_empty() {
+        super::send(console, LogLevel::Info, LogSource::Runtime, LogMessage::new("No module loaded."));
+    }
+
+    let mut infos = Vec::new();
+    for m in modules.iter_mut() {
+        let info = m.module.init()?;
+
+        let version = match m.module.manifest().and_then(|m| m.version.clone()) {
+            Some(v) => format!(" {}", v),
+            None => String::new()
+        };
+
+        super::send(console, LogLevel::Info, LogSource::Runtime, LogMessage::new("Initialized module:\n{text}").text(&format!("{name}{version} by {authors}\n\"{about}\"",
+            name = info.0,
+            version = version,
+            authors = info.1,
+            about = info.2)));
+
+        m.name = info.0.clone();
+        infos.push(info);
+    }
+
+    return Ok(match infos.len() {
+        0 => (String::new(), String::new(), String::new()),
+        1 => infos.remove(0),
+        count => (
+            infos.iter().map(|i| i.0.as_str()).collect::<Vec<_>>().join(" > "),
+            infos.iter().map(|i| i.1.as_str()).collect::<Vec<_>>().join(", "),
+            format!("A rack of {} modules.", count)
+        )
+    });
+}
+
+fn load(request: LoadRequest, console: &Option<ConsoleSender>) -> LoadedRack {
+    let mut rack = LoadedRack::new(request.id);
+
+    for slot in &request.slots {
+        let module = RuntimeModule::new(slot.content().clone(), request.sample_rate, slot.workspace_path());
+        super::send(console, LogLevel::Info, LogSource::Runtime, LogMessage::new("Loading module... ({text})\n").text(&module.hash));
+        rack.modules.push(RackModule::new(slot, module));
+    }
+
+    let init_timer = Timer::new();
+    let init_result = init_modules(&mut rack.modules, console);
+    rack.init_ms = init_timer.elapsed_ms();
+
+    match init_result {
+        Ok(info) => {
+            (rack.name, rack.author, rack.description) = info;
+            rack.initialized = true;
+            super::send(console, LogLevel::Info, LogSource::Runtime, LogMessage::new("Initialization took {:.2}ms.").arg(rack.init_ms as f64));
+        },
+        Err(e) => {
+            super::send(console, LogLevel::Error, LogSource::Runtime, LogMessage::new("Failed to initialize: {text}").text(&errors::describe(&e)));
+            rack.last_error = errors::locate(&e);
+        }
+    }
+
+    return rack;
+}
+
+fn from_raw(raw: *mut LoadedRack) -> Option<Box<LoadedRack>> {
+    return match raw.is_null() {
+        true => None,
+        // Every pointer in the handoff came from Box::into_raw, and swapping it out leaves nobody else holding it.
+        false => Some(unsafe { Box::from_raw(raw) })
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hands_over_the_newest_rack() {
+        let handoff = RackHandoff::new();
+        assert!(handoff.take().is_none());
+
+        assert!(handoff.put(Box::new(LoadedRack::new(1))).is_none());
+        let stale = handoff.put(Box::new(LoadedRack::new(2)));
+        assert_eq!(stale.map(|r| r.id), Some(1));
+
+        assert_eq!(handoff.take().map(|r| r.id), Some(2));
+        assert!(handoff.take().is_none());
+    }
+
+    #[test]
+    fn keeps_retired_racks_until_dropped() {
+        let handoff = RackHandoff::new();
+
+        for id in 0..RETIRED_SLOTS + 1 {
+            handoff.retire(Box::new(LoadedRack::new(id as u64)));
+        }
+        assert!(handoff.retired.iter().all(|slot| !slot.load(Ordering::Acquire).is_null()));
+
+        handoff.drop_retired();
+        assert!(handoff.retired.iter().all(|slot| slot.load(Ordering::Acquire).is_null()));
+    }
+}
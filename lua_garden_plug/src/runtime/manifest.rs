@@ -0,0 +1,131 @@
+use std::{ collections::BTreeMap, fs, io, path::Path };
+use serde::{ Deserialize, Serialize };
+use super::{ api, library };
+
+// Optional module.toml next to init.lua, describes a module without running it.
+#[derive(Clone, PartialEq, Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Manifest {
+    pub name: Option<String>,
+    pub authors: Vec<String>,
+    pub about: Option<String>,
+    pub version: Option<String>,
+    pub license: Option<String>,
+    pub tags: Vec<String>,
+    // The lowest lua_garden API version the module works with, see api::API_VERSION.
+    pub api_version: Option<u32>,
+    // How many channels the module is written for.
+    pub channels: Option<usize>,
+    // Values parameters start at, keyed by the name given to Parameter:new.
+    pub parameters: BTreeMap<String, f32>
+}
+
+impl Manifest {
+    // A manifest for a new module, named after its folder.
+    pub fn new(name: &str) -> Manifest {
+        Self {
+            name: Some(String::from(name)),
+            version: Some(String::from(library::DEFAULT_MODULE_VERSION)),
+            api_version: Some(api::API_VERSION),
+            ..Default::default()
+        }
+    }
+
+    // None when the workspace has no manifest, that's fine.
+    pub fn read(workspace_path: &str) -> Result<Option<Manifest>, String> {
+        let path = Path::new(workspace_path).join(library::MANIFEST_PATH);
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Couldn't read {}: {}", library::MANIFEST_PATH, e))
+        };
+
+        return match Manifest::parse(&text) {
+            Ok(manifest) => Ok(Some(manifest)),
+            Err(e) => Err(format!("Couldn't read {}: {}", library::MANIFEST_PATH, e))
+        };
+    }
+
+    pub fn parse(text: &str) -> Result<Manifest, String> {
+        return toml::from_str(text).map_err(|e| e.to_string());
+    }
+
+    pub fn write(&self, workspace_path: &str) -> io::Result<()> {
+        let text = match toml::to_string_pretty(self) {
+            Ok(text) => text,
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+        };
+
+        return fs::write(Path::new(workspace_path).join(library::MANIFEST_PATH), text);
+    }
+
+    // Fails when the module needs a newer runtime than this one.
+    pub fn check_api_version(&self) -> Result<(), String> {
+        return match self.api_version {
+            Some(version) if version > api::API_VERSION => Err(format!(
+                "This module needs version {} of the runtime API, this version of {} provides version {}.",
+                version, crate::consts::NAME, api::API_VERSION)),
+            _ => Ok(())
+        };
+    }
+
+    pub fn authors_text(&self) -> Option<String> {
+        if self.authors.is_empty() {
+            return None;
+        }
+
+        return Some(self.authors.join(", "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_full_manifest() {
+        let manifest = Manifest::parse(r#"
+            name = "Crusher"
+            authors = ["A", "B"]
+            about = "Crunchy."
+            version = "1.2.0"
+            license = "MIT"
+            tags = ["distortion"]
+            api_version = 1
+            channels = 2
+
+            [parameters]
+            Bits = 8
+            Mix = 0.5
+        "#).unwrap();
+
+        assert_eq!(manifest.name.as_deref(), Some("Crusher"));
+        assert_eq!(manifest.authors_text().as_deref(), Some("A, B"));
+        assert_eq!(manifest.channels, Some(2));
+        assert_eq!(manifest.parameters.get("Bits"), Some(&8.0));
+        assert_eq!(manifest.parameters.get("Mix"), Some(&0.5));
+    }
+
+    #[test]
+    fn everything_is_optional() {
+        let manifest = Manifest::parse("").unwrap();
+        assert_eq!(manifest, Manifest::default());
+        assert!(manifest.check_api_version().is_ok());
+    }
+
+    #[test]
+    fn newer_api_version_is_refused() {
+        let manifest = Manifest { api_version: Some(api::API_VERSION + 1), ..Default::default() };
+        assert!(manifest.check_api_version().is_err());
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut manifest = Manifest::new("Test");
+        manifest.tags = vec![String::from("filter")];
+        manifest.parameters.insert(String::from("Cutoff"), 1000.0);
+
+        let text = toml::to_string_pretty(&manifest).unwrap();
+        assert_eq!(Manifest::parse(&text).unwrap(), manifest);
+    }
+}
@@ -5,22 +5,83 @@ pub mod library;
 pub mod module_content;
 pub mod runtime_data;
 pub mod parameter;
-
-use crate::console::ConsoleSender;
-use module::RuntimeModule;
-use module_content::ModuleContent;
+pub mod spectral;
+pub mod resources;
+pub mod oscillators;
+pub mod envelopes;
+pub mod tuning;
+pub mod random;
+pub mod require;
+pub mod errors;
+pub mod api;
+pub mod lexer;
+pub mod lint;
+pub mod manifest;
+pub mod bundle;
+pub mod preset;
+pub mod rack;
+pub mod dry_wet;
+pub mod crossfade;
+pub mod safety;
+pub mod profiler;
+pub mod repl;
+pub mod telemetry;
+pub mod loader;
+
+use std::{ sync::Arc, time::Duration };
+use mlem_console::{ ConsoleSender, LogLevel, LogMessage, LogSource };
+use rack::{ RackModule, SlotStatus };
+use loader::{ LoadedRack, RackHandoff };
+use crossfade::{ Crossfade, FadeFrom };
+use safety::OutputSafety;
+use profiler::{ FunctionLabel, Profiler, ProfileReport, RunTimings };
+use repl::ReplCommand;
+use telemetry::TelemetrySender;
+use errors::ScriptError;
 use utils::{ Timer, RMS };
 use mlua::prelude::*;
 use nih_plug::prelude::*;
 
+// More than any of the audio layouts has, fading runs the outgoing modules on a fixed array of channels.
+const MAX_CHANNELS: usize = 8;
+// How much of a block a console snippet gets, it runs on the audio thread between blocks.
+const REPL_BLOCK_SHARE: f32 = 0.25;
+
 pub struct Runtime {
     pub console: Option<ConsoleSender>,
+    pub telemetry: Option<TelemetrySender>,
 
     pub name: String,
     pub author: String,
     pub description: String,
-
-    module: Option<RuntimeModule>,
+    pub last_error: Option<ScriptError>,
+
+    // Run in series, the first module gets the input.
+    modules: Vec<RackModule>,
+    // Racks come in from the loader ready to run, and go back to it to be dropped.
+    handoff: Arc<RackHandoff>,
+    // An empty rack for clearing to swap the modules out into, since nothing comes in then.
+    spare: Option<Box<LoadedRack>>,
+    // Whether the modules made it through a block, only then they are worth fading out.
+    running: bool,
+
+    crossfade_ms: f32,
+    fade: Option<Crossfade>,
+    fade_buffer: Vec<Vec<f32>>,
+    last_frame: Vec<f32>,
+    // What a held fade fades from, swapped with last_frame so failing doesn't allocate.
+    held_frame: Vec<f32>,
+
+    safety: OutputSafety,
+    // Stop the modules when the safety trips, rather than only muting them.
+    safety_stop: bool,
+
+    profiler: Profiler,
+    // Only worth reporting while someone is looking.
+    profiling: bool,
+    sampling: bool,
+    // Since meters and watches were last sent.
+    telemetry_samples: usize,
 
     sample_rate : f32,
     buffer_size : usize,
@@ -35,12 +96,31 @@ impl Runtime {
     pub fn new(console: Option<ConsoleSender>) -> Runtime {
         let runtime = Self {
             console: console,
+            telemetry: None,
 
             name: String::new(),
             author: String::new(),
             description: String::new(),
+            last_error: None,
+
+            modules: Vec::new(),
+            handoff: Arc::new(RackHandoff::new()),
+            spare: Some(Box::new(LoadedRack::new(0))),
+            running: false,
+
+            crossfade_ms: 0.0,
+            fade: None,
+            fade_buffer: Vec::new(),
+            last_frame: Vec::new(),
+            held_frame: Vec::new(),
 
-            module: None,
+            safety: OutputSafety::new(),
+            safety_stop: false,
+
+            profiler: Profiler::new(),
+            profiling: false,
+            sampling: false,
+            telemetry_samples: 0,
 
             sample_rate: 0.0,
             buffer_size: 0,
@@ -54,25 +134,137 @@ impl Runtime {
         return runtime;
     }
 
-    pub fn load_new_module(&mut self, content: ModuleContent) {
-        let module = RuntimeModule::new(content, self.sample_rate);
-        
-        self.load_module(Some(module));
+    pub fn handoff(&self) -> Arc<RackHandoff> {
+        return self.handoff.clone();
     }
 
-    pub fn load_module(&mut self, module: Option<RuntimeModule>) {
-        match module {
-            Some(m) => {
-                self.log(format!("Loading module... ({})\n", m.hash));
-                self.module = Some(m);
+    // Swaps in the rack the loader built for the given load, None while it is still building.
+    // The rack was initialized on the loader thread, only resetting is left.
+    pub fn swap_in_loaded(&mut self, load_id: u64) -> Option<bool> {
+        let mut rack = loop {
+            match self.handoff.take() {
+                Some(rack) if rack.id == load_id => break rack,
+                // From a load the interface moved on from.
+                Some(rack) => self.handoff.retire(rack),
+                None => return None
             }
+        };
+
+        // The outgoing modules, strings and error go back to the loader in the rack that brought the new ones.
+        std::mem::swap(&mut self.modules, &mut rack.modules);
+        std::mem::swap(&mut self.name, &mut rack.name);
+        std::mem::swap(&mut self.author, &mut rack.author);
+        std::mem::swap(&mut self.description, &mut rack.description);
+        std::mem::swap(&mut self.last_error, &mut rack.last_error);
+
+        let initialized = rack.initialized;
+        let init_ms = rack.init_ms;
+        self.swap_out(Some(rack));
+
+        if initialized {
+            self.run_time_rms.set(init_ms);
+            self.profiler.set_init_ms(init_ms);
+        }
+
+        return Some(initialized);
+    }
+
+    pub fn clear(&mut self) {
+        self.log(LogMessage::new("Clearing module..."));
+
+        match self.spare.take() {
+            Some(mut rack) => {
+                std::mem::swap(&mut self.modules, &mut rack.modules);
+                std::mem::swap(&mut self.last_error, &mut rack.last_error);
+                self.swap_out(Some(rack));
+            },
+            // Only when cleared already and nothing was loaded since, so there are no modules left.
             None => {
-                self.log(format!("Clearing module..."));
-                self.module = None;
+                self.last_error = None;
+                self.swap_out(None);
             }
         }
     }
+
+    // Whatever was playing keeps playing on a copy of the input while the new modules fade in.
+    fn swap_out(&mut self, outgoing: Option<Box<LoadedRack>>) {
+        let from = match outgoing {
+            Some(rack) if self.running && !rack.modules.is_empty() => FadeFrom::Modules(rack),
+            Some(rack) => {
+                self.keep_spare(rack);
+                FadeFrom::Dry
+            },
+            None => FadeFrom::Dry
+        };
+
+        self.running = false;
+        self.start_fade(from);
+        self.profiler.clear();
+    }
+
+    // An empty rack can be cleared into later, the others go back to the loader.
+    fn keep_spare(&mut self, rack: Box<LoadedRack>) {
+        match self.spare.is_none() && rack.modules.is_empty() && rack.last_error.is_none() {
+            true => self.spare = Some(rack),
+            false => self.handoff.retire(rack)
+        }
+    }
+
+    // Sizes the fade buffers up front, so fading doesn't allocate on the audio thread.
+    pub fn prepare(&mut self, channels: usize, max_samples: usize) {
+        self.fade_buffer = vec![vec![0.0; max_samples]; channels.min(MAX_CHANNELS)];
+        self.last_frame = vec![0.0; channels];
+        self.held_frame = vec![0.0; channels];
+        self.safety.prepare(channels);
+        // Until the first block comes in.
+        self.buffer_size = max_samples;
+    }
+
+    pub fn set_crossfade_ms(&mut self, crossfade_ms: f32) {
+        self.crossfade_ms = crossfade_ms;
+    }
+
+    // The modules stopped working, the output fades from where they left it to dry.
+    // A fade that is already going keeps going.
+    pub fn fail(&mut self) {
+        self.running = false;
+
+        if self.fade.is_none() {
+            std::mem::swap(&mut self.held_frame, &mut self.last_frame);
+            self.start_fade(FadeFrom::Held);
+        }
+    }
+
+    // Call instead of run while stopped, the output is dry apart from what is fading out.
+    pub fn fade_out(&mut self, buffer: &mut Buffer) {
+        if self.fade.is_none() { return; }
+
+        let channels = buffer.as_slice();
+        self.run_fade_from(channels);
+        self.finish_fade(channels);
+    }
+
+    // Follows reordering and bypassing in the rack, without reloading.
+    pub fn arrange(&mut self, order: &[(u64, bool)]) {
+        rack::arrange(&mut self.modules, order);
+    }
+
+    // Updates in place, so the audio thread doesn't allocate every block.
+    pub fn update_slot_status(&self, slots: &mut Vec<SlotStatus>) {
+        let same_slots = slots.len() == self.modules.len() && slots.iter().zip(&self.modules).all(|(s, m)| s.id == m.id);
+
+        if !same_slots {
+            *slots = self.modules.iter().map(|m| m.status()).collect();
+            return;
+        }
+
+        for (slot, module) in slots.iter_mut().zip(&self.modules) {
+            slot.bypassed = module.bypassed;
+            slot.run_ms = module.run_time_rms.get();
+        }
+    }
     
+    // Only runs init.lua again for the modules that are swapped in, new racks come initialized from the loader.
     pub fn init(&mut self, sample_rate: Option<f32>) -> bool {        
         match sample_rate {
             Some(rate) => self.sample_rate = rate,
@@ -80,17 +272,20 @@ impl Runtime {
         }
 
         let execute_timer = Timer::new();
-        let init_result = self.initialize_lua();
+        let init_result = loader::init_modules(&mut self.modules, &self.console);
         let execute_time = execute_timer.elapsed_ms();
 
         match init_result {
-            Ok(_r) => {
+            Ok(info) => {
+                (self.name, self.author, self.description) = info;
                 self.run_time_rms.set(execute_time);
-                self.log(format!("Initialization took {:.2}ms.", execute_time));
+                self.profiler.set_init_ms(execute_time);
+                self.log(LogMessage::new("Initialization took {:.2}ms.").arg(execute_time as f64));
                 return true;
             },
             Err(e) => {
-                self.log(format!("Failed to initialize: {e}"));
+                self.error(LogMessage::new("Failed to initialize: {text}").text(&errors::describe(&e)));
+                self.last_error = errors::locate(&e);
                 return false;
             }
         }
@@ -99,14 +294,19 @@ impl Runtime {
     pub fn reset(&mut self) -> bool {
         let execute_timer = Timer::new();
         let reset_result = self.reset_lua();
+        let execute_time = execute_timer.elapsed_ms();
 
         match reset_result {
             Ok(_r) => {
-                self.log(format!("Reset in {:.2}ms.", execute_timer.elapsed_ms()));
+                // A reset module gets another chance.
+                self.safety.reset();
+                self.profiler.set_reset_ms(execute_time);
+                self.log(LogMessage::new("Reset in {:.2}ms.").arg(execute_time as f64));
                 return true;
             },
             Err(e) => {
-                self.log(format!("Failed to reset: {e}"));
+                self.error(LogMessage::new("Failed to reset: {text}").text(&errors::describe(&e)));
+                self.last_error = errors::locate(&e);
                 return  false;
             }
         }
@@ -118,32 +318,117 @@ impl Runtime {
 
         match trigger_result {
             Ok(_r) => {
-                self.log(format!("Triggered in {:.2}ms.", execute_timer.elapsed_ms()));
+                self.log(LogMessage::new("Triggered in {:.2}ms.").arg(execute_timer.elapsed_ms() as f64));
                 return true;
             },
             Err(e) => {
-                self.log(format!("Failed to trigger: {e}"));
+                self.error(LogMessage::new("Failed to trigger: {text}").text(&errors::describe(&e)));
+                self.last_error = errors::locate(&e);
                 return  false;
             }
         }
     }
 
+    // Only call between blocks, the module sees whatever the snippet changed on its next run.
+    pub fn evaluate(&mut self, command: &ReplCommand) -> Result<String, String> {
+        // Nothing to go by before the host said how fast it runs.
+        let timeout = match self.sample_rate > 0.0 {
+            true => Duration::from_secs_f32(self.buffer_size as f32 / self.sample_rate * REPL_BLOCK_SHARE),
+            false => Duration::ZERO
+        };
+        let module = match command.slot_id {
+            Some(id) => self.modules.iter_mut().find(|m| m.id == id),
+            None => self.modules.first_mut()
+        };
+
+        return match module {
+            Some(m) => m.module.evaluate(&command.code, timeout),
+            None => Err(String::from("No module loaded."))
+        };
+    }
+
     pub fn run(&mut self, buffer : &mut Buffer) -> bool {
         let execute_timer = Timer::new();
-        let run_result = self.run_lua(buffer);
+        let channels = buffer.as_slice();
+
+        self.run_fade_from(channels);
+        let run_result = self.run_lua(channels);
 
         match run_result {
             Ok(_r) => {
-                self.run_time_rms.process( execute_timer.elapsed_ms(), self.sample_rate);
+                match self.safety.process(channels, self.sample_rate) {
+                    Some(issue) if self.safety_stop => {
+                        self.error(LogMessage::new("Stopped, {text}.").text(&issue.describe()));
+                        self.fail();
+                        self.finish_fade(channels);
+                        return false;
+                    },
+                    Some(issue) => self.warn(LogMessage::new("Muted until reset, {text}.").text(&issue.describe())),
+                    None => ()
+                }
+
+                self.finish_fade(channels);
+                self.hold_last_frame(channels);
+                self.running = true;
+
+                let execute_time = execute_timer.elapsed_ms();
+                self.run_time_rms.process(execute_time, self.sample_rate);
+                self.record_profile(execute_time);
+                self.send_telemetry(channels.first().map_or(0, |c| c.len()));
                 return true;
             },
             Err(e) => {
-                self.log(format!("Failed to run: {e}"));
+                self.error(LogMessage::new("Failed to run: {text}").text(&errors::describe(&e)));
+                self.last_error = errors::locate(&e);
+
+                self.fail();
+                self.finish_fade(channels);
                 return  false;
             }
         }
     }
 
+    // Sampling counts where the Lua code spends its time, at a cost.
+    pub fn set_profiling(&mut self, profiling: bool, sampling: bool) {
+        self.profiling = profiling;
+        self.sampling = profiling && sampling;
+
+        for m in &mut self.modules {
+            m.module.set_sampling(self.sampling);
+        }
+    }
+
+    // Only fills in the report every so many blocks, and only while profiling.
+    pub fn update_profile(&mut self, report: &mut ProfileReport) {
+        if !self.profiling || !self.profiler.report_due() { return; }
+
+        self.profiler.report(report);
+        report.memory_kb = self.modules.iter().map(|m| m.module.used_memory()).sum::<usize>() as f32 / 1024.0;
+
+        if !self.sampling {
+            report.functions.clear();
+            return;
+        }
+
+        // Functions get the module name in front once there are several.
+        let several = self.modules.len() > 1;
+        let mut total = 0;
+        report.functions.clear();
+        for m in &self.modules {
+            m.module.read_samples(|label, count| {
+                let label = match several {
+                    true => FunctionLabel::new(format_args!("{}: {}", m.name, label.as_str())),
+                    false => *label
+                };
+
+                profiler::add_top_function(&mut report.functions, label, count);
+                total += count;
+            });
+        }
+
+        profiler::share_functions(&mut report.functions, total);
+    }
+
     pub fn get_sample_rate(&self) -> f32 {
         return self.sample_rate;
     }
@@ -156,91 +441,270 @@ impl Runtime {
         return self.channels;
     }
 
+    pub fn is_running(&self) -> bool {
+        return self.running;
+    }
+
     pub fn get_run_ms(&self) -> f32 {
         return self.run_time_rms.get();
     }
 
+    pub fn get_latency_samples(&self) -> u32 {
+        return self.modules.iter()
+            .filter(|m| !m.bypassed)
+            .map(|m| m.module.get_latency_samples())
+            .sum();
+    }
+
     pub fn set_clip(&mut self, clip: bool) {
         self.clip = clip;
     }
 
+    pub fn set_safety_stop(&mut self, safety_stop: bool) {
+        self.safety_stop = safety_stop;
+    }
+
+    pub fn is_muted(&self) -> bool {
+        return self.safety.is_muted();
+    }
+
     pub fn set_input_noise(&mut self, input_noise: bool) {
         self.input_noise = input_noise;
     }
 
-    fn initialize_lua(&mut self) -> LuaResult<()> {
-        self.log(format!("Setting up Lua state..."));
+    fn reset_lua(&mut self) -> LuaResult<()> {
+        if self.modules.is_empty() {
+            self.log(LogMessage::new("No module loaded."));
+        }
 
-        match &mut self.module {
-            Some(module) => { 
-                let init_result = module.init();
+        for m in &mut self.modules {
+            m.module.reset()?;
+        }
 
-                match &init_result {
-                    Ok(r) => {
-                        self.log(format!("Initialized module:\n{name} by {authors}\n\"{about}\"", 
-                            name = r.0, 
-                            authors = r.1,
-                            about = r.2));
+        Ok(())
+    }
 
-                        self.name = r.0.clone();
-                        self.author = r.1.clone();
-                        self.description = r.2.clone();
-                    },
-                    Err(_e) => { 
-                        init_result?; 
-                    }
-                }
-            }
-            None => self.log(format!("No module loaded."))
+    fn trigger_lua(&mut self) -> LuaResult<()> {
+        if self.modules.is_empty() {
+            self.log(LogMessage::new("No module loaded."));
         }
 
+        for m in &mut self.modules {
+            m.module.trigger()?;
+        }
+        
         Ok(())
     }
 
-    fn reset_lua(&mut self) -> LuaResult<()> {
-        match &mut self.module {
-            Some(module) => module.reset()?,
-            None => self.log(format!("No module loaded."))
+    fn run_lua(&mut self, channels: &mut [&mut [f32]]) -> LuaResult<()> {
+        self.channels = channels.len();
+        self.buffer_size = channels.first().map_or(0, |c| c.len());
+
+        if self.modules.is_empty() {
+            self.log(LogMessage::new("No module loaded."));
+        }
+
+        // Noise goes into the first module that runs, clipping happens after the last.
+        let first_active = self.modules.iter().position(|m| !m.bypassed);
+        let last_active = self.modules.iter().rposition(|m| !m.bypassed);
+
+        for index in 0..self.modules.len() {
+            if self.modules[index].bypassed { continue; }
+
+            // Warn once when the module was written for a different channel count.
+            let expected_channels = self.modules[index].module.manifest().and_then(|m| m.channels);
+            match expected_channels {
+                Some(expected) if expected != self.channels && !self.modules[index].channel_warning_shown => {
+                    self.warn(LogMessage::new("{text} is made for {} channel(s), but is running on {}.").text(&self.modules[index].name).arg(expected as f64).arg(self.channels as f64));
+                    self.modules[index].channel_warning_shown = true;
+                },
+                _ => ()
+            }
+
+            let input_noise = self.input_noise && first_active == Some(index);
+            let clip = self.clip && last_active == Some(index);
+
+            let execute_timer = Timer::new();
+            let logs = self.modules[index].module.run(channels, input_noise, clip)?;
+            self.modules[index].run_time_rms.process(execute_timer.elapsed_ms(), self.sample_rate);
+
+            for (level, message) in logs {
+                self.send(level, LogSource::Module, LogMessage::new("{text}").text(&message));
+            }
         }
 
         Ok(())
     }
 
-    fn trigger_lua(&mut self) -> LuaResult<()> {
-        match &mut self.module {
-            Some(module) => module.trigger()?,
-            None => self.log(format!("No module loaded."))
+    fn record_profile(&mut self, block_ms: f32) {
+        let mut timings = RunTimings::default();
+        for m in self.modules.iter().filter(|m| !m.bypassed) {
+            timings.add(&m.module.timings);
+        }
+
+        let deadline_ms = self.buffer_size as f32 / self.sample_rate * 1000.0;
+        self.profiler.record_block(block_ms, &timings, deadline_ms);
+    }
+
+    // Every so often rather than every block, the interface can't draw faster than that anyway.
+    fn send_telemetry(&mut self, samples: usize) {
+        let sender = match &self.telemetry {
+            Some(s) => s,
+            None => return
+        };
+
+        self.telemetry_samples += samples;
+        let interval = (telemetry::SEND_INTERVAL_MS / 1000.0 * self.sample_rate) as usize;
+        if self.telemetry_samples < interval { return; }
+        self.telemetry_samples = 0;
+
+        for m in self.modules.iter().filter(|m| !m.bypassed) {
+            // Only for show, a module that broke its own tables keeps running.
+            let _ = m.module.send_telemetry(m.id, sender);
         }
-        
-        Ok(())
     }
 
-    fn run_lua(&mut self, buffer : &mut Buffer) -> LuaResult<()> {
-        self.channels = buffer.channels();
-        self.buffer_size = buffer.samples();
+    fn start_fade(&mut self, from: FadeFrom) {
+        let length = (self.crossfade_ms / 1000.0 * self.sample_rate) as usize;
+
+        // A fade that is still going gives way to the new one.
+        match self.fade.take() {
+            Some(fade) => retire_fade(&self.handoff, fade.from),
+            None => ()
+        }
+
+        match length {
+            0 => retire_fade(&self.handoff, from),
+            _ => self.fade = Some(Crossfade::new(from, length))
+        }
+    }
+
+    // Copies the input before the modules change it, then runs the outgoing modules on the copy.
+    fn run_fade_from(&mut self, channels: &[&mut [f32]]) {
+        let fade = match &mut self.fade {
+            Some(fade) => fade,
+            None => return
+        };
+
+        match fade.from {
+            FadeFrom::Held => return,
+            _ => ()
+        }
 
-        match &mut self.module {
-            Some(module) => {
-                let logs = module.run(buffer, self.input_noise, self.clip)?;
-            
-                for log in logs {
-                    self.log(log);
+        // Only when the host sends more than it said it would, there's no room to copy it then.
+        let samples = channels.first().map_or(0, |c| c.len());
+        if self.fade_buffer.len() < channels.len() || self.fade_buffer.iter().any(|c| c.len() < samples) {
+            std::mem::swap(&mut self.held_frame, &mut self.last_frame);
+            retire_fade(&self.handoff, std::mem::replace(&mut fade.from, FadeFrom::Held));
+            return;
+        }
+
+        for (copy, input) in self.fade_buffer.iter_mut().zip(channels.iter()) {
+            copy[..samples].copy_from_slice(input);
+        }
+
+        let failed = match &mut fade.from {
+            FadeFrom::Modules(rack) => {
+                let modules = &mut rack.modules;
+                let first_active = modules.iter().position(|m| !m.bypassed);
+                let last_active = modules.iter().rposition(|m| !m.bypassed);
+
+                let mut copies: [&mut [f32]; MAX_CHANNELS] = Default::default();
+                for (copy, buffer) in copies.iter_mut().zip(self.fade_buffer.iter_mut()) {
+                    *copy = &mut buffer[..samples];
                 }
+                let copies = &mut copies[..channels.len()];
+
+                // Their logs are dropped, the new modules are the ones to watch.
+                let mut failed = false;
+                for (index, m) in modules.iter_mut().enumerate() {
+                    if m.bypassed || failed { continue; }
+
+                    let input_noise = self.input_noise && first_active == Some(index);
+                    let clip = self.clip && last_active == Some(index);
+                    failed = m.module.run(copies, input_noise, clip).is_err();
+                }
+
+                // Bad output isn't worth fading from either.
+                failed || copies.iter().any(|c| c.iter().any(|s| !s.is_finite()))
+            },
+            _ => false
+        };
+
+        if failed {
+            std::mem::swap(&mut self.held_frame, &mut self.last_frame);
+            retire_fade(&self.handoff, std::mem::replace(&mut fade.from, FadeFrom::Held));
+        }
+    }
+
+    fn finish_fade(&mut self, channels: &mut [&mut [f32]]) {
+        let fade = match &mut self.fade {
+            Some(fade) => fade,
+            None => return
+        };
+
+        match &fade.from {
+            FadeFrom::Held => {
+                let frame = &self.held_frame;
+                fade.apply(channels, |channel, _| frame.get(channel).copied().unwrap_or(0.0));
+            },
+            _ => {
+                let copies = &self.fade_buffer;
+                fade.apply(channels, |channel, index| copies[channel][index]);
             }
-            None => self.log(format!("No module loaded."))
         }
 
-        Ok(())
+        fade.advance(channels.first().map_or(0, |c| c.len()));
+        if fade.is_finished() {
+            match self.fade.take() {
+                Some(fade) => retire_fade(&self.handoff, fade.from),
+                None => ()
+            }
+        }
     }
 
-    fn log(&self, message : String) {
-        match &self.console {
-            Some(c) => {
-                c.log(message);
-            },
-            None => {
-                println!("No console exists for Runtime. Log not registered by receiver: {}", message)
+    // Only the channels prepare made room for.
+    fn hold_last_frame(&mut self, channels: &[&mut [f32]]) {
+        for (held, samples) in self.last_frame.iter_mut().zip(channels.iter()) {
+            match samples.last() {
+                Some(sample) => *held = *sample,
+                None => ()
             }
         }
     }
+
+    fn log(&self, message: LogMessage) {
+        self.send(LogLevel::Info, LogSource::Runtime, message);
+    }
+
+    fn warn(&self, message: LogMessage) {
+        self.send(LogLevel::Warn, LogSource::Runtime, message);
+    }
+
+    fn error(&self, message: LogMessage) {
+        self.send(LogLevel::Error, LogSource::Runtime, message);
+    }
+
+    fn send(&self, level: LogLevel, source: LogSource, message: LogMessage) {
+        send(&self.console, level, source, message);
+    }
+}
+
+// The outgoing modules of a fade go back to the loader, dropping them here would free on the audio thread.
+fn retire_fade(handoff: &RackHandoff, from: FadeFrom) {
+    match from {
+        FadeFrom::Modules(rack) => handoff.retire(rack),
+        _ => ()
+    }
+}
+
+fn send(console: &Option<ConsoleSender>, level: LogLevel, source: LogSource, message: LogMessage) {
+    match console {
+        Some(c) => {
+            c.send(level, source, message);
+        },
+        None => {
+            println!("No console exists for Runtime. Log not registered by receiver: {}", message.format())
+        }
+    }
 }
\ No newline at end of file
@@ -1,10 +1,10 @@
-use std::collections::BTreeMap;
+use std::{ collections::{ hash_map::DefaultHasher, BTreeMap }, hash::{ Hash, Hasher }, sync::{ atomic::{ AtomicBool, Ordering }, Arc }, time::{ Duration, Instant } };
 
 use mlua::prelude::*;
-use nih_plug::prelude::*;
-use crate::runtime::module_content::ModuleContent;
+use crate::{ runtime::module_content::ModuleContent };
+use mlem_console::LogLevel;
 
-use super::{library, parameter::Parameter, utils};
+use super::{envelopes, library, manifest::Manifest, oscillators, parameter::Parameter, profiler::{ FunctionLabel, RunTimings, SampleTable }, random, repl, require, resources, spectral::{self, Stft, StftConfig}, telemetry::{ TelemetrySender, TelemetryValue }, tuning, utils::{ self, Timer }};
 
 pub const LUA_BUFFERS_KEY: &str = "BUFFER_RAW";
 pub const LUA_SAMPLE_RATE_KEY: &str = "SAMPLE_RATE";
@@ -15,50 +15,154 @@ pub const LUA_NAME_KEY: &str = "MODULE_NAME";
 pub const LUA_AUTHORS_KEY: &str = "MODULE_AUTHORS";
 pub const LUA_ABOUT_KEY: &str = "MODULE_ABOUT";
 pub const LUA_LOGS_KEY: &str = "LOGS";
+pub const LUA_LOG_COUNT_KEY: &str = "LOG_COUNT";
 pub const LUA_PARAMETERS_KEY: &str = "PARAMETERS";
 pub const LUA_PARAMETER_VALUE_UPDATES_KEY: &str = "PARAMETER_VALUE_UPDATES";
+pub const LUA_STFT_KEY: &str = "STFT";
+pub const LUA_METERS_KEY: &str = "METERS";
+pub const LUA_WATCHES_KEY: &str = "WATCHES";
+const LUA_SET_DEFAULT_KEY: &str = "set_default";
+const LUA_RUNTIME_KEY: &str = "runtime";
+const LUA_WARN_KEY: &str = "warn";
+const LUA_LEVEL_KEY: &str = "level";
+const LUA_MESSAGE_KEY: &str = "message";
+const LUA_VALUE_KEY: &str = "value";
+const LUA_MIN_KEY: &str = "min";
+const LUA_MAX_KEY: &str = "max";
+const LUA_FRESH_KEY: &str = "fresh";
 const UNKNOWN: &str = "???";
+const TIMEOUT_CHECK_INSTRUCTIONS: u32 = 10000;
+const SAMPLE_INSTRUCTIONS: u32 = 1000;
+const ANONYMOUS: &str = "(anonymous)";
 
 pub struct RuntimeModule {
     pub hash: String,
+    // Of the last run.
+    pub timings: RunTimings,
     
     lua: Lua,
     lua_buffers: LuaTable,
     channels: usize,
+    stft: Option<Stft>,
+    resources_loading: Arc<AtomicBool>,
+    // The samples themselves live in the Lua app data, where the hook can get at them.
+    sampling: bool,
 
     content: ModuleContent
 }
 
 impl RuntimeModule {
-    pub fn new(content: ModuleContent, sample_rate : f32) -> RuntimeModule {
+    pub fn new(content: ModuleContent, sample_rate : f32, workspace_path: Option<String>) -> RuntimeModule {
         let lua = Lua::new();
         let lua_buffers = lua.create_table().expect("Couldn't create buffers.");
 
         let mut module = Self {
             hash: String::new(),
+            timings: RunTimings::default(),
 
             lua: lua,
             lua_buffers: lua_buffers,
             channels: 0,
+            stft: None,
+            resources_loading: Arc::new(AtomicBool::new(false)),
+            sampling: false,
 
             content: content
         };
 
         module.lua.globals().set(LUA_BUFFERS_KEY, &module.lua_buffers).expect("Couldn't set global.");
         module.lua.globals().set(LUA_SAMPLE_RATE_KEY, sample_rate).expect("Couldn't set global.");
+        module.lua.set_app_data(SampleTable::new());
+        random::register(&module.lua).expect("Couldn't register random.");
+        require::register(&module.lua, &module.content.libraries).expect("Couldn't register require.");
+        resources::register(&module.lua, workspace_path, module.resources_loading.clone()).expect("Couldn't register resources.");
+        oscillators::register(&module.lua, sample_rate).expect("Couldn't register oscillators.");
+        envelopes::register(&module.lua, sample_rate).expect("Couldn't register envelopes.");
+        tuning::register(&module.lua).expect("Couldn't register tuning.");
 
         module.hash = format!("{:x}", module.content.generate_hash());
 
         return module;
     }
 
+    // Stops any script that is still running after timeout, for console snippets.
+    pub fn set_timeout(&self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        self.lua.set_hook(LuaHookTriggers::new().every_nth_instruction(TIMEOUT_CHECK_INSTRUCTIONS), move |_, _| {
+            if Instant::now() > deadline {
+                return Err(LuaError::runtime(format!("Took longer than {:.2} ms.", timeout.as_secs_f32() * 1000.0)));
+            }
+
+            Ok(LuaVmState::Continue)
+        });
+    }
+
+    // Every so many instructions, counts which function Lua is in. Slows the module down while on.
+    pub fn set_sampling(&mut self, sampling: bool) {
+        if sampling == self.sampling { return; }
+        self.sampling = sampling;
+
+        if !sampling {
+            self.lua.remove_hook();
+            match self.lua.app_data_mut::<SampleTable>() {
+                Some(mut samples) => samples.clear(),
+                None => ()
+            }
+            return;
+        }
+
+        // Runs on the audio thread, so it hashes and copies instead of allocating.
+        self.lua.set_hook(LuaHookTriggers::new().every_nth_instruction(SAMPLE_INSTRUCTIONS), move |lua, debug| {
+            let source = debug.source();
+            let short_src = source.short_src.as_deref().unwrap_or(UNKNOWN);
+            let line = source.line_defined.unwrap_or(0);
+
+            let mut hasher = DefaultHasher::new();
+            short_src.hash(&mut hasher);
+            line.hash(&mut hasher);
+
+            match lua.app_data_mut::<SampleTable>() {
+                Some(mut samples) => samples.record(hasher.finish(), || {
+                    let names = debug.names();
+                    FunctionLabel::new(format_args!("{} {}:{}", names.name.as_deref().unwrap_or(ANONYMOUS), short_src, line))
+                }),
+                None => ()
+            }
+
+            Ok(LuaVmState::Continue)
+        });
+    }
+
+    // Each sampled function with how often Lua was found in it, since sampling started.
+    pub fn read_samples(&self, mut read: impl FnMut(&FunctionLabel, u32)) {
+        match self.lua.app_data_ref::<SampleTable>() {
+            Some(samples) => samples.iter().for_each(|(label, count)| read(label, count)),
+            None => ()
+        }
+    }
+
+    pub fn used_memory(&self) -> usize {
+        return self.lua.used_memory();
+    }
+
     pub fn init(&mut self) -> LuaResult<(String, String, String)> {
-        let init_contents = format!("{internal}\n{header}\n\n{content}\n\n{footer}", 
-            internal = library::internal_includes(), 
-            header = library::INIT_HEADER, 
-            content = &self.content.init,
-            footer = library::INIT_FOOTER);
-        self.lua.load(init_contents).exec()?;
+        match &self.content.manifest {
+            Some(manifest) => manifest.check_api_version().map_err(LuaError::runtime)?,
+            None => ()
+        }
+
+        for include in library::INTERNAL_INCLUDES {
+            self.lua.load(include.0).set_name(library::internal_chunk_name(include.1)).exec()?;
+        }
+
+        // Resources may only touch the disk during init.
+        self.resources_loading.store(true, Ordering::Relaxed);
+        let init_result = self.exec_script(
+            (library::INIT_HEADER, library::INIT_HEADER_NAME),
+            (self.content.init.as_str(), library::INIT_PATH),
+            (library::INIT_FOOTER, library::INIT_FOOTER_NAME));
+        self.resources_loading.store(false, Ordering::Relaxed);
+        init_result?;
 
         // Read additional data
         let globals = self.lua.globals();
@@ -75,36 +179,98 @@ impl RuntimeModule {
         if globals.contains_key(LUA_ABOUT_KEY)? {
             about = globals.get(LUA_ABOUT_KEY)?;
         }
+
+        // module.toml wins over the globals.
+        match &self.content.manifest {
+            Some(manifest) => {
+                name = manifest.name.clone().unwrap_or(name);
+                authors = manifest.authors_text().unwrap_or(authors);
+                about = manifest.about.clone().unwrap_or(about);
+                self.apply_parameter_defaults(manifest)?;
+            },
+            None => ()
+        }
+
+        self.stft = None;
+        if globals.contains_key(LUA_STFT_KEY)? {
+            let config = StftConfig::new_from_lua(&globals.get(LUA_STFT_KEY)?)?;
+            self.stft = Some(Stft::new(&self.lua, config)?);
+        }
         
         Ok((name, authors, about))
     }
 
+    pub fn manifest(&self) -> Option<&Manifest> {
+        return self.content.manifest.as_ref();
+    }
+
+    // Starts parameters at the values from module.toml.
+    fn apply_parameter_defaults(&self, manifest: &Manifest) -> LuaResult<()> {
+        let parameters: LuaTable = self.lua.globals().get(LUA_PARAMETERS_KEY)?;
+
+        for (name, value) in &manifest.parameters {
+            match parameters.get::<Option<LuaTable>>(name.as_str())? {
+                Some(parameter) => parameter.call_method::<()>(LUA_SET_DEFAULT_KEY, *value)?,
+                None => {
+                    let runtime: LuaTable = self.lua.globals().get(LUA_RUNTIME_KEY)?;
+                    let warn: LuaFunction = runtime.get(LUA_WARN_KEY)?;
+                    warn.call::<()>(format!("{} has a default for \"{}\", but there is no parameter with that name.", library::MANIFEST_PATH, name))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn reset(&mut self) -> LuaResult<()> {
-        let reset_contents = format!("{header}\n\n{content}\n\n{footer}", 
-            header = library::RESET_HEADER, 
-            content = &self.content.reset,
-            footer = library::RESET_FOOTER);
-        self.lua.load(reset_contents).exec()?;
+        // Start over from RANDOM_SEED so renders come out the same every time.
+        random::reseed(&self.lua)?;
+        self.exec_script(
+            (library::RESET_HEADER, library::RESET_HEADER_NAME),
+            (self.content.reset.as_str(), library::RESET_PATH),
+            (library::RESET_FOOTER, library::RESET_FOOTER_NAME))?;
+
+        match &mut self.stft {
+            Some(stft) => stft.reset(),
+            None => ()
+        }
         
         Ok(())
     }
 
     pub fn trigger(&mut self) -> LuaResult<()> {
-        let trigger_contents = format!("{header}\n\n{content}\n\n{footer}", 
-            header = library::TRIGGER_HEADER, 
-            content = &self.content.trigger,
-            footer = library::TRIGGER_FOOTER);
-        self.lua.load(trigger_contents).exec()?;
+        self.exec_script(
+            (library::TRIGGER_HEADER, library::TRIGGER_HEADER_NAME),
+            (self.content.trigger.as_str(), library::TRIGGER_PATH),
+            (library::TRIGGER_FOOTER, library::TRIGGER_FOOTER_NAME))?;
         
         Ok(())
     }
 
-    pub fn run(&mut self, buffer : &mut Buffer, input_noise: bool, clip: bool) -> LuaResult<Vec<String>> {
-        self.lua.globals().set(LUA_CHANNELS_KEY,buffer.channels())?;
-        self.lua.globals().set(LUA_BUFFER_SIZE_KEY,buffer.samples())?;
+    // Runs a console snippet in this module's state, a snippet that takes longer than timeout is stopped.
+    pub fn evaluate(&mut self, code: &str, timeout: Duration) -> Result<String, String> {
+        self.set_timeout(timeout);
+        let result = repl::evaluate(&self.lua, code);
+        self.lua.remove_hook();
+
+        // The timeout replaced the sampler's hook.
+        if self.sampling {
+            self.sampling = false;
+            self.set_sampling(true);
+        }
+
+        return result;
+    }
+
+    // Takes one slice per channel, so it runs on the plugin buffer as well as on copies of it.
+    pub fn run(&mut self, channels: &mut [&mut [f32]], input_noise: bool, clip: bool) -> LuaResult<Vec<(LogLevel, String)>> {
+        let copy_timer = Timer::new();
+        let samples = channels.first().map_or(0, |c| c.len());
+        self.lua.globals().set(LUA_CHANNELS_KEY, channels.len())?;
+        self.lua.globals().set(LUA_BUFFER_SIZE_KEY, samples)?;
         self.lua.globals().set(LUA_INPUT_NOISE_KEY, input_noise)?;
         
-        for c in 0..buffer.channels() {
+        for c in 0..channels.len() {
             if self.channels <= c {
                 let buffer = self.lua.create_table()?;
                 self.lua_buffers.set(c + 1, buffer)?; // Lua indexes start at 1
@@ -113,47 +279,73 @@ impl RuntimeModule {
         }
 
         // Write to lua buffers
-        let mut channel_sample_index = 1; // Lua indexes start at 1
-        for channel_samples in buffer.iter_samples() {            
-            let mut channel = 0;
-            for sample in channel_samples {
-                let channel_buffer : LuaTable = self.lua_buffers.get(channel + 1)?; // Lua indexes start at 1
-                channel_buffer.set(channel_sample_index, *sample)?;
-
-                channel += 1;
+        for (channel, channel_samples) in channels.iter().enumerate() {
+            let channel_buffer: LuaTable = self.lua_buffers.get(channel + 1)?; // Lua indexes start at 1
+            for (index, sample) in channel_samples.iter().enumerate() {
+                channel_buffer.set(index + 1, *sample)?;
             }
-
-            channel_sample_index += 1;
         }
         
+        self.timings.copy_ms = copy_timer.elapsed_ms();
+
         // Execute lua run
-        let run_contents = format!("{header}\n\n{content}\n\n{footer}", 
-            header = library::RUN_HEADER, 
-            content = &self.content.run,
-            footer = library::RUN_FOOTER);
-        self.lua.load(run_contents).exec()?;
+        let memory_before = self.lua.used_memory();
+        let lua_timer = Timer::new();
+        self.exec_script(
+            (library::RUN_HEADER, library::RUN_HEADER_NAME),
+            (self.content.run.as_str(), library::RUN_PATH),
+            (library::RUN_FOOTER, library::RUN_FOOTER_NAME))?;
+        self.timings.lua_ms = lua_timer.elapsed_ms();
+        // Collections during the run hide some of it, close enough to spot garbage heavy code.
+        self.timings.allocated_bytes = self.lua.used_memory().saturating_sub(memory_before);
 
         // Write from lua buffers to plugin buffer
-        let mut channel_sample_index = 1; // Lua indexes start at 1
-        for channel_samples in buffer.iter_samples() {                        
-            let mut channel = 0;
-            for sample in channel_samples {
-                let channel_buffer: LuaTable = self.lua_buffers.get(channel + 1)?;
-                *sample = if clip {
-                    utils::clip(channel_buffer.get(channel_sample_index)?)
-                } else {
-                    channel_buffer.get(channel_sample_index)?
+        let copy_timer = Timer::new();
+        for (channel, channel_samples) in channels.iter_mut().enumerate() {
+            let channel_buffer: LuaTable = self.lua_buffers.get(channel + 1)?; // Lua indexes start at 1
+            for (index, sample) in channel_samples.iter_mut().enumerate() {
+                *sample = channel_buffer.get(index + 1)?;
+            }
+        }
+
+        self.timings.copy_ms += copy_timer.elapsed_ms();
+
+        // Spectral processing
+        let spectral_timer = Timer::new();
+        match &mut self.stft {
+            Some(stft) => {
+                let callback: Option<LuaFunction> = self.lua.globals().get(spectral::LUA_SPECTRAL_CALLBACK_KEY)?;
+                let callback = match callback {
+                    Some(c) => c,
+                    None => return Err(LuaError::runtime(format!("STFT is enabled but no \"{}(frame)\" function is defined.", spectral::LUA_SPECTRAL_CALLBACK_KEY)))
                 };
 
-                channel += 1;
-            }
+                for (channel, channel_samples) in channels.iter_mut().enumerate() {
+                    stft.process(&callback, channel, channel_samples)?;
+                }
+            },
+            None => ()
+        }
+        self.timings.spectral_ms = spectral_timer.elapsed_ms();
 
-            channel_sample_index += 1;
+        if clip {
+            for channel_samples in channels.iter_mut() {
+                for sample in channel_samples.iter_mut() {
+                    *sample = utils::clip(*sample);
+                }
+            }
         }
         
         return self.process_logs();
     }
 
+    pub fn get_latency_samples(&self) -> u32 {
+        return match &self.stft {
+            Some(stft) => stft.latency_samples(),
+            None => 0
+        };
+    }
+
     pub fn get_parameters(&mut self) -> LuaResult<LuaTable> {
         return Ok(self.lua.globals().get(LUA_PARAMETERS_KEY)?);
     }
@@ -171,18 +363,66 @@ impl RuntimeModule {
         return Ok(self.lua.globals().set(LUA_PARAMETER_VALUE_UPDATES_KEY, updates_table)?);
     }
 
-    fn process_logs(&mut self) -> LuaResult<Vec<String>> {
+    // Sends what runtime.meter and runtime.watch were last given, only text that isn't a string allocates.
+    // Only what was set since the last send, so values a module stopped setting go stale.
+    pub fn send_telemetry(&self, slot_id: u64, sender: &TelemetrySender) -> LuaResult<()> {
+        let meters: LuaTable = self.lua.globals().get(LUA_METERS_KEY)?;
+        for pair in meters.pairs::<LuaString, LuaTable>() {
+            let (name, meter) = pair?;
+            // The meter tables are kept for reuse, so they're only marked as sent.
+            let fresh: bool = meter.get(LUA_FRESH_KEY)?;
+            if !fresh { continue; }
+            meter.set(LUA_FRESH_KEY, false)?;
+
+            let value = TelemetryValue::Meter {
+                value: meter.get(LUA_VALUE_KEY)?,
+                min: meter.get(LUA_MIN_KEY)?,
+                max: meter.get(LUA_MAX_KEY)?
+            };
+
+            sender.send(slot_id, &name.as_bytes(), value);
+        }
+
+        let watches: LuaTable = self.lua.globals().get(LUA_WATCHES_KEY)?;
+        for pair in watches.pairs::<LuaString, LuaValue>() {
+            let (name, value) = pair?;
+            let value = match value {
+                LuaValue::Integer(i) => TelemetryValue::Number(i as f64),
+                LuaValue::Number(n) => TelemetryValue::Number(n),
+                LuaValue::String(s) => TelemetryValue::text(&s.as_bytes()),
+                other => TelemetryValue::text(other.to_string()?.as_bytes())
+            };
+
+            sender.send(slot_id, &name.as_bytes(), value);
+        }
+        watches.clear()?;
+
+        Ok(())
+    }
+
+    // Header, user script and footer run as separate chunks, so errors point at the right file and line.
+    fn exec_script(&self, header: (&str, &str), content: (&str, &str), footer: (&str, &str)) -> LuaResult<()> {
+        self.lua.load(header.0).set_name(library::internal_chunk_name(header.1)).exec()?;
+        self.lua.load(content.0).set_name(library::user_chunk_name(content.1)).exec()?;
+        self.lua.load(footer.0).set_name(library::internal_chunk_name(footer.1)).exec()?;
+
+        Ok(())
+    }
+
+    fn process_logs(&mut self) -> LuaResult<Vec<(LogLevel, String)>> {
         // Get logs
         let mut logs = Vec::new();
         let lua_logs: LuaTable = self.lua.globals().get(LUA_LOGS_KEY)?;
 
-        for log_pair in lua_logs.pairs::<String, String>() {
-            let (_key, value) = log_pair?;
-            logs.push(value);
+        for log in lua_logs.sequence_values::<LuaTable>() {
+            let log = log?;
+            let level: String = log.get(LUA_LEVEL_KEY)?;
+            logs.push((LogLevel::from_label(&level), log.get(LUA_MESSAGE_KEY)?));
         }
 
         // Clear logs
         self.lua.globals().set(LUA_LOGS_KEY, self.lua.create_table()?)?;
+        self.lua.globals().set(LUA_LOG_COUNT_KEY, 0)?;
 
         Ok(logs)
     }
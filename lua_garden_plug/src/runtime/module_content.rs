@@ -1,5 +1,6 @@
-use std::hash::{ DefaultHasher, Hasher };
+use std::{ collections::BTreeMap, hash::{ DefaultHasher, Hasher } };
 use base64::{engine::general_purpose::URL_SAFE, Engine as _};
+use super::{ library, manifest::Manifest };
 
 const ENCODING_SEPERATOR: char = '\\';
 
@@ -9,7 +10,11 @@ pub struct ModuleContent {
     pub reset: String,
     pub trigger: String,
     pub run: String,
-    pub interface: String
+    pub interface: String,
+    // Extra files for require(), keyed by their path like "dsp/filters.lua".
+    pub libraries: BTreeMap<String, String>,
+    // From module.toml, only workspaces have one.
+    pub manifest: Option<Manifest>
 }
 
 pub struct ConstModuleContent<'a> {
@@ -27,7 +32,9 @@ impl ModuleContent {
             reset,
             trigger,
             run,
-            interface
+            interface,
+            libraries: BTreeMap::new(),
+            manifest: None
         };
 
         return content;
@@ -39,10 +46,43 @@ impl ModuleContent {
         hasher.write(self.reset.as_bytes());
         hasher.write(self.trigger.as_bytes());
         hasher.write(self.run.as_bytes());
-        
+
+        for (path, library) in &self.libraries {
+            hasher.write(path.as_bytes());
+            hasher.write(library.as_bytes());
+        }
+
+        match &self.manifest {
+            Some(manifest) => hasher.write(format!("{:?}", manifest).as_bytes()),
+            None => ()
+        }
+
         return hasher.finish();
     }
 
+    // Adds files that aren't there yet, files already in the module win.
+    pub fn add_libraries(&mut self, libraries: &BTreeMap<String, String>) {
+        for (path, library) in libraries {
+            if !self.libraries.contains_key(path) {
+                self.libraries.insert(path.clone(), library.clone());
+            }
+        }
+    }
+
+    // Every script and library as (path, code).
+    pub fn all_scripts(&self) -> Vec<(&str, &str)> {
+        let mut scripts = vec![
+            (library::INIT_PATH, self.init.as_str()),
+            (library::RESET_PATH, self.reset.as_str()),
+            (library::TRIGGER_PATH, self.trigger.as_str()),
+            (library::RUN_PATH, self.run.as_str()),
+            (library::INTERFACE_PATH, self.interface.as_str())
+        ];
+        scripts.extend(self.libraries.iter().map(|(path, library)| (path.as_str(), library.as_str())));
+
+        return scripts;
+    }
+
     pub fn to_base64(&self) -> String{
         let hash = format!("{:x}", self.generate_hash());
         let init_enc = URL_SAFE.encode(self.init.clone());
@@ -51,7 +91,7 @@ impl ModuleContent {
         let run_enc = URL_SAFE.encode(self.run.clone());
         let interface_enc = URL_SAFE.encode(self.interface.clone());
 
-        let base64 = format!("{hash}{sp}{init_enc}{sp}{reset_enc}{sp}{trigger_enc}{sp}{run_enc}{sp}{interface_enc}", 
+        let mut base64 = format!("{hash}{sp}{init_enc}{sp}{reset_enc}{sp}{trigger_enc}{sp}{run_enc}{sp}{interface_enc}", 
             hash = hash,
             sp = ENCODING_SEPERATOR,
             init_enc = init_enc,
@@ -59,6 +99,13 @@ impl ModuleContent {
             trigger_enc = trigger_enc,
             run_enc = run_enc,
             interface_enc = interface_enc);
+
+        for (path, library) in &self.libraries {
+            base64.push_str(&format!("{sp}{path_enc}{sp}{library_enc}",
+                sp = ENCODING_SEPERATOR,
+                path_enc = URL_SAFE.encode(path),
+                library_enc = URL_SAFE.encode(library)));
+        }
             
         return base64;
     }
@@ -0,0 +1,448 @@
+use std::{ f32::consts::TAU, sync::Arc };
+use mlua::prelude::*;
+use realfft::RealFftPlanner;
+use super::{ random::{ self, Random }, resources::AudioResource };
+
+pub const LUA_OSCILLATORS_KEY: &str = "osc";
+const DEFAULT_FREQUENCY: f32 = 440.0;
+const DEFAULT_PULSE_WIDTH: f32 = 0.5;
+const MIN_PULSE_WIDTH: f32 = 0.01;
+const MAX_PULSE_WIDTH: f32 = 0.99;
+const MIN_WAVETABLE_SIZE: usize = 4;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Shape {
+    Sine,
+    Saw,
+    Square,
+    Triangle,
+    Pulse,
+    Wavetable
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum NoiseColor {
+    White,
+    Pink,
+    Brown
+}
+
+pub struct Oscillator {
+    pub shape: Shape,
+    pub frequency: f32,
+    pub phase: f32,
+    pub pulse_width: f32,
+
+    sample_rate: f32,
+    wavetable: Option<Arc<Wavetable>>
+}
+
+pub struct Wavetable {
+    levels: Vec<Vec<f32>> // Level 0 holds every harmonic, each next level holds half of the previous.
+}
+
+pub struct Noise {
+    pub color: NoiseColor,
+
+    random: Random,
+    pink: [f32; 7],
+    brown: f32
+}
+
+impl Shape {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Shape::Sine => "sine",
+            Shape::Saw => "saw",
+            Shape::Square => "square",
+            Shape::Triangle => "triangle",
+            Shape::Pulse => "pulse",
+            Shape::Wavetable => "wavetable"
+        };
+    }
+}
+
+impl NoiseColor {
+    pub fn from_name(name: &str) -> Option<NoiseColor> {
+        return match name {
+            "white" => Some(NoiseColor::White),
+            "pink" => Some(NoiseColor::Pink),
+            "brown" | "red" => Some(NoiseColor::Brown),
+            _ => None
+        };
+    }
+}
+
+impl Oscillator {
+    pub fn new(shape: Shape, sample_rate: f32, frequency: f32) -> Oscillator {
+        Self {
+            shape: shape,
+            frequency: frequency,
+            phase: 0.0,
+            pulse_width: DEFAULT_PULSE_WIDTH,
+
+            sample_rate: sample_rate,
+            wavetable: None
+        }
+    }
+
+    pub fn new_wavetable(wavetable: Arc<Wavetable>, sample_rate: f32, frequency: f32) -> Oscillator {
+        let mut oscillator = Oscillator::new(Shape::Wavetable, sample_rate, frequency);
+        oscillator.wavetable = Some(wavetable);
+
+        return oscillator;
+    }
+
+    pub fn reset(&mut self, phase: f32) {
+        self.phase = phase.rem_euclid(1.0);
+    }
+
+    pub fn run(&mut self, phase_offset: f32) -> f32 {
+        let increment = if self.sample_rate > 0.0 { self.frequency / self.sample_rate } else { 0.0 };
+        let dt = f32::min(f32::abs(increment), 0.5);
+        let t = (self.phase + phase_offset).rem_euclid(1.0);
+
+        let output = match self.shape {
+            Shape::Sine => f32::sin(t * TAU),
+            Shape::Saw => 2.0 * t - 1.0 - poly_blep(t, dt),
+            Shape::Square => pulse(t, dt, 0.5),
+            Shape::Pulse => pulse(t, dt, f32::clamp(self.pulse_width, MIN_PULSE_WIDTH, MAX_PULSE_WIDTH)),
+            Shape::Triangle => {
+                let naive = if t < 0.5 { 4.0 * t - 1.0 } else { 3.0 - 4.0 * t };
+                naive + 4.0 * dt * (poly_blamp(t, dt) - poly_blamp((t + 0.5) % 1.0, dt))
+            },
+            Shape::Wavetable => match &self.wavetable {
+                Some(w) => w.read(t, self.frequency, self.sample_rate),
+                None => 0.0
+            }
+        };
+
+        self.phase = (self.phase + increment).rem_euclid(1.0);
+
+        return output;
+    }
+}
+
+impl Wavetable {
+    pub fn new(samples: &[f32]) -> Result<Wavetable, String> {
+        let size = samples.len();
+        if size < MIN_WAVETABLE_SIZE {
+            return Err(format!("A wavetable needs at least {} samples, got {}.", MIN_WAVETABLE_SIZE, size));
+        }
+
+        let mut planner = RealFftPlanner::<f32>::new();
+        let forward = planner.plan_fft_forward(size);
+        let inverse = planner.plan_fft_inverse(size);
+
+        let mut input = samples.to_vec();
+        let mut spectrum = forward.make_output_vec();
+        match forward.process(&mut input, &mut spectrum) {
+            Ok(()) => (),
+            Err(e) => return Err(format!("Couldn't analyze wavetable: {}", e))
+        }
+
+        let mut levels = Vec::new();
+        let mut harmonics = size / 2;
+
+        while harmonics > 0 {
+            let mut level_spectrum = spectrum.clone();
+            for bin in (harmonics + 1)..level_spectrum.len() {
+                level_spectrum[bin].re = 0.0;
+                level_spectrum[bin].im = 0.0;
+            }
+
+            // DC and nyquist bins can't carry an imaginary part in a real signal.
+            let nyquist = level_spectrum.len() - 1;
+            level_spectrum[0].im = 0.0;
+            level_spectrum[nyquist].im = 0.0;
+
+            let mut level = inverse.make_output_vec();
+            match inverse.process(&mut level_spectrum, &mut level) {
+                Ok(()) => (),
+                Err(e) => return Err(format!("Couldn't build wavetable: {}", e))
+            }
+
+            for sample in level.iter_mut() {
+                *sample /= size as f32;
+            }
+
+            levels.push(level);
+            harmonics /= 2;
+        }
+
+        return Ok(Wavetable {
+            levels: levels
+        });
+    }
+
+    pub fn read(&self, phase: f32, frequency: f32, sample_rate: f32) -> f32 {
+        let size = self.levels[0].len();
+        let max_harmonic = sample_rate * 0.5 / f32::max(f32::abs(frequency), f32::EPSILON);
+
+        let mut level = 0;
+        while level + 1 < self.levels.len() && ((size / 2) >> level) as f32 > max_harmonic {
+            level += 1;
+        }
+
+        let table = &self.levels[level];
+        let position = phase * size as f32;
+        let index = f32::floor(position) as usize % size;
+        let t = position - f32::floor(position);
+
+        return table[index] + (table[(index + 1) % size] - table[index]) * t;
+    }
+}
+
+impl Noise {
+    pub fn new(color: NoiseColor, random: Random) -> Noise {
+        Self {
+            color: color,
+
+            random: random,
+            pink: [0.0; 7],
+            brown: 0.0
+        }
+    }
+
+    pub fn run(&mut self) -> f32 {
+        // Starts over with the module, filters and all.
+        if self.random.follow_restarts() {
+            self.pink = [0.0; 7];
+            self.brown = 0.0;
+        }
+
+        let white = self.random.bipolar() as f32;
+
+        match self.color {
+            NoiseColor::White => white,
+            NoiseColor::Pink => {
+                // Paul Kellet's refined pink noise filter.
+                let b = &mut self.pink;
+                b[0] = 0.99886 * b[0] + white * 0.0555179;
+                b[1] = 0.99332 * b[1] + white * 0.0750759;
+                b[2] = 0.96900 * b[2] + white * 0.1538520;
+                b[3] = 0.86650 * b[3] + white * 0.3104856;
+                b[4] = 0.55000 * b[4] + white * 0.5329522;
+                b[5] = -0.7616 * b[5] - white * 0.0168980;
+                let pink = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + white * 0.5362;
+                b[6] = white * 0.115926;
+
+                pink * 0.11
+            },
+            NoiseColor::Brown => {
+                // Leaky integration keeps it from drifting off.
+                self.brown = (self.brown + 0.02 * white) / 1.02;
+
+                self.brown * 3.5
+            }
+        }
+    }
+}
+
+fn pulse(t: f32, dt: f32, width: f32) -> f32 {
+    let naive = if t < width { 1.0 } else { -1.0 };
+
+    return naive + poly_blep(t, dt) - poly_blep((t - width).rem_euclid(1.0), dt);
+}
+
+fn poly_blep(t: f32, dt: f32) -> f32 {
+    if dt <= 0.0 { return 0.0; }
+
+    if t < dt {
+        let t = t / dt;
+        return t + t - t * t - 1.0;
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt;
+        return t * t + t + t + 1.0;
+    }
+
+    return 0.0;
+}
+
+fn poly_blamp(t: f32, dt: f32) -> f32 {
+    if dt <= 0.0 { return 0.0; }
+
+    if t < dt {
+        let t = t / dt - 1.0;
+        return -1.0 / 3.0 * t * t * t;
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt + 1.0;
+        return 1.0 / 3.0 * t * t * t;
+    }
+
+    return 0.0;
+}
+
+impl LuaUserData for Oscillator {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("shape", |_, this| Ok(this.shape.name()));
+        fields.add_field_method_get("frequency", |_, this| Ok(this.frequency));
+        fields.add_field_method_set("frequency", |_, this, frequency: f32| {
+            this.frequency = frequency;
+            Ok(())
+        });
+        fields.add_field_method_get("phase", |_, this| Ok(this.phase));
+        fields.add_field_method_set("phase", |_, this, phase: f32| {
+            this.reset(phase);
+            Ok(())
+        });
+        fields.add_field_method_get("pulse_width", |_, this| Ok(this.pulse_width));
+        fields.add_field_method_set("pulse_width", |_, this, pulse_width: f32| {
+            this.pulse_width = pulse_width;
+            Ok(())
+        });
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method_mut("run", |_, this, (frequency, phase_offset): (Option<f32>, Option<f32>)| {
+            match frequency {
+                Some(f) => this.frequency = f,
+                None => ()
+            }
+
+            Ok(this.run(phase_offset.unwrap_or(0.0)))
+        });
+
+        methods.add_method_mut("reset", |_, this, phase: Option<f32>| {
+            this.reset(phase.unwrap_or(0.0));
+            Ok(())
+        });
+    }
+}
+
+impl LuaUserData for Noise {
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method_mut("run", |_, this, ()| Ok(this.run()));
+    }
+}
+
+pub const API: [(&str, &str); 11] = [
+    ("osc", "Band-limited oscillators and noise."),
+    ("osc.sine(frequency?)", "Sine oscillator, frequency in hz."),
+    ("osc.saw(frequency?)", "Band-limited saw oscillator."),
+    ("osc.square(frequency?)", "Band-limited square oscillator."),
+    ("osc.triangle(frequency?)", "Band-limited triangle oscillator."),
+    ("osc.pulse(frequency?, width?)", "Band-limited pulse oscillator, width 0.01..0.99."),
+    ("osc.wavetable(samples, frequency?)", "Plays a single cycle from a table or resource, mipmapped against aliasing."),
+    ("osc.noise(color?)", "\"white\", \"pink\" or \"brown\" noise."),
+    ("Oscillator:run(frequency?, phase_offset?)", "Next sample, optionally with a new frequency and a phase offset in cycles."),
+    ("Oscillator:reset(phase?)", "Restarts the oscillator at phase 0..1."),
+    ("Noise:run()", "Next noise sample.")
+];
+
+pub fn register(lua: &Lua, sample_rate: f32) -> LuaResult<()> {
+    let oscillators = lua.create_table()?;
+
+    for shape in [Shape::Sine, Shape::Saw, Shape::Square, Shape::Triangle] {
+        oscillators.set(shape.name(), lua.create_function(move |_, frequency: Option<f32>| {
+            Ok(Oscillator::new(shape, sample_rate, frequency.unwrap_or(DEFAULT_FREQUENCY)))
+        })?)?;
+    }
+
+    oscillators.set(Shape::Pulse.name(), lua.create_function(move |_, (frequency, pulse_width): (Option<f32>, Option<f32>)| {
+        let mut oscillator = Oscillator::new(Shape::Pulse, sample_rate, frequency.unwrap_or(DEFAULT_FREQUENCY));
+        oscillator.pulse_width = pulse_width.unwrap_or(DEFAULT_PULSE_WIDTH);
+
+        Ok(oscillator)
+    })?)?;
+
+    // Accepts a table of samples or a loaded resource holding a single cycle.
+    oscillators.set(Shape::Wavetable.name(), lua.create_function(move |_, (samples, frequency): (LuaValue, Option<f32>)| {
+        let samples: Vec<f32> = match samples {
+            LuaValue::Table(t) => t.sequence_values::<f32>().collect::<LuaResult<Vec<f32>>>()?,
+            LuaValue::UserData(u) => match u.borrow::<AudioResource>()?.samples.first() {
+                Some(s) => s.clone(),
+                None => Vec::new()
+            },
+            _ => return Err(LuaError::runtime("A wavetable must be created from a table of samples or a resource."))
+        };
+
+        let wavetable = match Wavetable::new(&samples) {
+            Ok(w) => w,
+            Err(e) => return Err(LuaError::runtime(e))
+        };
+
+        Ok(Oscillator::new_wavetable(Arc::new(wavetable), sample_rate, frequency.unwrap_or(DEFAULT_FREQUENCY)))
+    })?)?;
+
+    // Every noise gets its own sequence forked from the module's random generator, so RANDOM_SEED makes it reproducible.
+    oscillators.set("noise", lua.create_function(|lua, color: Option<String>| {
+        let color_name = color.unwrap_or(String::from("white"));
+
+        return match NoiseColor::from_name(&color_name) {
+            Some(c) => Ok(Noise::new(c, random::fork(lua)?)),
+            None => Err(LuaError::runtime(format!("Unknown noise color \"{}\". Use \"white\", \"pink\" or \"brown\".", color_name)))
+        };
+    })?)?;
+
+    return lua.globals().set(LUA_OSCILLATORS_KEY, oscillators);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 44100.0;
+
+    fn cycles(shape: Shape, frequency: f32, count: usize) -> Vec<f32> {
+        let mut oscillator = Oscillator::new(shape, SAMPLE_RATE, frequency);
+        let length = (SAMPLE_RATE / frequency) as usize * count;
+
+        return (0..length).map(|_| oscillator.run(0.0)).collect();
+    }
+
+    #[test]
+    fn saw_and_square_keep_their_level_without_dc() {
+        // 100 samples a cycle, so the sums below cover whole cycles.
+        for shape in [Shape::Saw, Shape::Square] {
+            let samples = cycles(shape, 441.0, 10);
+
+            let max = samples.iter().cloned().fold(f32::MIN, f32::max);
+            let min = samples.iter().cloned().fold(f32::MAX, f32::min);
+            assert!(max > 0.9 && max <= 1.0 + 1e-4, "{} peaks at {}", shape.name(), max);
+            assert!(min < -0.9 && min >= -1.0 - 1e-4, "{} dips to {}", shape.name(), min);
+
+            let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+            assert!(mean.abs() < 1e-3, "{} has a dc offset of {}", shape.name(), mean);
+        }
+    }
+
+    #[test]
+    fn saw_is_smoothed_around_the_jump() {
+        let samples = cycles(Shape::Saw, 441.0, 2);
+
+        // A naive saw falls by almost 2 in one sample, the correction spreads that over two.
+        let largest_step = samples.windows(2).map(|w| (w[1] - w[0]).abs()).fold(0.0, f32::max);
+        assert!(largest_step < 1.2, "saw falls by {} in one sample", largest_step);
+        assert!(samples[0].abs() < 1e-4);
+    }
+
+    #[test]
+    fn wavetable_interpolates_between_points() {
+        let wavetable = Wavetable::new(&[0.0, 1.0, 0.0, -1.0]).unwrap();
+
+        // Low enough to read the full table.
+        for (phase, expected) in [(0.0, 0.0), (0.125, 0.5), (0.25, 1.0), (0.375, 0.5), (0.625, -0.5), (0.875, -0.5)] {
+            let value = wavetable.read(phase, 1.0, SAMPLE_RATE);
+            assert!((value - expected).abs() < 1e-5, "{} at phase {}, expected {}", value, phase, expected);
+        }
+
+        assert!(Wavetable::new(&[0.0, 1.0, 0.0]).is_err());
+    }
+
+    #[test]
+    fn noise_repeats_for_a_fixed_seed() {
+        for color in [NoiseColor::White, NoiseColor::Pink, NoiseColor::Brown] {
+            let mut a = Noise::new(color, Random::new(21));
+            let mut b = Noise::new(color, Random::new(21));
+            let mut other = Noise::new(color, Random::new(22));
+
+            let a: Vec<f32> = (0..256).map(|_| a.run()).collect();
+            let b: Vec<f32> = (0..256).map(|_| b.run()).collect();
+            let other: Vec<f32> = (0..256).map(|_| other.run()).collect();
+
+            assert_eq!(a, b);
+            assert_ne!(a, other);
+        }
+    }
+}
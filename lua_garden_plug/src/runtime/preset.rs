@@ -0,0 +1,178 @@
+use std::{ collections::BTreeMap, fs, io, path::Path };
+use serde::{ Deserialize, Serialize };
+use super::{ library, parameter::Parameter };
+
+// Presets are files, so names have to work as file names on every system, Windows being the strictest.
+const INVALID_NAME_CHARS: &str = "/\\:<>\"|?*";
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9"
+];
+
+// Parameter values keyed by parameter name, so presets survive code edits that add, remove or reorder parameters.
+#[derive(Clone, PartialEq, Default, Debug, Serialize, Deserialize)]
+pub struct Preset {
+    #[serde(default)]
+    pub values: BTreeMap<String, f32>
+}
+
+impl Preset {
+    pub fn from_parameters(parameters: &BTreeMap<String, Parameter>) -> Preset {
+        Self {
+            values: parameters.iter().map(|(name, p)| (name.clone(), p.value)).collect()
+        }
+    }
+
+    // Sets the parameters this preset knows about, returns how many it found.
+    pub fn apply(&self, parameters: &mut BTreeMap<String, Parameter>) -> usize {
+        let mut applied = 0;
+
+        for (name, value) in &self.values {
+            match parameters.get_mut(name) {
+                Some(parameter) => {
+                    parameter.value = value.clamp(parameter.min, parameter.max);
+                    parameter.set_changed(true);
+                    applied += 1;
+                },
+                None => ()
+            }
+        }
+
+        return applied;
+    }
+
+    // Presets saved with a workspace, in its presets folder.
+    pub fn read_all(workspace_path: &str) -> Result<BTreeMap<String, Preset>, String> {
+        let mut presets = BTreeMap::new();
+        let folder = Path::new(workspace_path).join(library::PRESETS_FOLDER);
+
+        let entries = match fs::read_dir(&folder) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(presets),
+            Err(e) => return Err(format!("Couldn't read presets: {}", e))
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(library::PRESET_EXTENSION) { continue; }
+
+            let name = match path.file_stem() {
+                Some(stem) => stem.to_string_lossy().to_string(),
+                None => continue
+            };
+
+            let preset = fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| toml::from_str::<Preset>(&text).map_err(|e| e.to_string()));
+
+            match preset {
+                Ok(preset) => { presets.insert(name, preset); },
+                Err(e) => return Err(format!("Couldn't read preset \"{}\": {}", name, e))
+            }
+        }
+
+        return Ok(presets);
+    }
+
+    pub fn write(&self, workspace_path: &str, name: &str) -> Result<(), String> {
+        let folder = Path::new(workspace_path).join(library::PRESETS_FOLDER);
+        let text = match toml::to_string_pretty(self) {
+            Ok(text) => text,
+            Err(e) => return Err(format!("Couldn't write preset \"{}\": {}", name, e))
+        };
+
+        return fs::create_dir_all(&folder)
+            .and_then(|_| fs::write(folder.join(preset_file_name(name)), text))
+            .map_err(|e| format!("Couldn't write preset \"{}\": {}", name, e));
+    }
+
+    pub fn delete(workspace_path: &str, name: &str) -> Result<(), String> {
+        let path = Path::new(workspace_path).join(library::PRESETS_FOLDER).join(preset_file_name(name));
+
+        return fs::remove_file(path).map_err(|e| format!("Couldn't delete preset \"{}\": {}", name, e));
+    }
+}
+
+// Preset names end up as file names.
+pub fn validate_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(String::from("Preset name is empty."));
+    }
+
+    // Windows drops dots at the end and reserves device names, with or without an extension.
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    let reserved = RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem));
+    if name.starts_with('.') || name.ends_with('.') || name.contains(|c: char| INVALID_NAME_CHARS.contains(c) || c.is_control()) || reserved {
+        return Err(format!("\"{}\" isn't a valid preset name.", name));
+    }
+
+    return Ok(String::from(name));
+}
+
+fn preset_file_name(name: &str) -> String {
+    return format!("{}.{}", name, library::PRESET_EXTENSION);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parameters() -> BTreeMap<String, Parameter> {
+        return BTreeMap::from([
+            (String::from("Cutoff"), Parameter::new(String::from("Cutoff"), 1000.0, 20.0, 20000.0, 0.0)),
+            (String::from("Mix"), Parameter::new(String::from("Mix"), 0.5, 0.0, 1.0, 0.0))
+        ]);
+    }
+
+    #[test]
+    fn applies_by_name_and_skips_unknown() {
+        let mut parameters = parameters();
+        let preset = Preset {
+            values: BTreeMap::from([(String::from("Mix"), 2.0), (String::from("Removed"), 1.0)])
+        };
+
+        assert_eq!(preset.apply(&mut parameters), 1);
+        assert_eq!(parameters["Mix"].value, 1.0); // Clamped
+        assert!(parameters["Mix"].changed);
+        assert_eq!(parameters["Cutoff"].value, 1000.0);
+        assert!(!parameters["Cutoff"].changed);
+    }
+
+    #[test]
+    fn round_trips_through_workspace() {
+        let folder = std::env::temp_dir().join(format!("lua_garden_presets_{}", std::process::id()));
+        let workspace = folder.to_str().unwrap();
+
+        let preset = Preset::from_parameters(&parameters());
+        preset.write(workspace, "Bright").unwrap();
+
+        let presets = Preset::read_all(workspace).unwrap();
+        assert_eq!(presets.get("Bright"), Some(&preset));
+
+        Preset::delete(workspace, "Bright").unwrap();
+        assert!(Preset::read_all(workspace).unwrap().is_empty());
+
+        let _ = fs::remove_dir_all(&folder);
+    }
+
+    #[test]
+    fn names_must_be_file_names() {
+        assert_eq!(validate_name(" Warm "), Ok(String::from("Warm")));
+        assert!(validate_name("").is_err());
+        assert!(validate_name("../x").is_err());
+
+        for name in ["a<b", "a>b", "a:b", "a\"b", "a|b", "a?b", "a*b", "a\\b", "tab\there", "Warm."] {
+            assert!(validate_name(name).is_err(), "{} should be refused", name);
+        }
+
+        for name in ["CON", "nul", "Com1", "lpt9", "aux.old", "PRN "] {
+            assert!(validate_name(name).is_err(), "{} should be refused", name);
+        }
+
+        assert_eq!(validate_name("Console"), Ok(String::from("Console")));
+        assert_eq!(validate_name("COM10"), Ok(String::from("COM10")));
+        assert_eq!(validate_name("v1.2 bright"), Ok(String::from("v1.2 bright")));
+    }
+}
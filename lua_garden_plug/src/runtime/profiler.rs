@@ -0,0 +1,408 @@
+use std::{ fmt, io::Write };
+
+const BLOCK_HISTORY: usize = 2048;
+// Sorting the history isn't free, so reports only go out every so many blocks.
+const REPORT_INTERVAL: usize = 32;
+pub const HISTOGRAM_BINS: usize = 20;
+pub const TOP_FUNCTIONS: usize = 16;
+// Distinct functions the sampler keeps apart, the rest only count as missed.
+pub const SAMPLE_SLOTS: usize = 512;
+pub const LABEL_BYTES: usize = 96;
+
+// Where one run of a module went.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct RunTimings {
+    pub copy_ms: f32,
+    pub lua_ms: f32,
+    pub spectral_ms: f32,
+    pub allocated_bytes: usize
+}
+
+impl RunTimings {
+    pub fn add(&mut self, other: &RunTimings) {
+        self.copy_ms += other.copy_ms;
+        self.lua_ms += other.lua_ms;
+        self.spectral_ms += other.spectral_ms;
+        self.allocated_bytes += other.allocated_bytes;
+    }
+}
+
+// What the profiler view gets to see.
+#[derive(Clone, PartialEq)]
+pub struct ProfileReport {
+    pub blocks: u64,
+    pub over_deadline: u64,
+    pub deadline_ms: f32,
+
+    // Over the recent history.
+    pub min_ms: f32,
+    pub avg_ms: f32,
+    pub p99_ms: f32,
+    pub max_ms: f32,
+    // From zero up to twice the deadline, the last bin takes everything above.
+    pub histogram: Vec<u32>,
+
+    // Averages per block.
+    pub copy_ms: f32,
+    pub lua_ms: f32,
+    pub spectral_ms: f32,
+    pub allocated_kb: f32,
+
+    pub memory_kb: f32,
+    pub init_ms: f32,
+    pub reset_ms: f32,
+
+    // (function, share of the samples), most expensive first.
+    pub functions: Vec<(FunctionLabel, f32)>
+}
+
+// A function's name and where it is, cut at LABEL_BYTES so it can be made without allocating.
+#[derive(Clone, Copy, PartialEq)]
+pub struct FunctionLabel {
+    bytes: [u8; LABEL_BYTES],
+    len: usize
+}
+
+#[derive(Clone, Copy)]
+struct SampleSlot {
+    key: u64,
+    count: u32,
+    label: FunctionLabel
+}
+
+// How often the sampler found Lua in each function, filled in from a hook so it never grows.
+pub struct SampleTable {
+    slots: Vec<SampleSlot>,
+    used: usize,
+    // Samples of functions that didn't fit anymore.
+    pub missed: u32
+}
+
+// Collects block times on the audio thread, into a fixed ring so it doesn't allocate once full.
+pub struct Profiler {
+    block_ms: Vec<f32>,
+    sorted: Vec<f32>,
+    position: usize,
+
+    blocks: u64,
+    over_deadline: u64,
+    deadline_ms: f32,
+    totals: RunTimings,
+
+    init_ms: f32,
+    reset_ms: f32,
+    since_report: usize
+}
+
+impl ProfileReport {
+    pub fn new() -> ProfileReport {
+        Self {
+            blocks: 0,
+            over_deadline: 0,
+            deadline_ms: 0.0,
+
+            min_ms: 0.0,
+            avg_ms: 0.0,
+            p99_ms: 0.0,
+            max_ms: 0.0,
+            histogram: vec![0; HISTOGRAM_BINS],
+
+            copy_ms: 0.0,
+            lua_ms: 0.0,
+            spectral_ms: 0.0,
+            allocated_kb: 0.0,
+
+            memory_kb: 0.0,
+            init_ms: 0.0,
+            reset_ms: 0.0,
+
+            functions: Vec::with_capacity(TOP_FUNCTIONS)
+        }
+    }
+}
+
+impl FunctionLabel {
+    pub fn new(text: fmt::Arguments) -> FunctionLabel {
+        let mut bytes = [0; LABEL_BYTES];
+        let mut cursor = &mut bytes[..];
+        // Running out of room only cuts the label.
+        let _ = cursor.write_fmt(text);
+        let len = LABEL_BYTES - cursor.len();
+
+        return FunctionLabel { bytes: bytes, len: len };
+    }
+
+    // Up to the last whole character, in case the cut went through one.
+    pub fn as_str(&self) -> &str {
+        let bytes = &self.bytes[..self.len];
+        return match std::str::from_utf8(bytes) {
+            Ok(text) => text,
+            Err(e) => std::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap_or_default()
+        };
+    }
+}
+
+impl SampleTable {
+    pub fn new() -> SampleTable {
+        let empty = SampleSlot {
+            key: 0,
+            count: 0,
+            label: FunctionLabel { bytes: [0; LABEL_BYTES], len: 0 }
+        };
+
+        Self {
+            slots: vec![empty; SAMPLE_SLOTS],
+            used: 0,
+            missed: 0
+        }
+    }
+
+    // The label is only made the first time a key comes up.
+    pub fn record(&mut self, key: u64, label: impl FnOnce() -> FunctionLabel) {
+        let start = (key % SAMPLE_SLOTS as u64) as usize;
+
+        for offset in 0..SAMPLE_SLOTS {
+            let slot = &mut self.slots[(start + offset) % SAMPLE_SLOTS];
+
+            match slot.count {
+                0 => {
+                    slot.key = key;
+                    slot.count = 1;
+                    slot.label = label();
+                    self.used += 1;
+                    return;
+                },
+                _ if slot.key == key => {
+                    slot.count += 1;
+                    return;
+                },
+                _ => ()
+            }
+        }
+
+        self.missed += 1;
+    }
+
+    pub fn clear(&mut self) {
+        for slot in &mut self.slots {
+            slot.count = 0;
+        }
+        self.used = 0;
+        self.missed = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.used;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.used == 0;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&FunctionLabel, u32)> {
+        return self.slots.iter().filter(|s| s.count > 0).map(|s| (&s.label, s.count));
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Self {
+            block_ms: Vec::with_capacity(BLOCK_HISTORY),
+            sorted: Vec::with_capacity(BLOCK_HISTORY),
+            position: 0,
+
+            blocks: 0,
+            over_deadline: 0,
+            deadline_ms: 0.0,
+            totals: RunTimings::default(),
+
+            init_ms: 0.0,
+            reset_ms: 0.0,
+            since_report: 0
+        }
+    }
+
+    // Starts over for new modules.
+    pub fn clear(&mut self) {
+        self.block_ms.clear();
+        self.position = 0;
+        self.blocks = 0;
+        self.over_deadline = 0;
+        self.totals = RunTimings::default();
+        self.init_ms = 0.0;
+        self.reset_ms = 0.0;
+    }
+
+    pub fn set_init_ms(&mut self, init_ms: f32) {
+        self.init_ms = init_ms;
+    }
+
+    pub fn set_reset_ms(&mut self, reset_ms: f32) {
+        self.reset_ms = reset_ms;
+    }
+
+    // The deadline is how long the block lasts in real time.
+    pub fn record_block(&mut self, block_ms: f32, timings: &RunTimings, deadline_ms: f32) {
+        match self.block_ms.len() < BLOCK_HISTORY {
+            true => self.block_ms.push(block_ms),
+            false => self.block_ms[self.position] = block_ms
+        }
+        self.position = (self.position + 1) % BLOCK_HISTORY;
+
+        self.blocks += 1;
+        if block_ms > deadline_ms {
+            self.over_deadline += 1;
+        }
+
+        self.deadline_ms = deadline_ms;
+        self.totals.add(timings);
+    }
+
+    pub fn report_due(&mut self) -> bool {
+        self.since_report += 1;
+        if self.since_report < REPORT_INTERVAL { return false; }
+
+        self.since_report = 0;
+        return true;
+    }
+
+    // Fills in everything but memory and functions, which the modules know about.
+    pub fn report(&mut self, report: &mut ProfileReport) {
+        report.blocks = self.blocks;
+        report.over_deadline = self.over_deadline;
+        report.deadline_ms = self.deadline_ms;
+        report.init_ms = self.init_ms;
+        report.reset_ms = self.reset_ms;
+
+        let blocks = self.blocks.max(1) as f32;
+        report.copy_ms = self.totals.copy_ms / blocks;
+        report.lua_ms = self.totals.lua_ms / blocks;
+        report.spectral_ms = self.totals.spectral_ms / blocks;
+        report.allocated_kb = self.totals.allocated_bytes as f32 / 1024.0 / blocks;
+
+        self.sorted.clear();
+        self.sorted.extend_from_slice(&self.block_ms);
+        self.sorted.sort_unstable_by(|a, b| a.total_cmp(b));
+
+        let count = self.sorted.len();
+        report.min_ms = self.sorted.first().copied().unwrap_or(0.0);
+        report.max_ms = self.sorted.last().copied().unwrap_or(0.0);
+        report.avg_ms = self.sorted.iter().sum::<f32>() / count.max(1) as f32;
+        report.p99_ms = match count {
+            0 => 0.0,
+            _ => self.sorted[((count - 1) as f32 * 0.99).round() as usize]
+        };
+
+        report.histogram.clear();
+        report.histogram.resize(HISTOGRAM_BINS, 0);
+        let range_ms = self.deadline_ms * 2.0;
+        if range_ms <= 0.0 { return; }
+
+        for ms in &self.block_ms {
+            let bin = ((ms / range_ms) * HISTOGRAM_BINS as f32) as usize;
+            report.histogram[bin.min(HISTOGRAM_BINS - 1)] += 1;
+        }
+    }
+}
+
+// Keeps the most sampled functions, most first, without growing past TOP_FUNCTIONS.
+pub fn add_top_function(functions: &mut Vec<(FunctionLabel, f32)>, label: FunctionLabel, samples: u32) {
+    let samples = samples as f32;
+    let position = functions.iter().position(|(_, s)| samples > *s).unwrap_or(functions.len());
+    if position >= TOP_FUNCTIONS { return; }
+
+    if functions.len() == TOP_FUNCTIONS {
+        functions.pop();
+    }
+    functions.insert(position, (label, samples));
+}
+
+// Turns the sample counts into shares of all samples.
+pub fn share_functions(functions: &mut [(FunctionLabel, f32)], total: u32) {
+    let total = total.max(1) as f32;
+    for (_, samples) in functions.iter_mut() {
+        *samples /= total;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_block_statistics() {
+        let mut profiler = Profiler::new();
+        let timings = RunTimings { copy_ms: 0.5, lua_ms: 1.0, spectral_ms: 0.0, allocated_bytes: 2048 };
+
+        for ms in 1..=20 {
+            profiler.record_block(ms as f32, &timings, 5.0);
+        }
+
+        let mut report = ProfileReport::new();
+        profiler.report(&mut report);
+
+        assert_eq!(report.blocks, 20);
+        assert_eq!(report.over_deadline, 15);
+        assert_eq!((report.min_ms, report.avg_ms, report.max_ms), (1.0, 10.5, 20.0));
+        assert_eq!(report.p99_ms, 20.0);
+        assert_eq!(report.lua_ms, 1.0);
+        assert_eq!(report.allocated_kb, 2.0);
+        assert_eq!(report.histogram.iter().sum::<u32>(), 20);
+        assert_eq!(report.histogram[2], 1);
+        assert_eq!(report.histogram[HISTOGRAM_BINS - 1], 11);
+    }
+
+    #[test]
+    fn keeps_a_bounded_history() {
+        let mut profiler = Profiler::new();
+
+        for _ in 0..BLOCK_HISTORY + 10 {
+            profiler.record_block(1.0, &RunTimings::default(), 2.0);
+        }
+
+        assert_eq!(profiler.block_ms.len(), BLOCK_HISTORY);
+        assert_eq!(profiler.blocks, (BLOCK_HISTORY + 10) as u64);
+    }
+
+    #[test]
+    fn sorts_functions_by_samples() {
+        let mut functions = Vec::with_capacity(TOP_FUNCTIONS);
+        add_top_function(&mut functions, FunctionLabel::new(format_args!("a")), 1);
+        add_top_function(&mut functions, FunctionLabel::new(format_args!("b")), 3);
+        for _ in 0..TOP_FUNCTIONS {
+            add_top_function(&mut functions, FunctionLabel::new(format_args!("c")), 2);
+        }
+        share_functions(&mut functions, 4);
+
+        assert_eq!(functions.len(), TOP_FUNCTIONS);
+        assert_eq!(functions[0].0.as_str(), "b");
+        assert_eq!(functions[0].1, 0.75);
+        assert!(functions.iter().all(|(label, _)| label.as_str() != "a"));
+    }
+
+    #[test]
+    fn samples_into_a_fixed_table() {
+        let mut table = SampleTable::new();
+
+        for key in 0..SAMPLE_SLOTS as u64 + 3 {
+            table.record(key * SAMPLE_SLOTS as u64, || FunctionLabel::new(format_args!("f {}", key)));
+        }
+        table.record(0, || panic!("Labels are only made once."));
+
+        assert_eq!(table.len(), SAMPLE_SLOTS);
+        assert_eq!(table.missed, 3);
+        assert_eq!(table.iter().map(|(_, count)| count).sum::<u32>(), SAMPLE_SLOTS as u32 + 1);
+
+        table.clear();
+        assert!(table.is_empty());
+        assert_eq!(table.iter().count(), 0);
+    }
+
+    #[test]
+    fn cuts_labels_on_a_character_boundary() {
+        let long = "é".repeat(LABEL_BYTES);
+        let label = FunctionLabel::new(format_args!("x{}", long));
+
+        assert_eq!(label.as_str().len(), LABEL_BYTES - 1);
+    }
+}
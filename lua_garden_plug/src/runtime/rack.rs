@@ -0,0 +1,152 @@
+use std::{ path::Path, sync::atomic::{ AtomicU64, Ordering } };
+use super::{ module::RuntimeModule, module_content::ModuleContent, utils::RMS, workspace::Workspace };
+
+const DRAFT_SLOT_NAME: &str = "Draft";
+
+static NEXT_SLOT_ID: AtomicU64 = AtomicU64::new(1);
+
+// Where the code of a rack slot comes from.
+#[derive(Clone, PartialEq)]
+pub enum RackSource {
+    // A copy of the draft, taken when the slot was added or updated.
+    Draft(ModuleContent),
+    Workspace(Workspace)
+}
+
+// One module in the rack, as the interface edits it.
+#[derive(Clone, PartialEq)]
+pub struct RackSlot {
+    // Follows the slot through reordering, so the runtime can rearrange without reloading.
+    pub id: u64,
+    pub source: RackSource,
+    pub bypassed: bool
+}
+
+// One loaded module in the rack, on the audio thread.
+pub struct RackModule {
+    pub id: u64,
+    pub module: RuntimeModule,
+    pub bypassed: bool,
+    pub name: String,
+    pub run_time_rms: RMS,
+    pub channel_warning_shown: bool
+}
+
+// What the interface gets to see of a loaded slot.
+#[derive(Clone, PartialEq)]
+pub struct SlotStatus {
+    pub id: u64,
+    pub name: String,
+    pub bypassed: bool,
+    pub run_ms: f32
+}
+
+impl RackSlot {
+    pub fn new(source: RackSource) -> RackSlot {
+        Self {
+            id: NEXT_SLOT_ID.fetch_add(1, Ordering::Relaxed),
+            source: source,
+            bypassed: false
+        }
+    }
+
+    pub fn content(&self) -> &ModuleContent {
+        return match &self.source {
+            RackSource::Draft(content) => content,
+            RackSource::Workspace(workspace) => &workspace.content
+        };
+    }
+
+    pub fn content_mut(&mut self) -> &mut ModuleContent {
+        return match &mut self.source {
+            RackSource::Draft(content) => content,
+            RackSource::Workspace(workspace) => &mut workspace.content
+        };
+    }
+
+    pub fn workspace_path(&self) -> Option<String> {
+        return match &self.source {
+            RackSource::Draft(_) => None,
+            RackSource::Workspace(workspace) => Some(workspace.path.clone())
+        };
+    }
+
+    // The manifest name if there is one, otherwise the folder name.
+    pub fn label(&self) -> String {
+        let manifest_name = self.content().manifest.as_ref().and_then(|m| m.name.clone());
+
+        return match (&self.source, manifest_name) {
+            (_, Some(name)) => name,
+            (RackSource::Draft(_), None) => String::from(DRAFT_SLOT_NAME),
+            (RackSource::Workspace(workspace), None) => Path::new(&workspace.path).file_name()
+                .map_or(workspace.path.clone(), |n| n.to_string_lossy().to_string())
+        };
+    }
+}
+
+impl RackModule {
+    pub fn new(slot: &RackSlot, module: RuntimeModule) -> RackModule {
+        Self {
+            id: slot.id,
+            module: module,
+            bypassed: slot.bypassed,
+            name: slot.label(),
+            run_time_rms: RMS::new(),
+            channel_warning_shown: false
+        }
+    }
+
+    pub fn status(&self) -> SlotStatus {
+        return SlotStatus {
+            id: self.id,
+            name: self.name.clone(),
+            bypassed: self.bypassed,
+            run_ms: self.run_time_rms.get()
+        };
+    }
+}
+
+// Sorts modules into the order of the (id, bypassed) list and sets their bypass.
+// Modules that aren't listed keep their order after the listed ones, an empty list changes nothing.
+pub fn arrange(modules: &mut Vec<RackModule>, order: &[(u64, bool)]) {
+    sort_by_ids(modules, order, |m| m.id);
+
+    for module in modules.iter_mut() {
+        match order.iter().find(|(id, _)| *id == module.id) {
+            Some((_, bypassed)) => module.bypassed = *bypassed,
+            None => ()
+        }
+    }
+}
+
+fn sort_by_ids<T>(items: &mut Vec<T>, order: &[(u64, bool)], id: impl Fn(&T) -> u64) {
+    items.sort_by_key(|item| {
+        let item_id = id(item);
+        order.iter().position(|(id, _)| *id == item_id).unwrap_or(usize::MAX)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sorts_into_rack_order() {
+        let mut items = vec![1, 2, 3, 4];
+        sort_by_ids(&mut items, &[(3, false), (1, true)], |i| *i);
+        assert_eq!(items, vec![3, 1, 2, 4]);
+
+        sort_by_ids(&mut items, &[], |i| *i);
+        assert_eq!(items, vec![3, 1, 2, 4]);
+    }
+
+    #[test]
+    fn slots_get_their_own_ids() {
+        let content = ModuleContent::new(String::new(), String::new(), String::new(), String::new(), String::new());
+        let a = RackSlot::new(RackSource::Draft(content.clone()));
+        let b = RackSlot::new(RackSource::Draft(content));
+
+        assert_ne!(a.id, b.id);
+        assert_eq!(a.label(), DRAFT_SLOT_NAME);
+    }
+}
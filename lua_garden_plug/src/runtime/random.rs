@@ -0,0 +1,401 @@
+use std::{ sync::{ atomic::{ AtomicU64, Ordering }, Arc }, time::{ SystemTime, UNIX_EPOCH } };
+use mlua::prelude::*;
+
+pub const LUA_RANDOM_KEY: &str = "random";
+pub const LUA_RANDOM_SEED_KEY: &str = "RANDOM_SEED";
+
+const PCG_MULTIPLIER: u64 = 6364136223846793005;
+const PCG_DEFAULT_STREAM: u64 = 1442695040888963407;
+
+static INSTANCE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// PCG32, small and fast enough to call per sample.
+#[derive(Clone)]
+pub struct Random {
+    state: u64,
+    increment: u64,
+    gaussian_spare: Option<f64>,
+    // Shared by the module generator and its forks, counts resets so the forks know to start over.
+    resets: Option<Arc<AtomicU64>>,
+    seen_resets: u64,
+    // The seed and stream a fork started from.
+    origin: Option<(u64, u64)>,
+    // Only the module generator follows RANDOM_SEED, this is the value it last seeded from.
+    follows_seed: bool,
+    seeded_with: Option<i64>
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        return Random::new_with_stream(seed, PCG_DEFAULT_STREAM);
+    }
+
+    pub fn new_with_stream(seed: u64, stream: u64) -> Random {
+        let mut random = Self {
+            state: 0,
+            increment: (stream << 1) | 1,
+            gaussian_spare: None,
+            resets: None,
+            seen_resets: 0,
+            origin: None,
+            follows_seed: false,
+            seeded_with: None
+        };
+        random.seed(seed);
+
+        return random;
+    }
+
+    // Different for every instance, used when the module doesn't ask for a seed.
+    pub fn new_unseeded() -> Random {
+        let time = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(t) => t.as_nanos() as u64,
+            Err(_) => 0
+        };
+        let instance = INSTANCE_COUNTER.fetch_add(1, Ordering::Relaxed);
+
+        return Random::new_with_stream(time ^ instance.wrapping_mul(PCG_MULTIPLIER), instance);
+    }
+
+    // The module generator, its forks start over whenever it restarts.
+    pub fn new_module() -> Random {
+        let mut random = Random::new_unseeded();
+        random.resets = Some(Arc::new(AtomicU64::new(0)));
+        random.follows_seed = true;
+
+        return random;
+    }
+
+    pub fn seed(&mut self, seed: u64) {
+        self.state = 0;
+        self.gaussian_spare = None;
+        self.next_u32();
+        self.state = self.state.wrapping_add(seed);
+        self.next_u32();
+    }
+
+    // A new generator with its own sequence, deterministic if this one is seeded.
+    pub fn fork(&mut self) -> Random {
+        let seed = (self.next_u32() as u64) << 32 | self.next_u32() as u64;
+        let stream = (self.next_u32() as u64) << 32 | self.next_u32() as u64;
+        let mut fork = Random::new_with_stream(seed, stream);
+
+        match &self.resets {
+            Some(resets) => {
+                fork.seen_resets = resets.load(Ordering::Relaxed);
+                fork.resets = Some(resets.clone());
+                fork.origin = Some((seed, stream));
+            },
+            None => ()
+        }
+
+        return fork;
+    }
+
+    // Seeds again on the default stream, so the same seed gives the same sequence in every instance.
+    // Sends every fork back to where it started, for resetting the module.
+    pub fn restart(&mut self, seed: u64) {
+        self.increment = (PCG_DEFAULT_STREAM << 1) | 1;
+        self.seed(seed);
+
+        match &self.resets {
+            Some(resets) => {
+                resets.fetch_add(1, Ordering::Relaxed);
+                self.seen_resets = resets.load(Ordering::Relaxed);
+            },
+            None => ()
+        }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        self.follow_restarts();
+
+        let state = self.state;
+        self.state = state.wrapping_mul(PCG_MULTIPLIER).wrapping_add(self.increment);
+
+        let xorshifted = (((state >> 18) ^ state) >> 27) as u32;
+        let rotation = (state >> 59) as u32;
+
+        return xorshifted.rotate_right(rotation);
+    }
+
+    // [0, 1)
+    pub fn uniform(&mut self) -> f64 {
+        return self.next_u32() as f64 / 4294967296.0;
+    }
+
+    // [-1, 1)
+    pub fn bipolar(&mut self) -> f64 {
+        return self.uniform() * 2.0 - 1.0;
+    }
+
+    // [min, max], inclusive like math.random.
+    pub fn integer(&mut self, min: i64, max: i64) -> i64 {
+        if max <= min { return min; }
+
+        let range = (max - min) as u64 + 1;
+        return min + (self.uniform() * range as f64) as i64;
+    }
+
+    // Box-Muller, every second call uses the spare value.
+    pub fn gaussian(&mut self) -> f64 {
+        self.follow_restarts();
+
+        match self.gaussian_spare.take() {
+            Some(spare) => return spare,
+            None => ()
+        }
+
+        let mut u = self.uniform();
+        while u <= f64::MIN_POSITIVE {
+            u = self.uniform();
+        }
+        let v = self.uniform();
+
+        let radius = f64::sqrt(-2.0 * f64::ln(u));
+        let angle = std::f64::consts::TAU * v;
+        self.gaussian_spare = Some(radius * f64::sin(angle));
+
+        return radius * f64::cos(angle);
+    }
+
+    // Seeds the module generator when the module set RANDOM_SEED or changed it, before anything is drawn from it.
+    fn follow_seed(&mut self, lua: &Lua) -> LuaResult<()> {
+        if !self.follows_seed { return Ok(()); }
+
+        match read_seed(lua)? {
+            Some(seed) if self.seeded_with != Some(seed) => {
+                self.seeded_with = Some(seed);
+                self.restart(seed as u64);
+            },
+            _ => ()
+        }
+
+        Ok(())
+    }
+
+    // A fork starts over from its origin once the module generator restarted, returns whether it did.
+    pub fn follow_restarts(&mut self) -> bool {
+        let (resets, origin) = match (&self.resets, self.origin) {
+            (Some(resets), Some(origin)) => (resets.load(Ordering::Relaxed), origin),
+            _ => return false
+        };
+        if resets == self.seen_resets { return false; }
+
+        self.seen_resets = resets;
+        self.increment = (origin.1 << 1) | 1;
+        self.seed(origin.0);
+
+        return true;
+    }
+}
+
+impl LuaUserData for Random {
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method_mut("seed", |_, this, seed: i64| {
+            this.seed(seed as u64);
+            Ok(())
+        });
+
+        methods.add_method_mut("uniform", |lua, this, (min, max): (Option<f64>, Option<f64>)| {
+            this.follow_seed(lua)?;
+            let min = min.unwrap_or(0.0);
+            let max = max.unwrap_or(1.0);
+
+            Ok(min + this.uniform() * (max - min))
+        });
+
+        methods.add_method_mut("bipolar", |lua, this, ()| {
+            this.follow_seed(lua)?;
+            Ok(this.bipolar())
+        });
+
+        methods.add_method_mut("gaussian", |lua, this, (mean, deviation): (Option<f64>, Option<f64>)| {
+            this.follow_seed(lua)?;
+            Ok(mean.unwrap_or(0.0) + this.gaussian() * deviation.unwrap_or(1.0))
+        });
+
+        methods.add_method_mut("integer", |lua, this, (min, max): (i64, i64)| {
+            this.follow_seed(lua)?;
+            Ok(this.integer(min, max))
+        });
+
+        methods.add_method_mut("chance", |lua, this, probability: f64| {
+            this.follow_seed(lua)?;
+            Ok(this.uniform() < probability)
+        });
+
+        methods.add_method_mut("new", |lua, this, seed: Option<i64>| {
+            return match seed {
+                Some(s) => Ok(Random::new(s as u64)),
+                None => {
+                    this.follow_seed(lua)?;
+                    Ok(this.fork())
+                }
+            };
+        });
+    }
+}
+
+pub const API: [(&str, &str); 8] = [
+    ("random", "The module's random generator, seed it with RANDOM_SEED or random:seed(seed)."),
+    ("random:seed(seed)", "Restarts the sequence from seed."),
+    ("random:uniform(min?, max?)", "Uniform number between min and max, 0..1 by default."),
+    ("random:bipolar()", "Uniform number between -1 and 1."),
+    ("random:gaussian(mean?, deviation?)", "Normally distributed number."),
+    ("random:integer(min, max)", "Whole number between min and max, both included."),
+    ("random:chance(probability)", "True with the given probability 0..1."),
+    ("random:new(seed?)", "An independent generator, forked from this one when no seed is given.")
+];
+
+// Seeded from RANDOM_SEED when it is first used, so everything created after setting it in init.lua is reproducible.
+pub fn register(lua: &Lua) -> LuaResult<()> {
+    let random = lua.create_userdata(Random::new_module())?;
+    return lua.globals().set(LUA_RANDOM_KEY, random);
+}
+
+// Restarts the module generator and its forks from RANDOM_SEED, if the module set one.
+pub fn reseed(lua: &Lua) -> LuaResult<()> {
+    return match read_seed(lua)? {
+        Some(seed) => {
+            let random: LuaAnyUserData = lua.globals().raw_get(LUA_RANDOM_KEY)?;
+            let mut random = random.borrow_mut::<Random>()?;
+            random.restart(seed as u64);
+            random.seeded_with = Some(seed);
+            Ok(())
+        },
+        None => Ok(())
+    };
+}
+
+// Gives native generators their own sequence, derived from the module generator.
+pub fn fork(lua: &Lua) -> LuaResult<Random> {
+    let random: LuaAnyUserData = lua.globals().raw_get(LUA_RANDOM_KEY)?;
+    let mut random = random.borrow_mut::<Random>()?;
+    random.follow_seed(lua)?;
+
+    return Ok(random.fork());
+}
+
+// Raw, so a module's own metatable on _G stays out of it.
+fn read_seed(lua: &Lua) -> LuaResult<Option<i64>> {
+    return Ok(match lua.globals().raw_get::<LuaValue>(LUA_RANDOM_SEED_KEY)? {
+        LuaValue::Integer(seed) => Some(seed),
+        LuaValue::Number(seed) => Some(seed as i64),
+        _ => None
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut a = Random::new(1234);
+        let mut b = Random::new(1234);
+
+        for _ in 0..1000 {
+            assert_eq!(a.next_u32(), b.next_u32());
+        }
+    }
+
+    #[test]
+    fn reseeding_restarts_sequence() {
+        let mut random = Random::new(42);
+        let first: Vec<u32> = (0..16).map(|_| random.next_u32()).collect();
+
+        random.seed(42);
+        let second: Vec<u32> = (0..16).map(|_| random.next_u32()).collect();
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn different_seeds_differ() {
+        let mut a = Random::new(1);
+        let mut b = Random::new(2);
+
+        let a: Vec<u32> = (0..16).map(|_| a.next_u32()).collect();
+        let b: Vec<u32> = (0..16).map(|_| b.next_u32()).collect();
+
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn forks_are_deterministic() {
+        let mut a = Random::new(7);
+        let mut b = Random::new(7);
+        let mut fork_a = a.fork();
+        let mut fork_b = b.fork();
+
+        assert_eq!(fork_a.next_u32(), fork_b.next_u32());
+        assert_ne!(fork_a.next_u32(), a.next_u32());
+    }
+
+    #[test]
+    fn forks_start_over_when_the_module_restarts() {
+        let mut module = Random::new_module();
+        module.restart(11);
+        let mut fork = module.fork();
+        let first: Vec<u32> = (0..16).map(|_| fork.next_u32()).collect();
+
+        module.restart(11);
+        let second: Vec<u32> = (0..16).map(|_| fork.next_u32()).collect();
+        assert_eq!(first, second);
+
+        // Forks of plain generators are on their own.
+        let mut plain = Random::new(11);
+        let mut fork = plain.fork();
+        let first = fork.next_u32();
+        plain.restart(11);
+        assert_ne!(fork.next_u32(), first);
+    }
+
+    #[test]
+    fn follows_random_seed_without_a_globals_metatable() {
+        let draw = || {
+            let lua = Lua::new();
+            register(&lua).unwrap();
+            lua.load("RANDOM_SEED = 5\na = random:uniform()\nb = random:new():uniform()\nc = getmetatable(_G)").exec().unwrap();
+
+            let c: LuaValue = lua.globals().get("c").unwrap();
+            assert!(c.is_nil());
+
+            let a: f64 = lua.globals().get("a").unwrap();
+            let b: f64 = lua.globals().get("b").unwrap();
+            (a, b)
+        };
+
+        assert_eq!(draw(), draw());
+    }
+
+    #[test]
+    fn ranges() {
+        let mut random = Random::new(99);
+
+        for _ in 0..10000 {
+            let uniform = random.uniform();
+            assert!(uniform >= 0.0 && uniform < 1.0);
+
+            let bipolar = random.bipolar();
+            assert!(bipolar >= -1.0 && bipolar < 1.0);
+
+            let integer = random.integer(1, 6);
+            assert!(integer >= 1 && integer <= 6);
+        }
+    }
+
+    #[test]
+    fn gaussian_statistics() {
+        let mut random = Random::new(5);
+        let count = 100000;
+        let values: Vec<f64> = (0..count).map(|_| random.gaussian()).collect();
+
+        let mean = values.iter().sum::<f64>() / count as f64;
+        let variance = values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / count as f64;
+
+        assert!(mean.abs() < 0.02);
+        assert!((variance - 1.0).abs() < 0.02);
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::VecDeque;
+
+use mlua::prelude::*;
+use super::errors;
+
+const CHUNK_NAME: &str = "=console";
+const MAX_DEPTH: usize = 3;
+const MAX_ITEMS: usize = 32;
+const INDENT: &str = "  ";
+const NO_VALUE: &str = "(no value)";
+
+// A snippet typed into the console, for the module in the slot or the first one.
+#[derive(Clone)]
+pub struct ReplCommand {
+    pub slot_id: Option<u64>,
+    pub code: String
+}
+
+pub struct ReplResult {
+    pub code: String,
+    pub output: Result<String, String>
+}
+
+// Shared by the interface and the audio thread, which only ever try_lock's it between blocks.
+pub struct Repl {
+    commands: VecDeque<ReplCommand>,
+    results: VecDeque<ReplResult>
+}
+
+impl Repl {
+    pub fn new() -> Repl {
+        Self {
+            commands: VecDeque::new(),
+            results: VecDeque::new()
+        }
+    }
+
+    pub fn send(&mut self, command: ReplCommand) {
+        self.commands.push_back(command);
+    }
+
+    pub fn take_command(&mut self) -> Option<ReplCommand> {
+        return self.commands.pop_front();
+    }
+
+    pub fn add_result(&mut self, result: ReplResult) {
+        self.results.push_back(result);
+    }
+
+    pub fn take_results(&mut self) -> Vec<ReplResult> {
+        return self.results.drain(..).collect();
+    }
+}
+
+// Tries the snippet as an expression first, so "Filters[1].cutoff" shows its value, then as statements.
+pub fn evaluate(lua: &Lua, code: &str) -> Result<String, String> {
+    let function = match lua.load(format!("return {}", code)).set_name(CHUNK_NAME).into_function() {
+        Ok(f) => f,
+        Err(_) => lua.load(code).set_name(CHUNK_NAME).into_function().map_err(|e| errors::describe(&e))?
+    };
+
+    let values = function.call::<LuaMultiValue>(()).map_err(|e| errors::describe(&e))?;
+    if values.is_empty() {
+        return Ok(String::from(NO_VALUE));
+    }
+
+    let printed: Vec<String> = values.iter().map(|v| pretty(v, 0)).collect();
+    return Ok(printed.join(", "));
+}
+
+// Tables are spread over lines, the sequence first and then the other keys in order.
+pub fn pretty(value: &LuaValue, depth: usize) -> String {
+    return match value {
+        LuaValue::String(s) => format!("{:?}", s.to_string_lossy()),
+        LuaValue::Table(t) => pretty_table(t, depth),
+        _ => value.to_string().unwrap_or_else(|_| String::from(value.type_name()))
+    };
+}
+
+fn pretty_table(table: &LuaTable, depth: usize) -> String {
+    if depth >= MAX_DEPTH {
+        return String::from("{ ... }");
+    }
+
+    let length = table.raw_len();
+    let mut sequence = Vec::new();
+    let mut fields = Vec::new();
+
+    for pair in table.pairs::<LuaValue, LuaValue>() {
+        let (key, value) = match pair {
+            Ok(p) => p,
+            Err(_) => continue
+        };
+
+        match key {
+            LuaValue::Integer(i) if i >= 1 && i as usize <= length => sequence.push((i, value)),
+            LuaValue::String(s) => fields.push((s.to_string_lossy().to_string(), value)),
+            _ => fields.push((format!("[{}]", pretty(&key, MAX_DEPTH)), value))
+        }
+    }
+
+    if sequence.is_empty() && fields.is_empty() {
+        return String::from("{}");
+    }
+
+    sequence.sort_by_key(|(i, _)| *i);
+    fields.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let indent = INDENT.repeat(depth + 1);
+    let mut lines = Vec::new();
+
+    for (_, value) in &sequence {
+        lines.push(format!("{}{}", indent, pretty(value, depth + 1)));
+    }
+
+    for (key, value) in &fields {
+        lines.push(format!("{}{} = {}", indent, key, pretty(value, depth + 1)));
+    }
+
+    let total = lines.len();
+    if total > MAX_ITEMS {
+        lines.truncate(MAX_ITEMS);
+        lines.push(format!("{}... {} more", indent, total - MAX_ITEMS));
+    }
+
+    return format!("{{\n{},\n{}}}", lines.join(",\n"), INDENT.repeat(depth));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluates_expressions_and_statements() {
+        let lua = Lua::new();
+        lua.load("Gain = { value = 0.5 }").exec().unwrap();
+
+        assert_eq!(evaluate(&lua, "Gain.value + 0.25").unwrap(), "0.75");
+        assert_eq!(evaluate(&lua, "Gain.value = 0.25").unwrap(), NO_VALUE);
+        assert_eq!(evaluate(&lua, "Gain.value, \"a\"").unwrap(), "0.25, \"a\"");
+        assert!(evaluate(&lua, "Gain.missing.value").unwrap_err().starts_with("console:1:"));
+    }
+
+    #[test]
+    fn prints_tables_over_lines() {
+        let lua = Lua::new();
+        let value: LuaValue = lua.load("return { 1, 2, name = \"tilt\", nested = { deep = { deeper = { true } } } }").eval().unwrap();
+
+        assert_eq!(pretty(&value, 0), "{\n  1,\n  2,\n  name = \"tilt\",\n  nested = {\n    deep = {\n      deeper = { ... },\n    },\n  },\n}");
+    }
+}
@@ -0,0 +1,56 @@
+use std::collections::BTreeMap;
+use mlua::prelude::*;
+use super::library;
+
+const LUA_PACKAGE_KEY: &str = "package";
+const LUA_SEARCHERS_KEY: &str = "searchers";
+const LUA_PATH_KEY: &str = "path";
+const LUA_CPATH_KEY: &str = "cpath";
+
+pub const API: [(&str, &str); 1] = [
+    ("require(name)", "Loads name.lua from the workspace or user library, dots become folders.")
+];
+
+// Lets require() find the module's extra files instead of searching the disk.
+pub fn register(lua: &Lua, libraries: &BTreeMap<String, String>) -> LuaResult<()> {
+    let libraries = libraries.clone();
+
+    let searcher = lua.create_function(move |lua, name: String| {
+        for path in candidate_paths(&name) {
+            match libraries.get(&path) {
+                Some(source) => {
+                    let loader = lua.load(source.as_str())
+                        .set_name(library::user_chunk_name(&path))
+                        .into_function()?;
+
+                    return Ok((LuaValue::Function(loader), LuaValue::String(lua.create_string(&path)?)));
+                },
+                None => ()
+            }
+        }
+
+        let message = format!("no file '{}' in the workspace or user library", candidate_paths(&name).join("' or '"));
+        return Ok((LuaValue::String(lua.create_string(&message)?), LuaValue::Nil));
+    })?;
+
+    let package: LuaTable = lua.globals().get(LUA_PACKAGE_KEY)?;
+    let default_searchers: LuaTable = package.get(LUA_SEARCHERS_KEY)?;
+
+    // Keep package.preload, drop the searchers that reach for the file system and C libraries.
+    let searchers = lua.create_table()?;
+    searchers.raw_set(1, default_searchers.raw_get::<LuaValue>(1)?)?;
+    searchers.raw_set(2, searcher)?;
+
+    package.set(LUA_SEARCHERS_KEY, searchers)?;
+    package.set(LUA_PATH_KEY, "")?;
+    package.set(LUA_CPATH_KEY, "")?;
+
+    Ok(())
+}
+
+// require("dsp.filters") looks for dsp/filters.lua, then dsp/filters/init.lua.
+fn candidate_paths(name: &str) -> [String; 2] {
+    let base = name.replace('.', "/");
+
+    return [format!("{}.lua", base), format!("{}/init.lua", base)];
+}
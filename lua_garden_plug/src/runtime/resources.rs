@@ -0,0 +1,276 @@
+use std::{ fs::{ self, File }, path::{ Component, Path, PathBuf }, sync::{ atomic::{ AtomicBool, Ordering }, Arc } };
+use mlua::prelude::*;
+use symphonia::core::{ audio::SampleBuffer, codecs::{ DecoderOptions, CODEC_TYPE_NULL }, errors::Error as SymphoniaError, formats::FormatOptions, io::MediaSourceStream, meta::MetadataOptions, probe::Hint };
+
+pub const LUA_RESOURCES_KEY: &str = "resources";
+const LUA_LOAD_KEY: &str = "load";
+const LUA_LOAD_WAV_KEY: &str = "load_wav";
+const LUA_LOAD_TEXT_KEY: &str = "load_text";
+const LUA_SAMPLE_RATE_KEY: &str = "SAMPLE_RATE";
+
+pub struct AudioResource {
+    pub name: String,
+    pub sample_rate: f32,
+    pub samples: Vec<Vec<f32>>
+}
+
+impl AudioResource {
+    pub fn load_from_path(path: &Path) -> Result<AudioResource, String> {
+        let name = match path.file_name() {
+            Some(n) => n.to_string_lossy().to_string(),
+            None => path.to_string_lossy().to_string()
+        };
+
+        let file = match File::open(path) {
+            Ok(f) => f,
+            Err(e) => return Err(format!("Couldn't open \"{}\": {}", name, e))
+        };
+
+        let mut hint = Hint::new();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(extension) => { hint.with_extension(extension); },
+            None => ()
+        }
+
+        let stream = MediaSourceStream::new(Box::new(file), Default::default());
+        let probed = match symphonia::default::get_probe().format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default()) {
+            Ok(p) => p,
+            Err(e) => return Err(format!("Unsupported audio file \"{}\": {}", name, e))
+        };
+
+        let mut format = probed.format;
+        let track = match format.tracks().iter().find(|t| t.codec_params.codec != CODEC_TYPE_NULL) {
+            Some(t) => t,
+            None => return Err(format!("No audio track in \"{}\".", name))
+        };
+
+        let track_id = track.id;
+        let sample_rate = match track.codec_params.sample_rate {
+            Some(rate) => rate as f32,
+            None => return Err(format!("Unknown sample rate in \"{}\".", name))
+        };
+
+        let mut decoder = match symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default()) {
+            Ok(d) => d,
+            Err(e) => return Err(format!("Unsupported codec in \"{}\": {}", name, e))
+        };
+
+        let mut samples: Vec<Vec<f32>> = Vec::new();
+        let mut sample_buffer: Option<SampleBuffer<f32>> = None;
+
+        loop {
+            let packet = match format.next_packet() {
+                Ok(p) => p,
+                Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(format!("Couldn't read \"{}\": {}", name, e))
+            };
+
+            if packet.track_id() != track_id { continue; }
+
+            let decoded = match decoder.decode(&packet) {
+                Ok(d) => d,
+                Err(SymphoniaError::DecodeError(_)) => continue, // Skip corrupt packets.
+                Err(e) => return Err(format!("Couldn't decode \"{}\": {}", name, e))
+            };
+
+            let spec = *decoded.spec();
+            let channels = spec.channels.count();
+            let buffer = sample_buffer.get_or_insert_with(|| SampleBuffer::<f32>::new(decoded.capacity() as u64, spec));
+            buffer.copy_interleaved_ref(decoded);
+
+            while samples.len() < channels {
+                samples.push(Vec::new());
+            }
+
+            for frame in buffer.samples().chunks(channels) {
+                for (channel, sample) in frame.iter().enumerate() {
+                    samples[channel].push(*sample);
+                }
+            }
+        }
+
+        if samples.is_empty() {
+            return Err(format!("\"{}\" contains no samples.", name));
+        }
+
+        return Ok(AudioResource {
+            name: name,
+            sample_rate: sample_rate,
+            samples: samples
+        });
+    }
+
+    pub fn length(&self) -> usize {
+        return match self.samples.first() {
+            Some(s) => s.len(),
+            None => 0
+        };
+    }
+
+    pub fn get(&self, channel: usize, index: isize) -> f32 {
+        if index < 0 { return 0.0; }
+
+        return match self.samples.get(channel) {
+            Some(s) => *s.get(index as usize).unwrap_or(&0.0),
+            None => 0.0
+        };
+    }
+
+    pub fn read(&self, channel: usize, position: f32) -> f32 {
+        let index = f32::floor(position);
+        let t = position - index;
+        let index = index as isize;
+
+        return self.get(channel, index) + (self.get(channel, index + 1) - self.get(channel, index)) * t;
+    }
+
+    // Cubic hermite interpolation, good enough for offline conversion of samples and impulse responses.
+    pub fn resample(&mut self, target_sample_rate: f32) {
+        if target_sample_rate <= 0.0 || target_sample_rate == self.sample_rate { return; }
+
+        let ratio = self.sample_rate / target_sample_rate;
+        let length = f32::ceil(self.length() as f32 / ratio) as usize;
+
+        for channel in 0..self.samples.len() {
+            let mut resampled = Vec::with_capacity(length);
+
+            for i in 0..length {
+                let position = i as f32 * ratio;
+                let index = f32::floor(position) as isize;
+                let t = position - index as f32;
+
+                let y0 = self.get(channel, index - 1);
+                let y1 = self.get(channel, index);
+                let y2 = self.get(channel, index + 1);
+                let y3 = self.get(channel, index + 2);
+
+                let c1 = 0.5 * (y2 - y0);
+                let c2 = y0 - 2.5 * y1 + 2.0 * y2 - 0.5 * y3;
+                let c3 = 0.5 * (y3 - y0) + 1.5 * (y1 - y2);
+
+                resampled.push(((c3 * t + c2) * t + c1) * t + y1);
+            }
+
+            self.samples[channel] = resampled;
+        }
+
+        self.sample_rate = target_sample_rate;
+    }
+}
+
+impl LuaUserData for AudioResource {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("name", |_, this| Ok(this.name.clone()));
+        fields.add_field_method_get("channels", |_, this| Ok(this.samples.len()));
+        fields.add_field_method_get("length", |_, this| Ok(this.length()));
+        fields.add_field_method_get("sample_rate", |_, this| Ok(this.sample_rate));
+        fields.add_field_method_get("duration", |_, this| Ok(this.length() as f32 / this.sample_rate));
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        // Lua indexes start at 1
+        methods.add_method("get", |_, this, (channel, index): (usize, isize)| {
+            Ok(this.get(channel.saturating_sub(1), index - 1))
+        });
+
+        methods.add_method("read", |_, this, (channel, position): (usize, f32)| {
+            Ok(this.read(channel.saturating_sub(1), position - 1.0))
+        });
+    }
+}
+
+pub const API: [(&str, &str); 6] = [
+    ("resources", "Loads files relative to the workspace, only from init.lua."),
+    ("resources.load(path, resample?)", "Loads a wav, flac or aiff file. Pass true to resample to SAMPLE_RATE."),
+    ("resources.load_wav(path, resample?)", "Same as resources.load."),
+    ("resources.load_text(path)", "Reads a text file."),
+    ("Resource:get(channel, index)", "The sample at index, 0 outside the resource."),
+    ("Resource:read(channel, position)", "Linearly interpolated sample at a fractional position.")
+];
+
+pub fn register(lua: &Lua, workspace_path: Option<String>, loading_allowed: Arc<AtomicBool>) -> LuaResult<()> {
+    let load_workspace_path = workspace_path.clone();
+    let load_loading_allowed = loading_allowed.clone();
+    let load = lua.create_function(move |lua, (path, resample): (String, Option<bool>)| {
+        let full_path = resolve_path(&load_workspace_path, &load_loading_allowed, &path)?;
+
+        let mut resource = match AudioResource::load_from_path(&full_path) {
+            Ok(r) => r,
+            Err(e) => return Err(LuaError::runtime(e))
+        };
+
+        if resample.unwrap_or(false) {
+            let sample_rate: f32 = lua.globals().get(LUA_SAMPLE_RATE_KEY)?;
+            resource.resample(sample_rate);
+        }
+
+        return lua.create_userdata(resource);
+    })?;
+
+    let load_text = lua.create_function(move |_, path: String| {
+        let full_path = resolve_path(&workspace_path, &loading_allowed, &path)?;
+
+        return match fs::read_to_string(&full_path) {
+            Ok(text) => Ok(text),
+            Err(e) => Err(LuaError::runtime(format!("Couldn't read \"{}\": {}", path, e)))
+        };
+    })?;
+
+    let resources = lua.create_table()?;
+    resources.set(LUA_LOAD_KEY, &load)?;
+    resources.set(LUA_LOAD_WAV_KEY, &load)?;
+    resources.set(LUA_LOAD_TEXT_KEY, load_text)?;
+
+    return lua.globals().set(LUA_RESOURCES_KEY, resources);
+}
+
+fn resolve_path(workspace_path: &Option<String>, loading_allowed: &AtomicBool, path: &str) -> LuaResult<PathBuf> {
+    if !loading_allowed.load(Ordering::Relaxed) {
+        return Err(LuaError::runtime(format!("Couldn't load \"{}\": resources can only be loaded from init.lua.", path)));
+    }
+
+    return match workspace_path {
+        Some(w) => workspace_file(w, path).map_err(|e| LuaError::runtime(format!("Couldn't load \"{}\": {}", path, e))),
+        None => Err(LuaError::runtime(format!("Couldn't load \"{}\": draft modules have no workspace folder to load from. Create a workspace to use resources.", path)))
+    };
+}
+
+// Modules only get to read from their own folder, not "../../.." or "/etc".
+fn workspace_file(workspace_path: &str, path: &str) -> Result<PathBuf, String> {
+    let relative = Path::new(path);
+    let inside = relative.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if !inside {
+        return Err(String::from("resources must be loaded by a path inside the workspace folder."));
+    }
+
+    // Links can still point outside, so check where the path really ends up.
+    let workspace = fs::canonicalize(workspace_path).map_err(|e| e.to_string())?;
+    let full_path = fs::canonicalize(workspace.join(relative)).map_err(|e| e.to_string())?;
+    if !full_path.starts_with(&workspace) {
+        return Err(String::from("resources must be loaded by a path inside the workspace folder."));
+    }
+
+    return Ok(full_path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_reads_inside_the_workspace() {
+        let root = std::env::temp_dir().join(format!("lua_garden_resources_{}", std::process::id()));
+        let workspace = root.join("workspace");
+        fs::create_dir_all(workspace.join("samples")).unwrap();
+        fs::write(workspace.join("samples/kick.wav"), [0]).unwrap();
+        fs::write(root.join("secret.txt"), "secret").unwrap();
+
+        let workspace_path = workspace.to_str().unwrap();
+        assert_eq!(workspace_file(workspace_path, "samples/kick.wav").unwrap(), fs::canonicalize(workspace.join("samples/kick.wav")).unwrap());
+        assert!(workspace_file(workspace_path, "../secret.txt").is_err());
+        assert!(workspace_file(workspace_path, "samples/../../secret.txt").is_err());
+        assert!(workspace_file(workspace_path, root.join("secret.txt").to_str().unwrap()).is_err());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
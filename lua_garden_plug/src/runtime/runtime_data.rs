@@ -2,9 +2,9 @@ use std::collections::BTreeMap;
 
 use mlua::Table;
 
-use crate::interface::interface_data::InterfaceData;
+use crate::interface::interface_data::{ self, InterfaceData };
 
-use super::{parameter::Parameter, Runtime};
+use super::{errors::ScriptError, parameter::Parameter, profiler::ProfileReport, rack::SlotStatus, Runtime};
 
 #[derive(Clone, PartialEq)]
 pub enum RuntimeState {
@@ -17,19 +17,34 @@ pub enum RuntimeState {
 #[derive(Clone)]
 pub struct RuntimeData {
     pub state : RuntimeState,
+    // The rack to swap in while refreshing, the loader builds it.
+    pub load_id: u64,
 
     pub sample_rate: f32,
     pub buffer_size: usize,
     pub channels: usize,
     pub run_ms: f32,
+    pub latency_samples: u32,
     pub input_noise: bool,
     pub clip: bool,
+    pub crossfade_ms: f32,
+    pub safety_stop: bool,
+    // The safety stage muted the output, until the next reset.
+    pub muted: bool,
+    pub profiling: bool,
+    pub sampling: bool,
+    pub profile: ProfileReport,
 
     pub module_name: String,
     pub module_author: String,
     pub module_description: String,
+    pub last_error: Option<ScriptError>,
 
-    pub parameters: BTreeMap<String, Parameter>,
+    // Per rack slot, in rack order.
+    pub slots: Vec<SlotStatus>,
+    pub parameters: Vec<BTreeMap<String, Parameter>>,
+    // (slot id, bypassed) as the interface wants it.
+    pub rack_order: Vec<(u64, bool)>,
 
     pub change: u32,
     last_interface_change: u32
@@ -39,19 +54,30 @@ impl RuntimeData {
     pub fn new() -> RuntimeData {
         Self {
             state: RuntimeState::Offline,
+            load_id: 0,
 
             sample_rate: 0.0,
             buffer_size: 0,
             channels: 0,
             run_ms: 0.0,
+            latency_samples: 0,
             input_noise: false,
             clip: true,
+            crossfade_ms: interface_data::DEFAULT_CROSSFADE_MS,
+            safety_stop: false,
+            muted: false,
+            profiling: false,
+            sampling: false,
+            profile: ProfileReport::new(),
 
             module_name: String::new(),
             module_author: String::new(),
             module_description: String::new(),
+            last_error: None,
             
-            parameters: BTreeMap::new(),
+            slots: Vec::new(),
+            parameters: Vec::new(),
+            rack_order: Vec::new(),
             
             change: 0,
             last_interface_change: 0
@@ -62,8 +88,14 @@ impl RuntimeData {
         if self.last_interface_change == interface_data.change { return; }
 
         self.state = interface_data.runtime_target_state.clone();
+        self.load_id = interface_data.runtime_load_id;
         self.clip = interface_data.runtime_clip;
         self.input_noise = interface_data.runtime_input_noise;
+        self.crossfade_ms = interface_data.runtime_crossfade_ms;
+        self.safety_stop = interface_data.runtime_safety_stop;
+        self.profiling = interface_data.runtime_profiling;
+        self.sampling = interface_data.runtime_sampling;
+        self.rack_order = interface_data.rack_order();
     }
 
     pub fn update_from_runtime(&mut self, runtime: &mut Runtime, interface_data: &InterfaceData) {
@@ -74,26 +106,23 @@ impl RuntimeData {
         self.module_description = runtime.description.clone();
         
         // TODO only copy when refresh from interface is required.
-        match &mut runtime.module {
-            Some(m) => {
-                match m.get_parameters() {
-                    Ok(p) => {
-                        self.update_parameters(interface_data, p);
-                    },
-                    Err(e) => {
-                        println!("Failed to copy parameters from lua {}", e);
-                    }
+        self.parameters.resize_with(runtime.modules.len(), BTreeMap::new);
+
+        for (slot, m) in runtime.modules.iter_mut().enumerate() {
+            match m.module.get_parameters() {
+                Ok(p) => {
+                    self.update_parameters(slot, interface_data, p);
+                },
+                Err(e) => {
+                    println!("Failed to copy parameters from lua {}", e);
                 }
+            }
 
-                match m.update_parameter_value_updates(&mut self.parameters) {
-                    Ok(()) => (),
-                    Err(e) => {
-                        println!("Failed to update lua parameter value updates: {}.", e);
-                    }
+            match m.module.update_parameter_value_updates(&mut self.parameters[slot]) {
+                Ok(()) => (),
+                Err(e) => {
+                    println!("Failed to update lua parameter value updates: {}.", e);
                 }
-            },
-            None => {
-                self.parameters.clear();
             }
         }
 
@@ -110,23 +139,24 @@ impl RuntimeData {
         self.change = self.change + 1;
     }
 
-    fn update_parameters(&mut self, interface_data: &InterfaceData, lua_parameters: Table) {
-        self.parameters.clear();
+    fn update_parameters(&mut self, slot: usize, interface_data: &InterfaceData, lua_parameters: Table) {
+        let parameters = &mut self.parameters[slot];
+        let interface_parameters = interface_data.parameters.get(slot);
+        parameters.clear();
 
         for lua_parameter in lua_parameters.pairs::<String, Table>() {
             match lua_parameter {
                 Ok(lua_parameter_lua) => {
                     match Parameter::new_from_lua(&lua_parameter_lua.1) {
                         Ok(lua_p) => {
-                            let parameter = self.parameters.entry(lua_parameter_lua.0).or_insert(lua_p);
-
-                            if interface_data.parameters.contains_key(&parameter.name) {
-                                let interface_parameter = interface_data.parameters.get(&parameter.name).expect("No parameter exists at key.");
+                            let parameter = parameters.entry(lua_parameter_lua.0).or_insert(lua_p);
 
-                                if interface_parameter.changed {
+                            match interface_parameters.and_then(|p| p.get(&parameter.name)) {
+                                Some(interface_parameter) if interface_parameter.changed => {
                                     parameter.update_from_parameter(interface_parameter);
                                     parameter.set_changed(interface_parameter.changed);
-                                }
+                                },
+                                _ => ()
                             }
                         },
                         Err(e) => {
@@ -0,0 +1,201 @@
+const DC_WINDOW_MS: f32 = 100.0;
+const DC_THRESHOLD: f32 = 0.25;
+const DC_HOLD_MS: f32 = 500.0;
+// +30dB, far past anything a module means to send.
+const RUNAWAY_LEVEL: f32 = 32.0;
+const MUTE_FADE_MS: f32 = 5.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SafetyFault {
+    NotANumber,
+    Infinite,
+    DC(f32),
+    Runaway(f32)
+}
+
+// What tripped the safety, and where.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SafetyIssue {
+    pub fault: SafetyFault,
+    pub channel: usize
+}
+
+impl SafetyIssue {
+    pub fn describe(&self) -> String {
+        let channel = self.channel + 1;
+
+        return match self.fault {
+            SafetyFault::NotANumber => format!("NaN in channel {}", channel),
+            SafetyFault::Infinite => format!("infinity in channel {}", channel),
+            SafetyFault::DC(offset) => format!("sustained DC offset of {:.2} in channel {}", offset, channel),
+            SafetyFault::Runaway(level) => format!("runaway level of {:.1}dB in channel {}", 20.0 * level.log10(), channel)
+        };
+    }
+}
+
+// Sits after the modules, catches what clipping can't and mutes the output until the next reset.
+pub struct OutputSafety {
+    dc: Vec<f32>,
+    dc_samples: Vec<usize>,
+    gain: f32,
+    muted: bool
+}
+
+impl OutputSafety {
+    pub fn new() -> OutputSafety {
+        Self {
+            dc: Vec::new(),
+            dc_samples: Vec::new(),
+            gain: 1.0,
+            muted: false
+        }
+    }
+
+    // Sizes the DC tracking up front, so detecting doesn't allocate on the audio thread.
+    pub fn prepare(&mut self, channels: usize) {
+        self.dc = vec![0.0; channels];
+        self.dc_samples = vec![0; channels];
+    }
+
+    pub fn reset(&mut self) {
+        self.dc.iter_mut().for_each(|dc| *dc = 0.0);
+        self.dc_samples.iter_mut().for_each(|samples| *samples = 0);
+        self.gain = 1.0;
+        self.muted = false;
+    }
+
+    pub fn is_muted(&self) -> bool {
+        return self.muted;
+    }
+
+    // Returns the issue only for the block that trips the safety, muted blocks stay quiet.
+    pub fn process(&mut self, channels: &mut [&mut [f32]], sample_rate: f32) -> Option<SafetyIssue> {
+        let issue = match self.muted {
+            true => None,
+            false => self.detect(channels, sample_rate)
+        };
+
+        if issue.is_some() {
+            self.muted = true;
+        }
+
+        if !self.muted { return None; }
+
+        for samples in channels.iter_mut() {
+            for sample in samples.iter_mut() {
+                if !sample.is_finite() {
+                    *sample = 0.0;
+                }
+            }
+        }
+
+        // A short fade where possible, a NaN can only be cut.
+        let step = 1.0 / (MUTE_FADE_MS / 1000.0 * sample_rate).max(1.0);
+        let samples = channels.first().map_or(0, |c| c.len());
+
+        for index in 0..samples {
+            let gain = (self.gain - step * (index + 1) as f32).max(0.0);
+            for channel in channels.iter_mut() {
+                channel[index] *= gain;
+            }
+        }
+
+        self.gain = (self.gain - step * samples as f32).max(0.0);
+
+        return issue;
+    }
+
+    fn detect(&mut self, channels: &[&mut [f32]], sample_rate: f32) -> Option<SafetyIssue> {
+        let coefficient = 1.0 - f32::exp(-1000.0 / (DC_WINDOW_MS * sample_rate));
+        let dc_hold_samples = (DC_HOLD_MS / 1000.0 * sample_rate) as usize;
+
+        for (channel, samples) in channels.iter().enumerate() {
+            for sample in samples.iter() {
+                let fault = match sample {
+                    s if s.is_nan() => Some(SafetyFault::NotANumber),
+                    s if s.is_infinite() => Some(SafetyFault::Infinite),
+                    s if s.abs() > RUNAWAY_LEVEL => Some(SafetyFault::Runaway(s.abs())),
+                    _ => None
+                };
+
+                match fault {
+                    Some(fault) => return Some(SafetyIssue { fault: fault, channel: channel }),
+                    None => ()
+                }
+            }
+
+            // Only the channels prepare made room for are watched for DC.
+            let (dc, dc_samples) = match (self.dc.get_mut(channel), self.dc_samples.get_mut(channel)) {
+                (Some(dc), Some(dc_samples)) => (dc, dc_samples),
+                _ => continue
+            };
+
+            for sample in samples.iter() {
+                *dc += coefficient * (sample - *dc);
+            }
+
+            match dc.abs() > DC_THRESHOLD {
+                true => *dc_samples += samples.len(),
+                false => *dc_samples = 0
+            }
+
+            if *dc_samples > dc_hold_samples {
+                return Some(SafetyIssue { fault: SafetyFault::DC(*dc), channel: channel });
+            }
+        }
+
+        return None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mutes_on_nan() {
+        let mut safety = OutputSafety::new();
+        let mut left = [0.5; 4];
+        let mut right = [0.5, f32::NAN, 0.5, 0.5];
+
+        let issue = safety.process(&mut [&mut left[..], &mut right[..]], 1000.0);
+        assert_eq!(issue, Some(SafetyIssue { fault: SafetyFault::NotANumber, channel: 1 }));
+        assert!(right.iter().all(|s| s.is_finite()));
+        assert!(left[3] < left[0] && left[0] < 0.5);
+
+        let mut left = [f32::NAN; 4];
+        assert_eq!(safety.process(&mut [&mut left[..]], 1000.0), None);
+        assert_eq!(left, [0.0; 4]);
+
+        safety.reset();
+        assert!(!safety.is_muted());
+    }
+
+    #[test]
+    fn catches_sustained_dc_only() {
+        let mut safety = OutputSafety::new();
+        safety.prepare(1);
+        let mut alternating = [0.9, -0.9, 0.9, -0.9];
+
+        for _ in 0..1000 {
+            assert_eq!(safety.process(&mut [&mut alternating[..]], 1000.0), None);
+        }
+
+        let mut issue = None;
+        for _ in 0..1000 {
+            let mut offset = [0.9; 4];
+            issue = issue.or(safety.process(&mut [&mut offset[..]], 1000.0));
+        }
+
+        assert!(matches!(issue, Some(SafetyIssue { fault: SafetyFault::DC(_), channel: 0 })));
+    }
+
+    #[test]
+    fn passes_clean_signal() {
+        let mut safety = OutputSafety::new();
+        let mut samples = [0.5, -1.5, 2.0];
+
+        assert_eq!(safety.process(&mut [&mut samples[..]], 48000.0), None);
+        assert_eq!(samples, [0.5, -1.5, 2.0]);
+    }
+}
@@ -0,0 +1,302 @@
+use std::sync::Arc;
+use mlua::prelude::*;
+use realfft::{ num_complex::Complex, ComplexToReal, RealFftPlanner, RealToComplex };
+
+pub const LUA_SPECTRAL_CALLBACK_KEY: &str = "spectral";
+const LUA_FFT_SIZE_KEY: &str = "fft_size";
+const LUA_HOP_SIZE_KEY: &str = "hop_size";
+const LUA_WINDOW_KEY: &str = "window";
+const LUA_CHANNEL_KEY: &str = "channel";
+const LUA_BINS_KEY: &str = "bins";
+const LUA_MAGNITUDE_KEY: &str = "magnitude";
+const LUA_PHASE_KEY: &str = "phase";
+
+const MIN_FFT_SIZE: usize = 16;
+pub const MAX_FFT_SIZE: usize = 32768;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum WindowType {
+    Rectangular,
+    Hann,
+    Hamming,
+    Blackman
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct StftConfig {
+    pub fft_size: usize,
+    pub hop_size: usize,
+    pub window: WindowType
+}
+
+pub struct Stft {
+    pub config: StftConfig,
+
+    forward: Arc<dyn RealToComplex<f32>>,
+    inverse: Arc<dyn ComplexToReal<f32>>,
+    window: Vec<f32>,
+    analysis_scale: f32,
+    synthesis_scale: f32,
+
+    frame_input: Vec<f32>,
+    frame_output: Vec<f32>,
+    spectrum: Vec<Complex<f32>>,
+    forward_scratch: Vec<Complex<f32>>,
+    inverse_scratch: Vec<Complex<f32>>,
+    channels: Vec<StftChannel>,
+
+    lua_frame: LuaTable,
+    lua_magnitude: LuaTable,
+    lua_phase: LuaTable
+}
+
+struct StftChannel {
+    input: Vec<f32>,
+    output: Vec<f32>,
+    accumulator: Vec<f32>,
+    position: usize
+}
+
+impl WindowType {
+    pub fn from_name(name: &str) -> Option<WindowType> {
+        return match name {
+            "rectangular" | "rect" | "none" => Some(WindowType::Rectangular),
+            "hann" | "hanning" => Some(WindowType::Hann),
+            "hamming" => Some(WindowType::Hamming),
+            "blackman" => Some(WindowType::Blackman),
+            _ => None
+        };
+    }
+
+    fn value(&self, index: usize, size: usize) -> f32 {
+        let phase = std::f32::consts::TAU * index as f32 / size as f32;
+
+        return match self {
+            WindowType::Rectangular => 1.0,
+            WindowType::Hann => 0.5 - 0.5 * f32::cos(phase),
+            WindowType::Hamming => 0.54 - 0.46 * f32::cos(phase),
+            WindowType::Blackman => 0.42 - 0.5 * f32::cos(phase) + 0.08 * f32::cos(2.0 * phase)
+        };
+    }
+}
+
+impl StftConfig {
+    pub fn new_from_lua(lua_config: &LuaTable) -> LuaResult<StftConfig> {
+        let fft_size: usize = lua_config.get(LUA_FFT_SIZE_KEY)?;
+        let hop_size: usize = lua_config.get(LUA_HOP_SIZE_KEY)?;
+        let window_name: String = lua_config.get(LUA_WINDOW_KEY)?;
+
+        if !fft_size.is_power_of_two() || fft_size < MIN_FFT_SIZE || fft_size > MAX_FFT_SIZE {
+            return Err(LuaError::runtime(format!("STFT size must be a power of two between {min} and {max}, got {size}.",
+                min = MIN_FFT_SIZE,
+                max = MAX_FFT_SIZE,
+                size = fft_size)));
+        }
+
+        if hop_size == 0 || hop_size > fft_size {
+            return Err(LuaError::runtime(format!("STFT hop size must be between 1 and the FFT size ({size}), got {hop}.",
+                size = fft_size,
+                hop = hop_size)));
+        }
+
+        let window = match WindowType::from_name(&window_name) {
+            Some(w) => w,
+            None => return Err(LuaError::runtime(format!("Unknown STFT window \"{}\". Use \"hann\", \"hamming\", \"blackman\" or \"rectangular\".", window_name)))
+        };
+
+        return Ok(StftConfig {
+            fft_size: fft_size,
+            hop_size: hop_size,
+            window: window
+        });
+    }
+}
+
+impl Stft {
+    pub fn new(lua: &Lua, config: StftConfig) -> LuaResult<Stft> {
+        let mut planner = RealFftPlanner::<f32>::new();
+        let forward = planner.plan_fft_forward(config.fft_size);
+        let inverse = planner.plan_fft_inverse(config.fft_size);
+
+        let window: Vec<f32> = (0..config.fft_size).map(|i| config.window.value(i, config.fft_size)).collect();
+        let window_sum: f32 = window.iter().sum();
+        let window_square_sum: f32 = window.iter().map(|w| w * w).sum();
+
+        let bins = config.fft_size / 2 + 1;
+        let lua_frame = lua.create_table()?;
+        let lua_magnitude = lua.create_table_with_capacity(bins, 0)?;
+        let lua_phase = lua.create_table_with_capacity(bins, 0)?;
+
+        for bin in 1..=bins {
+            lua_magnitude.raw_set(bin, 0.0)?;
+            lua_phase.raw_set(bin, 0.0)?;
+        }
+
+        lua_frame.raw_set(LUA_BINS_KEY, bins)?;
+        lua_frame.raw_set(LUA_FFT_SIZE_KEY, config.fft_size)?;
+        lua_frame.raw_set(LUA_HOP_SIZE_KEY, config.hop_size)?;
+        lua_frame.raw_set(LUA_MAGNITUDE_KEY, &lua_magnitude)?;
+        lua_frame.raw_set(LUA_PHASE_KEY, &lua_phase)?;
+
+        let stft = Self {
+            config: config,
+
+            frame_input: forward.make_input_vec(),
+            frame_output: inverse.make_output_vec(),
+            spectrum: forward.make_output_vec(),
+            forward_scratch: forward.make_scratch_vec(),
+            inverse_scratch: inverse.make_scratch_vec(),
+            channels: Vec::new(),
+
+            forward: forward,
+            inverse: inverse,
+            window: window,
+            // Scale magnitudes so a full scale sine reads as 1.0 in its bin.
+            analysis_scale: 2.0 / window_sum,
+            // Undo the unnormalized inverse FFT and the overlapping synthesis windows.
+            synthesis_scale: config.hop_size as f32 / (config.fft_size as f32 * window_square_sum),
+
+            lua_frame: lua_frame,
+            lua_magnitude: lua_magnitude,
+            lua_phase: lua_phase
+        };
+
+        return Ok(stft);
+    }
+
+    pub fn latency_samples(&self) -> u32 {
+        return self.config.fft_size as u32;
+    }
+
+    pub fn reset(&mut self) {
+        self.channels.clear();
+    }
+
+    pub fn process(&mut self, callback: &LuaFunction, channel: usize, samples: &mut [f32]) -> LuaResult<()> {
+        while self.channels.len() <= channel {
+            self.channels.push(StftChannel::new(&self.config));
+        }
+
+        let output_offset = self.config.fft_size - self.config.hop_size;
+
+        for sample in samples.iter_mut() {
+            let frame_ready = {
+                let state = &mut self.channels[channel];
+                state.input[state.position] = *sample;
+                *sample = state.output[state.position - output_offset];
+                state.position += 1;
+
+                if state.position >= self.config.fft_size {
+                    state.position = output_offset;
+                    true
+                } else {
+                    false
+                }
+            };
+
+            if frame_ready {
+                self.process_frame(callback, channel)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn process_frame(&mut self, callback: &LuaFunction, channel: usize) -> LuaResult<()> {
+        let fft_size = self.config.fft_size;
+        let hop_size = self.config.hop_size;
+
+        // Analysis
+        let state = &self.channels[channel];
+        for i in 0..fft_size {
+            self.frame_input[i] = state.input[i] * self.window[i];
+        }
+
+        self.forward.process_with_scratch(&mut self.frame_input, &mut self.spectrum, &mut self.forward_scratch)
+            .map_err(LuaError::external)?;
+
+        for (bin, value) in self.spectrum.iter().enumerate() {
+            self.lua_magnitude.raw_set(bin + 1, value.norm() * self.analysis_scale)?; // Lua indexes start at 1
+            self.lua_phase.raw_set(bin + 1, value.arg())?;
+        }
+
+        // Let the module do its thing
+        self.lua_frame.raw_set(LUA_CHANNEL_KEY, channel + 1)?; // Lua indexes start at 1
+        callback.call::<()>(&self.lua_frame)?;
+
+        // Synthesis
+        for bin in 0..self.spectrum.len() {
+            let magnitude: f32 = self.lua_magnitude.raw_get(bin + 1)?;
+            let phase: f32 = self.lua_phase.raw_get(bin + 1)?;
+            self.spectrum[bin] = Complex::from_polar(magnitude / self.analysis_scale, phase);
+        }
+
+        // DC and nyquist bins can't carry an imaginary part in a real signal.
+        let nyquist = self.spectrum.len() - 1;
+        self.spectrum[0].im = 0.0;
+        self.spectrum[nyquist].im = 0.0;
+
+        self.inverse.process_with_scratch(&mut self.spectrum, &mut self.frame_output, &mut self.inverse_scratch)
+            .map_err(LuaError::external)?;
+
+        // Overlap-add
+        let state = &mut self.channels[channel];
+        for i in 0..fft_size {
+            state.accumulator[i] += self.frame_output[i] * self.window[i] * self.synthesis_scale;
+        }
+
+        state.output.copy_from_slice(&state.accumulator[..hop_size]);
+        state.accumulator.copy_within(hop_size.., 0);
+        state.accumulator[fft_size - hop_size..].fill(0.0);
+        state.input.copy_within(hop_size.., 0);
+
+        Ok(())
+    }
+}
+
+impl StftChannel {
+    fn new(config: &StftConfig) -> StftChannel {
+        Self {
+            input: vec![0.0; config.fft_size],
+            output: vec![0.0; config.hop_size],
+            accumulator: vec![0.0; config.fft_size],
+            position: config.fft_size - config.hop_size
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A callback that leaves the bins alone gives back the input, delayed by the latency.
+    #[test]
+    fn round_trips_at_unity_gain() {
+        let lua = Lua::new();
+        let callback = lua.create_function(|_, _frame: LuaTable| Ok(())).unwrap();
+        let fft_size = 64;
+        let input: Vec<f32> = (0..fft_size * 8).map(|i| f32::sin(i as f32 * 0.3) * 0.5 + f32::sin(i as f32 * 1.7) * 0.25).collect();
+
+        // Overlaps at which each window adds up to a constant.
+        let configs = [(WindowType::Rectangular, 32), (WindowType::Hann, 16), (WindowType::Hamming, 16), (WindowType::Blackman, 8)];
+
+        for (window, hop_size) in configs {
+            let mut stft = Stft::new(&lua, StftConfig { fft_size: fft_size, hop_size: hop_size, window: window }).unwrap();
+            let latency = stft.latency_samples() as usize;
+            let mut output = input.clone();
+
+            // Blocks that don't line up with the hops, like a host's.
+            for block in output.chunks_mut(37) {
+                stft.process(&callback, 0, block).unwrap();
+            }
+
+            for (index, sample) in output.iter().enumerate() {
+                let expected = match index >= latency {
+                    true => input[index - latency],
+                    false => 0.0
+                };
+                assert!((sample - expected).abs() < 1e-4, "hop {} sample {}: {} instead of {}", hop_size, index, sample, expected);
+            }
+        }
+    }
+}
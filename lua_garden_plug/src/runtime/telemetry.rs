@@ -0,0 +1,244 @@
+use std::{ collections::{ BTreeMap, VecDeque }, time::Instant };
+use mlem_console::queue::{ self, Consumer, Producer };
+
+pub const NAME_BYTES: usize = 32;
+pub const TEXT_BYTES: usize = 64;
+pub const HISTORY_LENGTH: usize = 120;
+// How often the runtime sends, graphs move at about this rate.
+pub const SEND_INTERVAL_MS: f32 = 25.0;
+const SENDER_SLOTS: usize = 1024;
+// Values a module stopped publishing go away after this long.
+const STALE_SECONDS: f32 = 2.0;
+
+// What runtime.meter and runtime.watch were given, small enough to send from the audio thread.
+#[derive(Clone, Copy)]
+pub enum TelemetryValue {
+    Meter { value: f32, min: f32, max: f32 },
+    Number(f64),
+    Text([u8; TEXT_BYTES], usize)
+}
+
+#[derive(Clone, Copy)]
+struct TelemetryRecord {
+    slot_id: u64,
+    name: [u8; NAME_BYTES],
+    name_len: usize,
+    value: TelemetryValue
+}
+
+pub struct TelemetrySender {
+    producer: Producer<TelemetryRecord>
+}
+
+// The interface side, collects the records into one watch per module and name.
+pub struct TelemetryReceiver {
+    consumers: Vec<Consumer<TelemetryRecord>>,
+    watches: BTreeMap<(u64, String), Watch>,
+    // Values that came in faster than the interface could keep up, since it started.
+    dropped: u64
+}
+
+pub enum Reading {
+    Meter { value: f32, min: f32, max: f32 },
+    Number(f64),
+    Text(String)
+}
+
+pub struct Watch {
+    pub slot_id: u64,
+    pub name: String,
+    pub reading: Reading,
+    // Meters and numbers, oldest first.
+    pub history: VecDeque<f32>,
+    updated: Instant
+}
+
+impl TelemetryValue {
+    // Cut at TEXT_BYTES.
+    pub fn text(text: &[u8]) -> TelemetryValue {
+        let mut bytes = [0; TEXT_BYTES];
+        let len = fill(&mut bytes, text);
+
+        return TelemetryValue::Text(bytes, len);
+    }
+}
+
+impl TelemetrySender {
+    // Names are cut at NAME_BYTES, a full queue drops the value until the next send and the receiver counts it.
+    pub fn send(&self, slot_id: u64, name: &[u8], value: TelemetryValue) {
+        let mut record = TelemetryRecord {
+            slot_id: slot_id,
+            name: [0; NAME_BYTES],
+            name_len: 0,
+            value: value
+        };
+        record.name_len = fill(&mut record.name, name);
+
+        let _ = self.producer.push(record);
+    }
+}
+
+impl TelemetryReceiver {
+    pub fn new() -> TelemetryReceiver {
+        Self {
+            consumers: Vec::new(),
+            watches: BTreeMap::new(),
+            dropped: 0
+        }
+    }
+
+    pub fn create_sender(&mut self) -> TelemetrySender {
+        let empty = TelemetryRecord {
+            slot_id: 0,
+            name: [0; NAME_BYTES],
+            name_len: 0,
+            value: TelemetryValue::Number(0.0)
+        };
+
+        let (producer, consumer) = queue::queue(SENDER_SLOTS, empty);
+        self.consumers.push(consumer);
+
+        return TelemetrySender { producer: producer };
+    }
+
+    // In slot and name order.
+    pub fn watches(&self) -> impl Iterator<Item = &Watch> {
+        return self.watches.values();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.watches.is_empty();
+    }
+
+    pub fn dropped(&self) -> u64 {
+        return self.dropped;
+    }
+
+    pub fn update(&mut self) {
+        let now = Instant::now();
+
+        for consumer in &mut self.consumers {
+            while let Some(record) = consumer.pop() {
+                let name = String::from_utf8_lossy(&record.name[..record.name_len]).to_string();
+                let watch = self.watches.entry((record.slot_id, name.clone())).or_insert_with(|| Watch {
+                    slot_id: record.slot_id,
+                    name: name,
+                    reading: Reading::Number(0.0),
+                    history: VecDeque::with_capacity(HISTORY_LENGTH),
+                    updated: now
+                });
+
+                watch.update(record.value, now);
+            }
+
+            self.dropped += consumer.take_dropped();
+        }
+
+        self.watches.retain(|_, watch| now.duration_since(watch.updated).as_secs_f32() < STALE_SECONDS);
+    }
+}
+
+impl Watch {
+    fn update(&mut self, value: TelemetryValue, now: Instant) {
+        self.updated = now;
+
+        let point = match value {
+            TelemetryValue::Meter { value, min, max } => {
+                self.reading = Reading::Meter { value: value, min: min, max: max };
+                Some(value)
+            },
+            TelemetryValue::Number(number) => {
+                self.reading = Reading::Number(number);
+                Some(number as f32)
+            },
+            TelemetryValue::Text(bytes, len) => {
+                self.reading = Reading::Text(String::from_utf8_lossy(&bytes[..len]).to_string());
+                None
+            }
+        };
+
+        match point {
+            Some(point) => {
+                if self.history.len() == HISTORY_LENGTH {
+                    self.history.pop_front();
+                }
+                self.history.push_back(point);
+            },
+            None => self.history.clear()
+        }
+    }
+
+    // Where the meter sits between its min and max.
+    pub fn fraction(&self) -> f32 {
+        return match self.reading {
+            Reading::Meter { value, min, max } if max != min => ((value - min) / (max - min)).clamp(0.0, 1.0),
+            _ => 0.0
+        };
+    }
+}
+
+fn fill(into: &mut [u8], from: &[u8]) -> usize {
+    let len = from.len().min(into.len());
+    into[..len].copy_from_slice(&from[..len]);
+
+    return len;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_one_watch_per_slot_and_name() {
+        let mut receiver = TelemetryReceiver::new();
+        let sender = receiver.create_sender();
+
+        sender.send(1, b"gain_reduction", TelemetryValue::Meter { value: -3.0, min: -12.0, max: 0.0 });
+        sender.send(1, b"gain_reduction", TelemetryValue::Meter { value: -6.0, min: -12.0, max: 0.0 });
+        sender.send(2, b"gain_reduction", TelemetryValue::Number(1.5));
+        sender.send(1, b"state", TelemetryValue::text(b"attack"));
+        receiver.update();
+
+        let watches: Vec<&Watch> = receiver.watches().collect();
+        assert_eq!(watches.len(), 3);
+        assert_eq!(watches[0].history, vec![-3.0, -6.0]);
+        assert_eq!(watches[0].fraction(), 0.5);
+        assert_eq!(watches[2].slot_id, 2);
+
+        match &watches[1].reading {
+            Reading::Text(text) => assert_eq!(text, "attack"),
+            _ => panic!("Expected text.")
+        }
+    }
+
+    #[test]
+    fn cuts_long_names_and_bounds_history() {
+        let mut receiver = TelemetryReceiver::new();
+        let sender = receiver.create_sender();
+        let name = [b'a'; NAME_BYTES * 2];
+
+        for value in 0..HISTORY_LENGTH + 10 {
+            sender.send(0, &name, TelemetryValue::Number(value as f64));
+            receiver.update();
+        }
+
+        let watch = receiver.watches().next().unwrap();
+        assert_eq!(watch.name.len(), NAME_BYTES);
+        assert_eq!(watch.history.len(), HISTORY_LENGTH);
+        assert_eq!(watch.history.front(), Some(&10.0));
+    }
+
+    #[test]
+    fn counts_what_a_full_queue_dropped() {
+        let mut receiver = TelemetryReceiver::new();
+        let sender = receiver.create_sender();
+
+        for _ in 0..SENDER_SLOTS + 5 {
+            sender.send(0, b"level", TelemetryValue::Number(1.0));
+        }
+        receiver.update();
+
+        assert_eq!(receiver.dropped(), 5);
+        assert_eq!(receiver.watches().next().unwrap().history.len(), HISTORY_LENGTH);
+    }
+}
@@ -0,0 +1,636 @@
+use mlua::prelude::*;
+
+pub const LUA_TUNING_KEY: &str = "TUNING";
+const DEFAULT_NOTES_PER_OCTAVE: usize = 12;
+const DEFAULT_REFERENCE_NOTE: i32 = 48; // A4, base_octave * notes_per_octave in pitch.lua.
+const DEFAULT_REFERENCE_FREQUENCY: f64 = 440.0;
+const DEFAULT_NOTE_NAMES: [&str; 12] = ["A", "A#", "B", "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#"];
+const CENTS_PER_OCTAVE: f64 = 1200.0;
+
+// A Scala scale. Holds the cents of every degree after the root, the last one being the period.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Scale {
+    pub description: String,
+    pub cents: Vec<f64>
+}
+
+// A Scala keyboard mapping. A size of 0 maps every key to the next scale degree.
+#[derive(Clone, PartialEq, Debug)]
+pub struct KeyboardMapping {
+    pub size: usize,
+    pub first_note: i32,
+    pub last_note: i32,
+    pub middle_note: i32,
+    pub reference_note: i32,
+    pub reference_frequency: f64,
+    pub octave_degree: usize,
+    pub map: Vec<Option<usize>>
+}
+
+pub struct Tuning {
+    pub scale: Scale,
+    pub mapping: KeyboardMapping,
+    pub note_names: Vec<String>,
+
+    scale_degrees: Vec<usize>
+}
+
+impl Scale {
+    pub fn equal_temperament(notes_per_octave: usize) -> Scale {
+        let notes = usize::max(notes_per_octave, 1);
+        let cents = (1..=notes).map(|d| CENTS_PER_OCTAVE * d as f64 / notes as f64).collect();
+
+        Self {
+            description: format!("{}-TET", notes),
+            cents: cents
+        }
+    }
+
+    pub fn from_scl(text: &str) -> Result<Scale, String> {
+        let mut lines = scala_lines(text);
+
+        let description = match lines.next() {
+            Some(l) => String::from(l.trim()),
+            None => return Err(String::from("Scale file is empty."))
+        };
+
+        let count = match lines.next().and_then(|l| l.split_whitespace().next()) {
+            Some(c) => match c.parse::<usize>() {
+                Ok(c) => c,
+                Err(_) => return Err(format!("Invalid note count \"{}\".", c))
+            },
+            None => return Err(String::from("Scale file has no note count."))
+        };
+
+        let mut cents = Vec::with_capacity(count);
+        for line in lines.take(count) {
+            let value = match line.split_whitespace().next() {
+                Some(v) => v,
+                None => return Err(String::from("Empty pitch line."))
+            };
+
+            cents.push(parse_scala_pitch(value)?);
+        }
+
+        if cents.len() != count {
+            return Err(format!("Expected {} pitches, found {}.", count, cents.len()));
+        }
+
+        if count == 0 {
+            return Err(String::from("A scale needs at least one pitch."));
+        }
+
+        return Ok(Scale {
+            description: description,
+            cents: cents
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        return self.cents.len();
+    }
+
+    pub fn period(&self) -> f64 {
+        return self.cents[self.cents.len() - 1];
+    }
+
+    // Cents above the root for any degree, wrapping around the period.
+    pub fn degree_cents(&self, degree: i64) -> f64 {
+        let size = self.len() as i64;
+        let periods = degree.div_euclid(size);
+        let degree_in_period = degree.rem_euclid(size) as usize;
+        let base = if degree_in_period == 0 { 0.0 } else { self.cents[degree_in_period - 1] };
+
+        return periods as f64 * self.period() + base;
+    }
+}
+
+impl KeyboardMapping {
+    pub fn linear(middle_note: i32, reference_frequency: f64) -> KeyboardMapping {
+        Self {
+            size: 0,
+            first_note: i32::MIN,
+            last_note: i32::MAX,
+            middle_note: middle_note,
+            reference_note: middle_note,
+            reference_frequency: reference_frequency,
+            octave_degree: 0,
+            map: Vec::new()
+        }
+    }
+
+    pub fn from_kbm(text: &str) -> Result<KeyboardMapping, String> {
+        let values: Vec<&str> = scala_lines(text)
+            .filter_map(|l| l.split_whitespace().next())
+            .collect();
+
+        if values.len() < 7 {
+            return Err(String::from("Keyboard mapping needs at least 7 values: size, first note, last note, middle note, reference note, reference frequency and octave degree."));
+        }
+
+        let size = parse_kbm_value::<usize>(values[0], "map size")?;
+        let first_note = parse_kbm_value::<i32>(values[1], "first note")?;
+        let last_note = parse_kbm_value::<i32>(values[2], "last note")?;
+        let middle_note = parse_kbm_value::<i32>(values[3], "middle note")?;
+        let reference_note = parse_kbm_value::<i32>(values[4], "reference note")?;
+        let reference_frequency = parse_kbm_value::<f64>(values[5], "reference frequency")?;
+        let octave_degree = parse_kbm_value::<usize>(values[6], "octave degree")?;
+
+        if reference_frequency <= 0.0 {
+            return Err(format!("Reference frequency must be positive, got {}.", reference_frequency));
+        }
+
+        // Keys without an entry are unmapped.
+        let mut map = vec![None; size];
+        for (key, value) in values.iter().skip(7).take(size).enumerate() {
+            if *value == "x" || *value == "X" { continue; }
+            map[key] = Some(parse_kbm_value::<usize>(value, "map entry")?);
+        }
+
+        return Ok(KeyboardMapping {
+            size: size,
+            first_note: first_note,
+            last_note: last_note,
+            middle_note: middle_note,
+            reference_note: reference_note,
+            reference_frequency: reference_frequency,
+            octave_degree: octave_degree,
+            map: map
+        });
+    }
+}
+
+impl Tuning {
+    pub fn new() -> Tuning {
+        Self {
+            scale: Scale::equal_temperament(DEFAULT_NOTES_PER_OCTAVE),
+            mapping: KeyboardMapping::linear(DEFAULT_REFERENCE_NOTE, DEFAULT_REFERENCE_FREQUENCY),
+            note_names: DEFAULT_NOTE_NAMES.iter().map(|n| String::from(*n)).collect(),
+
+            scale_degrees: Vec::new()
+        }
+    }
+
+    pub fn set_scale(&mut self, scale: Scale) {
+        if scale.len() != self.note_names.len() {
+            self.note_names = (0..scale.len()).map(|d| d.to_string()).collect();
+        }
+
+        self.scale = scale;
+        self.scale_degrees.clear();
+    }
+
+    pub fn set_mapping(&mut self, mapping: KeyboardMapping) {
+        self.mapping = mapping;
+    }
+
+    pub fn set_scale_degrees(&mut self, degrees: Vec<usize>) {
+        self.scale_degrees = degrees;
+    }
+
+    pub fn notes_per_octave(&self) -> usize {
+        return if self.mapping.size > 0 { self.mapping.size } else { self.scale.len() };
+    }
+
+    // Scale degree for a key, None when unmapped.
+    pub fn note_degree(&self, note: i32) -> Option<i64> {
+        if note < self.mapping.first_note || note > self.mapping.last_note { return None; }
+
+        let offset = note as i64 - self.mapping.middle_note as i64;
+        if self.mapping.size == 0 {
+            return Some(offset);
+        }
+
+        let size = self.mapping.size as i64;
+        let key = offset.rem_euclid(size) as usize;
+        let octaves = offset.div_euclid(size);
+        let octave_degree = if self.mapping.octave_degree == 0 { self.scale.len() } else { self.mapping.octave_degree };
+
+        return match self.mapping.map[key] {
+            Some(degree) => Some(degree as i64 + octaves * octave_degree as i64),
+            None => None
+        };
+    }
+
+    pub fn note_cents(&self, note: i32) -> Option<f64> {
+        if note < self.mapping.first_note || note > self.mapping.last_note { return None; }
+        if self.mapping.size == 0 {
+            return Some(self.scale.degree_cents(note as i64 - self.mapping.middle_note as i64));
+        }
+
+        // Mapped degrees past the formal octave still wrap around the scale's own period.
+        let offset = note as i64 - self.mapping.middle_note as i64;
+        let size = self.mapping.size as i64;
+        let key = offset.rem_euclid(size) as usize;
+        let octaves = offset.div_euclid(size);
+        let octave_degree = if self.mapping.octave_degree == 0 { self.scale.len() } else { self.mapping.octave_degree };
+
+        return match self.mapping.map[key] {
+            Some(degree) => Some(self.scale.degree_cents(degree as i64) + octaves as f64 * self.scale.degree_cents(octave_degree as i64)),
+            None => None
+        };
+    }
+
+    // Fractional notes glide between their neighbours.
+    pub fn note_hz(&self, note: f64) -> Option<f64> {
+        let reference_cents = self.note_cents(self.mapping.reference_note)?;
+        let low = f64::floor(note);
+        let t = note - low;
+        let low_cents = self.note_cents(low as i32)?;
+        let cents = if t > 0.0 {
+            low_cents + (self.note_cents(low as i32 + 1)? - low_cents) * t
+        } else {
+            low_cents
+        };
+
+        return Some(self.mapping.reference_frequency * f64::powf(2.0, (cents - reference_cents) / CENTS_PER_OCTAVE));
+    }
+
+    pub fn closest_note(&self, hz: f64) -> Option<i32> {
+        return self.closest_note_where(hz, |_| true);
+    }
+
+    pub fn quantize_note(&self, note: i32) -> Option<i32> {
+        if self.scale_degrees.is_empty() {
+            return match self.note_cents(note) {
+                Some(_) => Some(note),
+                None => None
+            };
+        }
+
+        // Search outwards, preferring the lower note on ties.
+        let range = (self.notes_per_octave() * 2) as i32;
+        for distance in 0..=range {
+            for candidate in [note - distance, note + distance] {
+                if self.in_scale(candidate) {
+                    return Some(candidate);
+                }
+            }
+        }
+
+        return None;
+    }
+
+    pub fn quantize_hz(&self, hz: f64) -> Option<f64> {
+        let note = self.closest_note_where(hz, |note| self.in_scale(note))?;
+
+        return self.note_hz(note as f64);
+    }
+
+    pub fn octave(&self, note: i32) -> i32 {
+        let offset = note - self.mapping.middle_note;
+
+        return offset.div_euclid(self.notes_per_octave() as i32);
+    }
+
+    pub fn note_name(&self, note: i32) -> Option<String> {
+        let degree = self.note_degree(note)?;
+        let degree_in_period = degree.rem_euclid(self.scale.len() as i64) as usize;
+
+        return match self.note_names.get(degree_in_period) {
+            Some(n) => Some(n.clone()),
+            None => Some(degree_in_period.to_string())
+        };
+    }
+
+    fn in_scale(&self, note: i32) -> bool {
+        return match self.note_degree(note) {
+            Some(degree) => {
+                let degree_in_period = degree.rem_euclid(self.scale.len() as i64) as usize;
+                self.scale_degrees.is_empty() || self.scale_degrees.contains(&degree_in_period)
+            },
+            None => false
+        };
+    }
+
+    fn closest_note_where(&self, hz: f64, accept: impl Fn(i32) -> bool) -> Option<i32> {
+        if hz <= 0.0 { return None; }
+
+        let reference_cents = self.note_cents(self.mapping.reference_note)?;
+        let target = reference_cents + CENTS_PER_OCTAVE * f64::log2(hz / self.mapping.reference_frequency);
+
+        // Guess using the average step size, then look around for the best match.
+        let keys = self.notes_per_octave() as f64;
+        let octave_degree = if self.mapping.size == 0 || self.mapping.octave_degree == 0 { self.scale.len() } else { self.mapping.octave_degree };
+        let step = self.scale.degree_cents(octave_degree as i64) / keys;
+        if step <= 0.0 { return None; }
+
+        let middle_cents = self.note_cents(self.mapping.middle_note).unwrap_or(0.0);
+        let guess = self.mapping.middle_note as f64 + f64::round((target - middle_cents) / step);
+        if !guess.is_finite() || guess.abs() > i32::MAX as f64 / 2.0 { return None; }
+        let guess = guess as i32;
+        let range = (self.notes_per_octave() * 2) as i32 + 1;
+
+        let mut closest: Option<(i32, f64)> = None;
+        for note in (guess - range)..=(guess + range) {
+            if !accept(note) { continue; }
+
+            let cents = match self.note_cents(note) {
+                Some(c) => c,
+                None => continue
+            };
+
+            let distance = f64::abs(cents - target);
+            match closest {
+                Some((_, d)) if d <= distance => (),
+                _ => closest = Some((note, distance))
+            }
+        }
+
+        return match closest {
+            Some((note, _)) => Some(note),
+            None => None
+        };
+    }
+}
+
+fn scala_lines(text: &str) -> impl Iterator<Item = &str> {
+    return text.lines().filter(|l| !l.starts_with('!'));
+}
+
+fn parse_scala_pitch(value: &str) -> Result<f64, String> {
+    if value.contains('.') {
+        return match value.parse::<f64>() {
+            Ok(c) => Ok(c),
+            Err(_) => Err(format!("Invalid cents value \"{}\".", value))
+        };
+    }
+
+    let (numerator, denominator) = match value.split_once('/') {
+        Some((n, d)) => (n, d),
+        None => (value, "1")
+    };
+
+    let ratio = match (numerator.parse::<f64>(), denominator.parse::<f64>()) {
+        (Ok(n), Ok(d)) if n > 0.0 && d > 0.0 => n / d,
+        _ => return Err(format!("Invalid ratio \"{}\".", value))
+    };
+
+    return Ok(CENTS_PER_OCTAVE * f64::log2(ratio));
+}
+
+fn parse_kbm_value<T: std::str::FromStr>(value: &str, name: &str) -> Result<T, String> {
+    return match value.parse::<T>() {
+        Ok(v) => Ok(v),
+        Err(_) => Err(format!("Invalid {} \"{}\".", name, value))
+    };
+}
+
+impl LuaUserData for Tuning {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("description", |_, this| Ok(this.scale.description.clone()));
+        fields.add_field_method_get("notes_per_octave", |_, this| Ok(this.notes_per_octave()));
+        fields.add_field_method_get("reference_note", |_, this| Ok(this.mapping.reference_note));
+        fields.add_field_method_get("reference_frequency", |_, this| Ok(this.mapping.reference_frequency));
+        fields.add_field_method_set("reference_frequency", |_, this, hz: f64| {
+            if hz <= 0.0 {
+                return Err(LuaError::runtime(format!("Reference frequency must be positive, got {}.", hz)));
+            }
+
+            this.mapping.reference_frequency = hz;
+            Ok(())
+        });
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("note_hz", |_, this, note: f64| Ok(this.note_hz(note)));
+        methods.add_method("closest_note", |_, this, hz: f64| Ok(this.closest_note(hz)));
+        methods.add_method("closest_frequency", |_, this, hz: f64| {
+            Ok(match this.closest_note(hz) {
+                Some(note) => this.note_hz(note as f64),
+                None => None
+            })
+        });
+        methods.add_method("quantize", |_, this, hz: f64| Ok(this.quantize_hz(hz)));
+        methods.add_method("quantize_note", |_, this, note: f64| Ok(this.quantize_note(f64::round(note) as i32)));
+        methods.add_method("octave", |_, this, note: f64| Ok(this.octave(f64::floor(note) as i32)));
+        methods.add_method("degree", |_, this, note: f64| Ok(this.note_degree(f64::floor(note) as i32)));
+        methods.add_method("note_name", |_, this, note: f64| Ok(this.note_name(f64::floor(note) as i32)));
+
+        methods.add_method_mut("set_scale", |_, this, degrees: Option<Vec<usize>>| {
+            this.set_scale_degrees(degrees.unwrap_or_default());
+            Ok(())
+        });
+        methods.add_method_mut("set_note_names", |_, this, names: Vec<String>| {
+            this.note_names = names;
+            Ok(())
+        });
+        methods.add_method_mut("set_reference", |_, this, (note, hz): (i32, f64)| {
+            this.mapping.reference_note = note;
+            this.mapping.reference_frequency = hz;
+            Ok(())
+        });
+        methods.add_method_mut("equal_temperament", |_, this, notes_per_octave: usize| {
+            this.set_scale(Scale::equal_temperament(notes_per_octave));
+            Ok(())
+        });
+        methods.add_method_mut("load_scl", |_, this, text: String| {
+            match Scale::from_scl(&text) {
+                Ok(scale) => this.set_scale(scale),
+                Err(e) => return Err(LuaError::runtime(format!("Couldn't parse scale: {}", e)))
+            }
+            Ok(())
+        });
+        methods.add_method_mut("load_kbm", |_, this, text: String| {
+            match KeyboardMapping::from_kbm(&text) {
+                Ok(mapping) => this.set_mapping(mapping),
+                Err(e) => return Err(LuaError::runtime(format!("Couldn't parse keyboard mapping: {}", e)))
+            }
+            Ok(())
+        });
+    }
+}
+
+pub const API: [(&str, &str); 15] = [
+    ("TUNING", "Scale and reference pitch used by pitch.*."),
+    ("TUNING:note_hz(note)", "Frequency of a note, fractional notes glide between scale steps."),
+    ("TUNING:closest_note(hz)", "The note closest to hz."),
+    ("TUNING:closest_frequency(hz)", "Frequency of the note closest to hz."),
+    ("TUNING:quantize(hz)", "Snaps hz to the closest note in the active scale degrees."),
+    ("TUNING:quantize_note(note)", "Snaps a note to the active scale degrees."),
+    ("TUNING:octave(note)", "Octave of a note relative to the reference note."),
+    ("TUNING:degree(note)", "Scale degree of a note."),
+    ("TUNING:note_name(note)", "Name of a note."),
+    ("TUNING:set_scale(degrees?)", "Limits quantizing to these degrees, nil for all of them."),
+    ("TUNING:set_note_names(names)", "Names for each degree of the scale."),
+    ("TUNING:set_reference(note, hz)", "Tunes note to hz."),
+    ("TUNING:equal_temperament(notes_per_octave)", "Equal steps per octave, 12 by default."),
+    ("TUNING:load_scl(text)", "Uses a Scala .scl scale, read it with resources.load_text."),
+    ("TUNING:load_kbm(text)", "Uses a Scala .kbm keyboard mapping.")
+];
+
+pub fn register(lua: &Lua) -> LuaResult<()> {
+    return lua.globals().set(LUA_TUNING_KEY, Tuning::new());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 0.0001;
+
+    const PYTHAGOREAN_SCL: &str = "! pythagorean.scl\n\
+        !\n\
+        Pythagorean, 5 notes\n \
+        5\n\
+        !\n \
+        9/8\n \
+        81/64\n \
+        3/2\n \
+        27/16\n \
+        2\n";
+
+    const WHITE_KEYS_KBM: &str = "! white keys only\n\
+        12\n\
+        0\n\
+        127\n\
+        60\n\
+        69\n\
+        440.0\n\
+        7\n\
+        ! mapping\n\
+        0\n\
+        x\n\
+        1\n\
+        x\n\
+        2\n\
+        3\n\
+        x\n\
+        4\n\
+        x\n\
+        5\n\
+        x\n\
+        6\n";
+
+    fn approx(a: f64, b: f64) -> bool {
+        return f64::abs(a - b) < EPSILON;
+    }
+
+    #[test]
+    fn default_tuning_matches_pitch_lua() {
+        let tuning = Tuning::new();
+
+        assert!(approx(tuning.note_hz(48.0).unwrap(), 440.0));
+        assert!(approx(tuning.note_hz(60.0).unwrap(), 880.0));
+        assert!(approx(tuning.note_hz(51.0).unwrap(), 523.2511));
+        assert_eq!(tuning.notes_per_octave(), 12);
+        assert_eq!(tuning.octave(48), 0);
+        assert_eq!(tuning.octave(47), -1);
+    }
+
+    #[test]
+    fn fractional_notes_interpolate() {
+        let tuning = Tuning::new();
+
+        assert!(approx(tuning.note_hz(48.5).unwrap(), 440.0 * f64::powf(2.0, 0.5 / 12.0)));
+    }
+
+    #[test]
+    fn note_names_start_at_first_name() {
+        let tuning = Tuning::new();
+
+        assert_eq!(tuning.note_name(0).unwrap(), "A");
+        assert_eq!(tuning.note_name(3).unwrap(), "C");
+        assert_eq!(tuning.note_name(11).unwrap(), "G#");
+        assert_eq!(tuning.note_name(48).unwrap(), "A");
+        assert_eq!(tuning.note_name(-1).unwrap(), "G#");
+    }
+
+    #[test]
+    fn closest_note_rounds_to_nearest() {
+        let tuning = Tuning::new();
+
+        assert_eq!(tuning.closest_note(440.0), Some(48));
+        assert_eq!(tuning.closest_note(450.0), Some(48));
+        assert_eq!(tuning.closest_note(460.0), Some(49));
+        assert_eq!(tuning.closest_note(220.0), Some(36));
+        assert_eq!(tuning.closest_note(0.0), None);
+    }
+
+    #[test]
+    fn quantizes_to_scale() {
+        let mut tuning = Tuning::new();
+        // A minor: A B C D E F G
+        tuning.set_scale_degrees(vec![0, 2, 3, 5, 7, 8, 10]);
+
+        assert_eq!(tuning.quantize_note(49), Some(48));
+        assert_eq!(tuning.quantize_note(50), Some(50));
+        assert_eq!(tuning.quantize_note(54), Some(53));
+        assert!(approx(tuning.quantize_hz(466.16).unwrap(), 440.0));
+        assert!(approx(tuning.quantize_hz(480.0).unwrap(), tuning.note_hz(50.0).unwrap()));
+    }
+
+    #[test]
+    fn parses_scl() {
+        let scale = Scale::from_scl(PYTHAGOREAN_SCL).unwrap();
+
+        assert_eq!(scale.description, "Pythagorean, 5 notes");
+        assert_eq!(scale.len(), 5);
+        assert!(approx(scale.cents[0], 203.9100));
+        assert!(approx(scale.period(), 1200.0));
+        assert!(approx(scale.degree_cents(-1), 1200.0 * f64::log2(27.0 / 16.0) - 1200.0));
+    }
+
+    #[test]
+    fn parses_scl_cents() {
+        let scale = Scale::from_scl("Quarter tones\n2\n50.0 quarter\n1200.0\n").unwrap();
+
+        assert!(approx(scale.cents[0], 50.0));
+        assert!(approx(scale.period(), 1200.0));
+    }
+
+    #[test]
+    fn rejects_broken_scl() {
+        assert!(Scale::from_scl("").is_err());
+        assert!(Scale::from_scl("Broken\n3\n100.0\n").is_err());
+        assert!(Scale::from_scl("Broken\n1\n-3/2\n").is_err());
+        assert!(Scale::from_scl("Broken\n0\n").is_err());
+    }
+
+    #[test]
+    fn scl_replaces_names_with_degrees() {
+        let mut tuning = Tuning::new();
+        tuning.set_scale(Scale::from_scl(PYTHAGOREAN_SCL).unwrap());
+
+        assert_eq!(tuning.notes_per_octave(), 5);
+        assert_eq!(tuning.note_name(50).unwrap(), "2");
+        assert!(approx(tuning.note_hz(53.0).unwrap(), 880.0));
+        assert!(approx(tuning.note_hz(50.0).unwrap(), 440.0 * 81.0 / 64.0));
+    }
+
+    #[test]
+    fn parses_kbm() {
+        let mapping = KeyboardMapping::from_kbm(WHITE_KEYS_KBM).unwrap();
+
+        assert_eq!(mapping.size, 12);
+        assert_eq!(mapping.middle_note, 60);
+        assert_eq!(mapping.reference_note, 69);
+        assert_eq!(mapping.octave_degree, 7);
+        assert_eq!(mapping.map[0], Some(0));
+        assert_eq!(mapping.map[1], None);
+        assert_eq!(mapping.map[11], Some(6));
+    }
+
+    #[test]
+    fn kbm_maps_white_keys_to_heptatonic_scale() {
+        let mut tuning = Tuning::new();
+        tuning.set_scale(Scale::equal_temperament(7));
+        tuning.set_mapping(KeyboardMapping::from_kbm(WHITE_KEYS_KBM).unwrap());
+
+        assert!(approx(tuning.note_hz(69.0).unwrap(), 440.0));
+        assert!(approx(tuning.note_hz(81.0).unwrap(), 880.0));
+        assert!(tuning.note_hz(61.0).is_none());
+        assert_eq!(tuning.closest_note(440.0), Some(69));
+        assert_eq!(tuning.note_degree(72), Some(7));
+    }
+
+    #[test]
+    fn kbm_limits_note_range() {
+        let mut tuning = Tuning::new();
+        tuning.set_mapping(KeyboardMapping::from_kbm("0\n10\n20\n15\n15\n100.0\n0\n").unwrap());
+
+        assert!(approx(tuning.note_hz(15.0).unwrap(), 100.0));
+        assert!(tuning.note_hz(9.0).is_none());
+        assert!(tuning.note_hz(21.0).is_none());
+    }
+}
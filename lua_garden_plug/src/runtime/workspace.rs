@@ -1,5 +1,13 @@
-use std::{ fs::{self, File}, io::{self, Write} };
-use super::{ library, module_content::ModuleContent };
+use std::{ collections::BTreeMap, fs::{self, File}, io::{self, Write}, path::Path, sync::atomic::{ AtomicU32, Ordering }, time::SystemTime };
+use mlua::prelude::*;
+use super::{ library, manifest::Manifest, module, module_content::ModuleContent };
+
+// Reading the info runs init.lua in a sandbox, which stops it after so many checks of so many instructions.
+const INFO_CHECK_INSTRUCTIONS: u32 = 1000;
+const INFO_MAX_CHECKS: u32 = 1000;
+const INFO_MEMORY_LIMIT: usize = 16 * 1024 * 1024;
+// Base functions that reach the disk or the console, the sandbox leaves out io, os and package altogether.
+const INFO_REMOVED_GLOBALS: [&str; 4] = ["dofile", "loadfile", "load", "print"];
 
 #[derive(Clone, PartialEq)]
 pub struct Workspace {
@@ -7,13 +15,32 @@ pub struct Workspace {
     pub content: ModuleContent
 }
 
+// What the workspace browser shows about a workspace without loading it.
+#[derive(Clone)]
+pub struct WorkspaceInfo {
+    pub path: String,
+    pub name: String,
+    pub authors: String,
+    pub about: String,
+    pub version: Option<String>,
+    pub tags: Vec<String>,
+    pub modified: Option<SystemTime>,
+    pub error: Option<String>
+}
+
 impl Workspace {
     pub fn create_at_path(path: String, content: Option<ModuleContent>) -> Result<Workspace, String> {
-        let content = match content {
+        let mut content = match content {
             Some(c) => c,
             None => library::MODULE_DEFAULT.to_module_content()
         };
 
+        // Every new workspace starts with a manifest, named after its folder.
+        if content.manifest.is_none() {
+            let folder_name = Path::new(&path).file_name().map_or(String::new(), |n| n.to_string_lossy().to_string());
+            content.manifest = Some(Manifest::new(&folder_name));
+        }
+
         match Workspace::create_files(&path, &content) {
             Err(e) => return Err(format!("Failed to create workspace folder: {}", e)),
             Ok(_) => ()
@@ -51,6 +78,77 @@ impl Workspace {
         Ok(())
     }
 
+    // Folders directly inside folder that look like a workspace.
+    pub fn find_in_folder(folder: &str) -> io::Result<Vec<String>> {
+        let mut paths = Vec::new();
+
+        for entry in fs::read_dir(folder)? {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with('.') || !entry.file_type()?.is_dir() {
+                continue;
+            }
+
+            if entry.path().join(library::INIT_PATH).is_file() {
+                paths.push(entry.path().to_string_lossy().to_string());
+            }
+        }
+
+        paths.sort();
+        return Ok(paths);
+    }
+
+    // Reads module.toml, or MODULE_NAME and friends from running init.lua in a sandbox.
+    // Only the workspace's own code runs, without the plugin's API, files or the OS.
+    pub fn read_info(path: String) -> WorkspaceInfo {
+        let mut info = WorkspaceInfo {
+            path: path.clone(),
+            name: Path::new(&path).file_name().map_or(String::new(), |n| n.to_string_lossy().to_string()),
+            authors: String::new(),
+            about: String::new(),
+            version: None,
+            tags: Vec::new(),
+            modified: Workspace::last_modified(&path),
+            error: None
+        };
+
+        let workspace = match Workspace::load_from_path(path.clone()) {
+            Ok(w) => w,
+            Err(e) => {
+                info.error = Some(e);
+                return info;
+            }
+        };
+
+        // A manifest with a name says everything.
+        match &workspace.content.manifest {
+            Some(manifest) if manifest.name.is_some() => {
+                info.name = manifest.name.clone().unwrap_or_default();
+                info.authors = manifest.authors_text().unwrap_or_default();
+                info.about = manifest.about.clone().unwrap_or_default();
+                info.version = manifest.version.clone();
+                info.tags = manifest.tags.clone();
+                return info;
+            },
+            _ => ()
+        }
+
+        let globals = sandboxed_globals(&workspace.content.init);
+        info.name = globals.get(module::LUA_NAME_KEY).cloned().unwrap_or(info.name);
+        info.authors = globals.get(module::LUA_AUTHORS_KEY).cloned().unwrap_or_default();
+        info.about = globals.get(module::LUA_ABOUT_KEY).cloned().unwrap_or_default();
+
+        return info;
+    }
+
+    // The newest change to any of the module's own files.
+    fn last_modified(path: &str) -> Option<SystemTime> {
+        return library::MODULE_PATHS.iter()
+            .chain([library::MANIFEST_PATH].iter())
+            .filter_map(|file| fs::metadata(Path::new(path).join(file)).ok())
+            .filter_map(|metadata| metadata.modified().ok())
+            .max();
+    }
+
     fn create_files(path: &String, content :&ModuleContent) -> io::Result<()> {
         fs::create_dir_all(path)?;
 
@@ -65,16 +163,112 @@ impl Workspace {
         let mut interface_file = File::create(format!("{path}/{file}", path = path, file = library::INTERFACE_PATH))?;
         interface_file.write_all(content.interface.as_bytes())?;
 
+        for (library_path, library) in &content.libraries {
+            let file_path = format!("{path}/{file}", path = path, file = library_path);
+            match Path::new(&file_path).parent() {
+                Some(parent) => fs::create_dir_all(parent)?,
+                None => ()
+            }
+
+            let mut library_file = File::create(file_path)?;
+            library_file.write_all(library.as_bytes())?;
+        }
+
+        match &content.manifest {
+            Some(manifest) => manifest.write(path)?,
+            None => ()
+        }
+
+        Ok(())
+    }
+
+    fn read_files(&mut self) -> Result<(), String> {
+        match self.read_scripts() {
+            Err(e) => return Err(e.to_string()),
+            Ok(_) => ()
+        }
+
+        self.content.manifest = Manifest::read(&self.path)?;
+
         Ok(())
     }
 
-    fn read_files(&mut self) -> io::Result<()> {
+    fn read_scripts(&mut self) -> io::Result<()> {
         self.content.init = fs::read_to_string(format!("{path}/{file}", path = &self.path, file = library::INIT_PATH))?;
         self.content.reset = fs::read_to_string(format!("{path}/{file}", path = &self.path, file = library::RESET_PATH))?;
         self.content.trigger = fs::read_to_string(format!("{path}/{file}", path = &self.path, file = library::TRIGGER_PATH))?;
         self.content.run = fs::read_to_string(format!("{path}/{file}", path = &self.path, file = library::RUN_PATH))?;
         self.content.interface = fs::read_to_string(format!("{path}/{file}", path = &self.path, file = library::INTERFACE_PATH))?;
+        self.content.libraries = library::read_lua_files(&self.path, true)?;
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+// MODULE_NAME and friends after running code in a Lua of its own.
+// Whatever was set before an error still counts, init.lua may well call the API the sandbox doesn't have.
+fn sandboxed_globals(code: &str) -> BTreeMap<String, String> {
+    let mut globals = BTreeMap::new();
+
+    let lua = match sandbox() {
+        Ok(lua) => lua,
+        Err(_) => return globals
+    };
+
+    let _ = lua.load(code).set_name(library::user_chunk_name(library::INIT_PATH)).exec();
+
+    for key in [module::LUA_NAME_KEY, module::LUA_AUTHORS_KEY, module::LUA_ABOUT_KEY] {
+        match lua.globals().raw_get::<LuaValue>(key) {
+            Ok(LuaValue::String(value)) => {
+                globals.insert(String::from(key), value.to_string_lossy());
+            },
+            _ => ()
+        }
+    }
+
+    return globals;
+}
+
+fn sandbox() -> LuaResult<Lua> {
+    let lua = Lua::new_with(LuaStdLib::TABLE | LuaStdLib::STRING | LuaStdLib::MATH | LuaStdLib::UTF8, LuaOptions::new())?;
+    lua.set_memory_limit(INFO_MEMORY_LIMIT)?;
+
+    for name in INFO_REMOVED_GLOBALS {
+        lua.globals().raw_set(name, LuaNil)?;
+    }
+
+    let checks = AtomicU32::new(0);
+    lua.set_hook(LuaHookTriggers::new().every_nth_instruction(INFO_CHECK_INSTRUCTIONS), move |_, _| {
+        if checks.fetch_add(1, Ordering::Relaxed) >= INFO_MAX_CHECKS {
+            return Err(LuaError::runtime("Took too long to read."));
+        }
+
+        Ok(LuaVmState::Continue)
+    });
+
+    return Ok(lua);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_globals_in_a_sandbox() {
+        let init = "VERSION = \"2\";\nMODULE_NAME = \"Crusher \" .. VERSION;\nMODULE_ABOUT = [[\nCrushes bits.]];\nlocal MODULE_AUTHORS = \"Not me\";\nos.remove(\"init.lua\");\nMODULE_AUTHORS = \"Never set\";";
+        let globals = sandboxed_globals(init);
+
+        assert_eq!(globals.get("MODULE_NAME").map(String::as_str), Some("Crusher 2"));
+        assert_eq!(globals.get("MODULE_ABOUT").map(String::as_str), Some("Crushes bits."));
+        assert_eq!(globals.get("MODULE_AUTHORS"), None);
+    }
+
+    #[test]
+    fn stops_init_that_never_ends() {
+        let globals = sandboxed_globals("MODULE_NAME = \"Loop\"\nwhile true do end");
+        assert_eq!(globals.get("MODULE_NAME").map(String::as_str), Some("Loop"));
+
+        let globals = sandboxed_globals("MODULE_NAME = dofile or loadfile or io or os or require or \"Sandboxed\"");
+        assert_eq!(globals.get("MODULE_NAME").map(String::as_str), Some("Sandboxed"));
+    }
+}
@@ -1,10 +1,9 @@
 pub mod consts;
 pub mod runtime;
 pub mod interface;
-pub mod console;
 
 use atomic_float::{ AtomicF32, AtomicF64 };
-use console::ConsoleReceiver;
+use mlem_console::ConsoleReceiver;
 use runtime::{ Runtime };
 use interface::{ Interface };
 use nih_plug::prelude::*;
@@ -100,7 +99,7 @@ impl Plugin for PluginImplementation {
     fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
         let editor_state = self.params.editor_state.clone();
         let params = self.params.clone();
-        let interface = Interface::new();
+        let mut interface = Interface::new();
         
         self.runtime.console = Some(interface.console.create_sender());
         let editor = interface.create_interface(editor_state, params);
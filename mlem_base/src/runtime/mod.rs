@@ -2,7 +2,8 @@ pub mod utils;
 
 use std::{ sync::atomic::Ordering };
 
-use crate::{ PluginImplementationParams, console::ConsoleSender};
+use crate::{ PluginImplementationParams };
+use mlem_console::{ ConsoleSender, LogMessage };
 use nih_plug::{ prelude::* };
 use utils::{ RMS, Timer };
 
@@ -38,7 +39,7 @@ impl Runtime {
         // User code here
 
         let execute_time = execute_timer.elapsed_ms();
-        self.log(format!("Init in {:.2}ms.", execute_time));
+        self.log(LogMessage::new("Init in {:.2}ms.").arg(execute_time as f64));
     }
 
     pub fn reset(&mut self) {
@@ -46,7 +47,7 @@ impl Runtime {
 
         // User code here
 
-        self.log(format!("Reset in {:.2}ms.", execute_timer.elapsed_ms()));
+        self.log(LogMessage::new("Reset in {:.2}ms.").arg(execute_timer.elapsed_ms() as f64));
     }
 
     pub fn run(&mut self, buffer: &mut Buffer, params: &PluginImplementationParams, transport: &Transport) {
@@ -75,13 +76,13 @@ impl Runtime {
         params.run_ms.store(self.run_time.get(), Ordering::Relaxed);
     }
 
-    fn log(&self, message: String) {
+    fn log(&self, message: LogMessage) {
         match &self.console {
             Some(c) => {
                 c.log(message);
             },
             None => {
-                println!("No console exists for Runtime. Log not registered by receiver: {}", message)
+                println!("No console exists for Runtime. Log not registered by receiver: {}", message.format())
             }
         }
     }
@@ -0,0 +1,484 @@
+use std::{ collections::VecDeque, fs, time::SystemTime };
+use crate::{ message::LogMessage, queue::{ self, Consumer, Producer } };
+
+pub const DEFAULT_CAPACITY : usize = 1024;
+pub const MIN_CAPACITY : usize = 64;
+pub const MAX_CAPACITY : usize = 65536;
+// Per sender, what can pile up between two updates of the interface.
+pub const SENDER_SLOTS : usize = 256;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum LogLevel {
+    Info,
+    Warn,
+    Error
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LogSource {
+    // The runtime, on the audio thread.
+    Runtime,
+    // Lua code, through runtime.log, runtime.warn and runtime.error.
+    Module,
+    Interface
+}
+
+// Which entries the console shows.
+#[derive(Clone, PartialEq)]
+pub struct ConsoleFilter {
+    pub min_level: LogLevel,
+    pub runtime: bool,
+    pub module: bool,
+    pub interface: bool,
+    // Case insensitive, matches anywhere in the message.
+    pub search: String
+}
+
+pub struct ConsoleReceiver {
+    entries: VecDeque<ConsoleEntry>,
+    capacity: usize,
+    // Repeats of the newest entry only count up instead of filling the console.
+    pub collapse_repeats: bool,
+
+    // One queue per sender, so each stays single producer.
+    consumers: Vec<Consumer<LogRecord>>,
+
+    log_counter: u32,
+    // Counts up when entries come, go or move, so views know when to filter again.
+    revision: u64,
+    started: SystemTime
+}
+
+// The rows a filter lets through, kept until the entries or the filter change.
+pub struct ConsoleView {
+    filter: Option<ConsoleFilter>,
+    revision: u64,
+    // Entry index and line within it, newest first, so every row has the same height.
+    rows: Vec<(usize, usize)>
+}
+
+// Sends from one thread without locking or allocating, formatting happens in the receiver.
+pub struct ConsoleSender {
+    producer: Producer<LogRecord>
+}
+
+// What goes through the queue.
+#[derive(Clone, Copy)]
+struct LogRecord {
+    level: LogLevel,
+    source: LogSource,
+    time: SystemTime,
+    message: LogMessage
+}
+
+pub struct ConsoleLog {
+    pub message: String,
+    pub level: LogLevel,
+    pub source: LogSource,
+    pub time: SystemTime
+}
+
+pub struct ConsoleEntry {
+    pub log: ConsoleLog,
+    pub number: u32,
+    pub repeats: u32
+}
+
+impl LogLevel {
+    pub fn label(&self) -> &'static str {
+        return match self {
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error"
+        };
+    }
+
+    // The names Lua uses, anything else is info.
+    pub fn from_label(label: &str) -> LogLevel {
+        return match label {
+            "warn" => LogLevel::Warn,
+            "error" => LogLevel::Error,
+            _ => LogLevel::Info
+        };
+    }
+}
+
+impl LogSource {
+    pub fn label(&self) -> &'static str {
+        return match self {
+            LogSource::Runtime => "runtime",
+            LogSource::Module => "module",
+            LogSource::Interface => "interface"
+        };
+    }
+}
+
+impl ConsoleFilter {
+    pub fn new() -> ConsoleFilter {
+        Self {
+            min_level: LogLevel::Info,
+            runtime: true,
+            module: true,
+            interface: true,
+            search: String::new()
+        }
+    }
+
+    pub fn matches(&self, log: &ConsoleLog) -> bool {
+        return self.matches_search(log, &self.search.to_lowercase());
+    }
+
+    // Takes the search already lowercased, so filtering many entries only does that once.
+    fn matches_search(&self, log: &ConsoleLog, search: &str) -> bool {
+        if log.level < self.min_level { return false; }
+
+        let source_shown = match log.source {
+            LogSource::Runtime => self.runtime,
+            LogSource::Module => self.module,
+            LogSource::Interface => self.interface
+        };
+        if !source_shown { return false; }
+
+        return search.is_empty() || log.message.to_lowercase().contains(search);
+    }
+}
+
+impl Default for ConsoleFilter {
+    fn default() -> Self {
+        return ConsoleFilter::new();
+    }
+}
+
+impl ConsoleReceiver {
+    pub fn new() -> ConsoleReceiver {
+        let console = Self {
+            entries: VecDeque::new(),
+            capacity: DEFAULT_CAPACITY,
+            collapse_repeats: true,
+
+            consumers: Vec::new(),
+
+            log_counter: 0,
+            revision: 0,
+            started: SystemTime::now()
+        };
+
+        return console;
+    }
+
+    // Allocates the sender's queue up front.
+    pub fn create_sender(&mut self) -> ConsoleSender {
+        let empty = LogRecord {
+            level: LogLevel::Info,
+            source: LogSource::Runtime,
+            time: SystemTime::UNIX_EPOCH,
+            message: LogMessage::new("")
+        };
+
+        let (producer, consumer) = queue::queue(SENDER_SLOTS, empty);
+        self.consumers.push(consumer);
+
+        return ConsoleSender { producer: producer };
+    }
+
+    // Newest first, as of the last update.
+    pub fn entries(&self) -> &VecDeque<ConsoleEntry> {
+        return &self.entries;
+    }
+
+    // Everything the console holds as text, newest first.
+    pub fn get_log_string(&mut self) -> String {
+        self.update();
+
+        let lines: Vec<String> = self.entries.iter().map(|entry| self.format_entry(entry)).collect();
+        return lines.join("\n");
+    }
+
+    // Only the message, for previews.
+    pub fn get_last_log(&mut self) -> String {
+        self.update();
+
+        return match self.entries.front() {
+            Some(entry) => entry.log.message.clone(),
+            None => String::new()
+        };
+    }
+
+    pub fn capacity(&self) -> usize {
+        return self.capacity;
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.clamp(MIN_CAPACITY, MAX_CAPACITY);
+        self.entries.truncate(self.capacity);
+        self.revision += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.update();
+        self.entries.clear();
+        self.revision += 1;
+    }
+
+    pub fn revision(&self) -> u64 {
+        return self.revision;
+    }
+
+    // [0012 01:02.345 warn module] message (x3), the time counts from when the console started.
+    pub fn format_entry(&self, entry: &ConsoleEntry) -> String {
+        let elapsed = entry.log.time.duration_since(self.started).unwrap_or_default().as_millis();
+        let repeats = match entry.repeats {
+            0 => String::new(),
+            r => format!(" (x{})", r + 1)
+        };
+
+        return format!("[{number:04} {minutes:02}:{seconds:02}.{millis:03} {level} {source}] {message}{repeats}",
+            number = entry.number,
+            minutes = elapsed / 60000,
+            seconds = elapsed / 1000 % 60,
+            millis = elapsed % 1000,
+            level = entry.log.level.label(),
+            source = entry.log.source.label(),
+            message = entry.log.message.trim_end(),
+            repeats = repeats);
+    }
+
+    // Writes what the console holds, oldest first.
+    pub fn export(&mut self, path: &str) -> Result<(), String> {
+        self.update();
+
+        let mut text = String::new();
+        for entry in self.entries.iter().rev() {
+            text += &self.format_entry(entry);
+            text += "\n";
+        }
+
+        return fs::write(path, text).map_err(|e| format!("Couldn't write \"{}\": {}", path, e));
+    }
+
+    pub fn log(&mut self, message: String) {
+        self.add_log(ConsoleLog::new(LogLevel::Info, LogSource::Interface, message));
+    }
+
+    pub fn warn(&mut self, message: String) {
+        self.add_log(ConsoleLog::new(LogLevel::Warn, LogSource::Interface, message));
+    }
+
+    pub fn error(&mut self, message: String) {
+        self.add_log(ConsoleLog::new(LogLevel::Error, LogSource::Interface, message));
+    }
+
+    // Takes in what the senders sent since the last update, returns whether there was anything.
+    pub fn update(&mut self) -> bool {
+        let mut updated = false;
+
+        for index in 0..self.consumers.len() {
+            while let Some(record) = self.consumers[index].pop() {
+                self.add_log(ConsoleLog {
+                    message: record.message.format(),
+                    level: record.level,
+                    source: record.source,
+                    time: record.time
+                });
+                updated = true;
+            }
+
+            let dropped = self.consumers[index].take_dropped();
+            if dropped > 0 {
+                self.add_log(ConsoleLog::new(LogLevel::Warn, LogSource::Interface, format!("Dropped {} logs, they came in faster than the console could keep up.", dropped)));
+                updated = true;
+            }
+        }
+
+        return updated;
+    }
+
+    fn add_log(&mut self, log: ConsoleLog) {
+        let repeated = match self.entries.front_mut() {
+            Some(newest) if self.collapse_repeats && newest.log.message == log.message && newest.log.level == log.level && newest.log.source == log.source => {
+                newest.repeats += 1;
+                newest.log.time = log.time;
+                true
+            },
+            _ => false
+        };
+
+        if repeated { return; }
+
+        let entry = ConsoleEntry {
+            log: log,
+            number: self.log_counter,
+            repeats: 0
+        };
+
+        self.entries.push_front(entry);
+        self.log_counter += 1;
+        self.revision += 1;
+
+        self.entries.truncate(self.capacity);
+    }
+}
+
+impl ConsoleView {
+    pub fn new() -> ConsoleView {
+        Self {
+            filter: None,
+            revision: 0,
+            rows: Vec::new()
+        }
+    }
+
+    // Filters again only when the console or the filter changed since the last call.
+    pub fn rows(&mut self, console: &ConsoleReceiver, filter: &ConsoleFilter) -> &[(usize, usize)] {
+        let current = self.revision == console.revision() && self.filter.as_ref() == Some(filter);
+        if current { return &self.rows; }
+
+        let search = filter.search.to_lowercase();
+        self.rows.clear();
+        for (index, entry) in console.entries().iter().enumerate() {
+            if !filter.matches_search(&entry.log, &search) { continue; }
+
+            let lines = entry.log.message.trim_end().lines().count().max(1);
+            for line in 0..lines {
+                self.rows.push((index, line));
+            }
+        }
+
+        self.filter = Some(filter.clone());
+        self.revision = console.revision();
+        return &self.rows;
+    }
+}
+
+impl Default for ConsoleView {
+    fn default() -> Self {
+        return ConsoleView::new();
+    }
+}
+
+impl Default for ConsoleReceiver {
+    fn default() -> Self {
+        return ConsoleReceiver::new();
+    }
+}
+
+impl ConsoleSender {
+    pub fn log(&self, message: LogMessage) {
+        self.send(LogLevel::Info, LogSource::Runtime, message);
+    }
+
+    // Drops the log when the queue is full, the receiver reports how many.
+    pub fn send(&self, level: LogLevel, source: LogSource, message: LogMessage) {
+        let _ = self.producer.push(LogRecord {
+            level: level,
+            source: source,
+            time: SystemTime::now(),
+            message: message
+        });
+    }
+}
+
+impl ConsoleLog {
+    pub fn new(level: LogLevel, source: LogSource, message: String) -> ConsoleLog {
+        Self {
+            message: message,
+            level: level,
+            source: source,
+            time: SystemTime::now()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collapses_repeated_logs() {
+        let mut console = ConsoleReceiver::new();
+        console.log(String::from("tick"));
+        console.log(String::from("tick"));
+        console.warn(String::from("tick"));
+
+        let entries = console.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].repeats, 1);
+
+        console.collapse_repeats = false;
+        console.warn(String::from("tick"));
+        assert_eq!(console.entries().len(), 3);
+    }
+
+    #[test]
+    fn filters_by_level_source_and_search() {
+        let mut filter = ConsoleFilter::new();
+        let log = ConsoleLog::new(LogLevel::Warn, LogSource::Module, String::from("Filter Blew Up"));
+        assert!(filter.matches(&log));
+
+        filter.search = String::from("blew");
+        assert!(filter.matches(&log));
+
+        filter.min_level = LogLevel::Error;
+        assert!(!filter.matches(&log));
+
+        filter.min_level = LogLevel::Info;
+        filter.module = false;
+        assert!(!filter.matches(&log));
+    }
+
+    #[test]
+    fn filters_again_only_on_changes() {
+        let mut console = ConsoleReceiver::new();
+        let mut view = ConsoleView::new();
+        let mut filter = ConsoleFilter::new();
+        console.log(String::from("one\ntwo"));
+        console.warn(String::from("Three"));
+
+        assert_eq!(view.rows(&console, &filter), &[(0, 0), (1, 0), (1, 1)]);
+
+        filter.search = String::from("THREE");
+        assert_eq!(view.rows(&console, &filter), &[(0, 0)]);
+
+        console.warn(String::from("Three"));
+        assert_eq!(console.entries()[0].repeats, 1);
+        assert_eq!(view.rows(&console, &filter), &[(0, 0)]);
+
+        console.error(String::from("three again"));
+        assert_eq!(view.rows(&console, &filter), &[(0, 0), (1, 0)]);
+
+        console.clear();
+        assert!(view.rows(&console, &filter).is_empty());
+    }
+
+    #[test]
+    fn formats_what_senders_sent() {
+        let mut console = ConsoleReceiver::new();
+        let sender = console.create_sender();
+
+        for _ in 0..SENDER_SLOTS + 3 {
+            sender.send(LogLevel::Error, LogSource::Module, LogMessage::new("Took {:.1}ms").arg(2.25));
+        }
+
+        assert!(console.update());
+        let entries = console.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].log.level, LogLevel::Warn);
+        assert_eq!(entries[1].log.message, "Took 2.2ms");
+        assert_eq!(entries[1].repeats as usize, SENDER_SLOTS - 1);
+
+        assert!(!console.update());
+    }
+
+    #[test]
+    fn keeps_to_capacity() {
+        let mut console = ConsoleReceiver::new();
+        console.set_capacity(0);
+
+        for i in 0..MIN_CAPACITY + 5 {
+            console.log(format!("{}", i));
+        }
+
+        assert_eq!(console.entries().len(), MIN_CAPACITY);
+        assert_eq!(console.entries()[0].log.message, format!("{}", MIN_CAPACITY + 4));
+    }
+}
@@ -0,0 +1,6 @@
+mod console;
+mod message;
+pub mod queue;
+
+pub use console::*;
+pub use message::*;
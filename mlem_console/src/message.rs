@@ -0,0 +1,131 @@
+use std::fmt::Write;
+
+// Lua errors with a traceback fit, longer text is cut and marked.
+pub const TEXT_BYTES: usize = 1024;
+pub const MAX_ARGS: usize = 4;
+
+const TEXT_PLACEHOLDER: &str = "text";
+const PRECISION_PREFIX: &str = ":.";
+const MISSING_ARG: &str = "?";
+const CUT_MARKER: &str = "…";
+
+// A log that fits in a queue slot, so the audio thread can send it without allocating.
+// The template is static, "{}" and "{:.2}" take the numbers in order and "{text}" takes the text.
+// Turning it into a string is left to the receiver.
+#[derive(Clone, Copy)]
+pub struct LogMessage {
+    template: &'static str,
+    args: [f64; MAX_ARGS],
+    arg_count: usize,
+    text: [u8; TEXT_BYTES],
+    text_len: usize,
+    text_cut: bool
+}
+
+impl LogMessage {
+    pub fn new(template: &'static str) -> LogMessage {
+        Self {
+            template: template,
+            args: [0.0; MAX_ARGS],
+            arg_count: 0,
+            text: [0; TEXT_BYTES],
+            text_len: 0,
+            text_cut: false
+        }
+    }
+
+    // Arguments past MAX_ARGS are ignored.
+    pub fn arg(mut self, value: f64) -> LogMessage {
+        if self.arg_count < MAX_ARGS {
+            self.args[self.arg_count] = value;
+            self.arg_count += 1;
+        }
+
+        return self;
+    }
+
+    // Cut at TEXT_BYTES, on a character boundary, formatting marks where.
+    pub fn text(mut self, text: &str) -> LogMessage {
+        let mut len = text.len().min(TEXT_BYTES);
+        while !text.is_char_boundary(len) {
+            len -= 1;
+        }
+
+        self.text[..len].copy_from_slice(&text.as_bytes()[..len]);
+        self.text_len = len;
+        self.text_cut = len < text.len();
+
+        return self;
+    }
+
+    pub fn format(&self) -> String {
+        let mut formatted = String::new();
+        let mut rest = self.template;
+        let mut arg = 0;
+
+        while let Some(start) = rest.find('{') {
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => break
+            };
+
+            formatted.push_str(&rest[..start]);
+            let placeholder = &rest[start + 1..end];
+
+            match placeholder {
+                TEXT_PLACEHOLDER => {
+                    formatted.push_str(self.text_str());
+                    if self.text_cut {
+                        formatted.push_str(CUT_MARKER);
+                    }
+                },
+                _ => {
+                    let precision = placeholder.strip_prefix(PRECISION_PREFIX).and_then(|p| p.parse::<usize>().ok());
+                    let _ = match (self.args[..self.arg_count].get(arg), precision) {
+                        (Some(value), Some(precision)) => write!(formatted, "{:.*}", precision, value),
+                        (Some(value), None) => write!(formatted, "{}", value),
+                        (None, _) => write!(formatted, "{}", MISSING_ARG)
+                    };
+                    arg += 1;
+                }
+            }
+
+            rest = &rest[end + 1..];
+        }
+
+        formatted.push_str(rest);
+        return formatted;
+    }
+
+    fn text_str(&self) -> &str {
+        return std::str::from_utf8(&self.text[..self.text_len]).unwrap_or("");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_in_the_template() {
+        let message = LogMessage::new("Ran {text} in {:.2}ms at {}hz, {} {missing").text("run.lua").arg(1.23456).arg(48000.0);
+        assert_eq!(message.format(), "Ran run.lua in 1.23ms at 48000hz, ? {missing");
+    }
+
+    #[test]
+    fn cuts_text_on_a_character_boundary() {
+        let long = format!("{}é", "a".repeat(TEXT_BYTES - 1));
+        let message = LogMessage::new("{text}").text(&long);
+
+        assert_eq!(message.format(), format!("{}…", "a".repeat(TEXT_BYTES - 1)));
+    }
+
+    #[test]
+    fn only_marks_text_that_was_cut() {
+        let exact = "a".repeat(TEXT_BYTES);
+        assert_eq!(LogMessage::new("{text}.").text(&exact).format(), format!("{}.", exact));
+
+        let long = "a".repeat(TEXT_BYTES + 1);
+        assert_eq!(LogMessage::new("{text}.").text(&long).format(), format!("{}….", exact));
+    }
+}
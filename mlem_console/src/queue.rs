@@ -0,0 +1,132 @@
+use std::{ cell::{ Cell, UnsafeCell }, marker::PhantomData, sync::{ atomic::{ AtomicU64, AtomicUsize, Ordering }, Arc } };
+
+// A single producer, single consumer ring of preallocated slots.
+// Pushing never blocks or allocates, a full queue drops the value and counts it.
+struct Queue<T> {
+    slots: Box<[UnsafeCell<T>]>,
+    // Only ever go up, the slot is the index modulo the capacity.
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    dropped: AtomicU64
+}
+
+// The producer only writes slots the consumer is done with and the other way around, see push and pop.
+unsafe impl<T: Send> Sync for Queue<T> {}
+
+pub struct Producer<T> {
+    queue: Arc<Queue<T>>,
+    // Not Sync, so only one thread at a time can push, which keeps it single producer.
+    _not_sync: PhantomData<Cell<()>>
+}
+
+pub struct Consumer<T> {
+    queue: Arc<Queue<T>>
+}
+
+// Slots start out as copies of empty, so nothing gets allocated later.
+pub fn queue<T: Copy + Send>(capacity: usize, empty: T) -> (Producer<T>, Consumer<T>) {
+    let slots: Vec<UnsafeCell<T>> = (0..capacity.max(1)).map(|_| UnsafeCell::new(empty)).collect();
+    let queue = Arc::new(Queue {
+        slots: slots.into_boxed_slice(),
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+        dropped: AtomicU64::new(0)
+    });
+
+    let producer = Producer {
+        queue: queue.clone(),
+        _not_sync: PhantomData
+    };
+
+    return (producer, Consumer { queue: queue });
+}
+
+impl<T: Copy + Send> Producer<T> {
+    // Returns false when the queue was full and the value was dropped.
+    pub fn push(&self, value: T) -> bool {
+        let queue = &self.queue;
+        let head = queue.head.load(Ordering::Relaxed);
+        let tail = queue.tail.load(Ordering::Acquire);
+
+        if head.wrapping_sub(tail) >= queue.slots.len() {
+            queue.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+
+        // The consumer doesn't read this slot until head moves past it.
+        unsafe { *queue.slots[head % queue.slots.len()].get() = value; }
+        queue.head.store(head.wrapping_add(1), Ordering::Release);
+
+        return true;
+    }
+}
+
+impl<T: Copy + Send> Consumer<T> {
+    pub fn pop(&mut self) -> Option<T> {
+        let queue = &self.queue;
+        let tail = queue.tail.load(Ordering::Relaxed);
+        let head = queue.head.load(Ordering::Acquire);
+
+        if tail == head { return None; }
+
+        // The producer doesn't write this slot again until tail moves past it.
+        let value = unsafe { *queue.slots[tail % queue.slots.len()].get() };
+        queue.tail.store(tail.wrapping_add(1), Ordering::Release);
+
+        return Some(value);
+    }
+
+    // How many values were dropped since the last call.
+    pub fn take_dropped(&mut self) -> u64 {
+        return self.queue.dropped.swap(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_and_counts_when_full() {
+        let (producer, mut consumer) = queue(2, 0);
+
+        assert!(producer.push(1));
+        assert!(producer.push(2));
+        assert!(!producer.push(3));
+        assert_eq!(consumer.take_dropped(), 1);
+
+        assert_eq!(consumer.pop(), Some(1));
+        assert!(producer.push(4));
+        assert_eq!(consumer.pop(), Some(2));
+        assert_eq!(consumer.pop(), Some(4));
+        assert_eq!(consumer.pop(), None);
+        assert_eq!(consumer.take_dropped(), 0);
+    }
+
+    #[test]
+    fn keeps_order_across_threads() {
+        let (producer, mut consumer) = queue(16, 0u32);
+
+        let thread = std::thread::spawn(move || {
+            let mut value = 0;
+            while value < 10000 {
+                if producer.push(value) {
+                    value += 1;
+                }
+            }
+        });
+
+        let mut expected = 0;
+        while expected < 10000 {
+            match consumer.pop() {
+                Some(value) => {
+                    assert_eq!(value, expected);
+                    expected += 1;
+                },
+                None => std::thread::yield_now()
+            }
+        }
+
+        thread.join().unwrap();
+    }
+}
@@ -3,7 +3,8 @@ pub mod utils;
 use core::fmt;
 use std::{ fmt::Error, sync::atomic::Ordering };
 
-use crate::{ PluginImplementationParams, console::ConsoleSender};
+use crate::{ PluginImplementationParams };
+use mlem_console::{ ConsoleSender, LogMessage };
 use nih_plug::{ prelude::* };
 use utils::{ RMS, Timer };
 use ebur128::{ EbuR128, Mode };
@@ -57,7 +58,7 @@ impl Runtime {
         let execute_timer = Timer::new();
         let execute_time = execute_timer.elapsed_ms();
         
-        self.log(format!("Init in {:.2}ms.", execute_time));
+        self.log(LogMessage::new("Init in {:.2}ms.").arg(execute_time as f64));
     }
 
     pub fn reset(&mut self) {
@@ -65,10 +66,10 @@ impl Runtime {
 
         match self.reset_meter() {
             Ok(()) => (),
-            Err(e) => self.log(format!("Failed to reset meter: {}", e))
+            Err(e) => self.log(LogMessage::new("Failed to reset meter: {text}").text(&e.to_string()))
         }
 
-        self.log(format!("Reset in {:.2}ms.", execute_timer.elapsed_ms()));
+        self.log(LogMessage::new("Reset in {:.2}ms.").arg(execute_timer.elapsed_ms() as f64));
     }
 
     pub fn run(&mut self, buffer: &mut Buffer, params: &PluginImplementationParams, transport: &Transport) {
@@ -80,7 +81,7 @@ impl Runtime {
             match self.reset_meter() {
                 Ok(()) => (),
                 Err(e) => {
-                    self.log(format!("Failed to reset meter: {}", e));
+                    self.log(LogMessage::new("Failed to reset meter: {text}").text(&e.to_string()));
                 }
             }
         }
@@ -89,7 +90,7 @@ impl Runtime {
         if params.reset_meter.load(Ordering::Relaxed) {
             match self.reset_meter() {
                 Ok(()) => (),
-                Err(e) => self.log(format!("Couldn't refresh EbuR128: {}", e))
+                Err(e) => self.log(LogMessage::new("Couldn't refresh EbuR128: {text}").text(&e.to_string()))
             }
 
             params.reset_meter.store(false, Ordering::Relaxed);
@@ -98,7 +99,7 @@ impl Runtime {
         match self.run_ebur128(buffer) {
             Ok(()) => (),
             Err(e) => {
-                self.log(format!("Failed to run EbuR128: {}", e));
+                self.log(LogMessage::new("Failed to run EbuR128: {text}").text(&e.to_string()));
             }
         }
 
@@ -130,7 +131,7 @@ impl Runtime {
                         ebur128.add_frames_f32(samples)?;
                     },
                     None => {
-                        self.log(format!("Could not get samples from block."));
+                        self.log(LogMessage::new("Could not get samples from block."));
                     }
                 };
             }
@@ -164,13 +165,13 @@ impl Runtime {
         Ok(())
     }
 
-    fn log(&self, message: String) {
+    fn log(&self, message: LogMessage) {
         match &self.console {
             Some(c) => {
                 c.log(message);
             },
             None => {
-                println!("No console exists for Runtime. Log not registered by receiver: {}", message)
+                println!("No console exists for Runtime. Log not registered by receiver: {}", message.format())
             }
         }
     }